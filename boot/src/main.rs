@@ -5,7 +5,7 @@ extern crate alloc;
 
 use alloc::vec::Vec;
 
-use alloc::string::String;
+use core::fmt::Write;
 
 // These use statements should be collated together
 use log::info;
@@ -17,20 +17,10 @@ use uefi::proto::media::file::{File, FileInfo};
 
 use uefi::proto::media::file::FileHandle;
 
-use uefi::proto::console::gop::{PixelFormat, FrameBuffer, GraphicsOutput};
+use uefi::proto::console::gop::GraphicsOutput;
 
 // My crates
-mod bdf_loader;
-
-use bdf_loader::load_bdf_to_mono_font;
-
-use graphics::{CPUFrameBuffer, TextBuffer};
-
-const RED: u32 = 0xff00000;
-const GREEN: u32 = 0xff00;
-const BLUE: u32 = 0xff;
-const BLACK: u32 = 0;
-const WHITE: u32 = 0xffffff;
+use graphics::{Color, Console, FramebufferTarget, GopFramebuffer, GraphicsError, RawFramebuffer};
 
 /// Reads a file (passed as a handle) to an owned heap array and returns it
 fn read_file_from_handle(file_handle: FileHandle) -> Result<Vec<u8>, Status>{
@@ -59,27 +49,20 @@ fn read_file_from_handle(file_handle: FileHandle) -> Result<Vec<u8>, Status>{
     return Ok(file_ram_location);
 }
 
-fn fill_frame_buffer(frame_buffer: &mut FrameBuffer, pixel: u32, height: usize, stride: usize){
-    for y in 0..height {
-        let offset = y * stride;
-        for x in 0..stride {
+fn fill_frame_buffer(target: &mut impl FramebufferTarget, color: Color){
+    let format = target.format();
+    for y in 0..target.height() {
+        for x in 0..target.width() {
             unsafe {
-                (frame_buffer.as_mut_ptr() as *mut u32)
-                .add(offset + x)
-                .write_volatile(pixel)
+                format.write(
+                    target.address().add(y * target.pitch() + x * format.bytes_per_pixel()),
+                    color
+                )
             }
         }
     }
 }
 
-/*unsafe*/ fn print(message: &str, text_buffer: &mut TextBuffer, cpu_frame_buffer: &mut CPUFrameBuffer, frame_buffer_ptr: *mut u8, mono_font: &graphics::MonoFont)
-    -> Result<(), &'static str> {
-    text_buffer.write_str(message)?;
-    text_buffer.write_pixels(cpu_frame_buffer, mono_font, (20,20))?;
-    cpu_frame_buffer.flush(frame_buffer_ptr as *mut u32);
-    Ok(())
-}
-
 #[entry]
 fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     uefi_services::init(&mut system_table).unwrap();
@@ -132,18 +115,15 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
 
     info!("Loading the bdf file into a MonoFont struct");
 
-    let mono_font = match load_bdf_to_mono_font(
+    let mono_font = match graphics::bdf::parse(
         match alloc::str::from_utf8(font_bdf.as_slice()) {
             Ok(string) => string,
             Err(_) => return Status::ABORTED
         }
     ) {
-        Ok(font) => font,
-        // I'm not entirely sure whether I need to do this since err_msg is &'static str so maybe I
-        // can just put it in info!(err_msg), but even if that's fine it should be the same outputted
-        // binary so it doesn't really matter
-        Err(err_msg) => {
-            info!("{err_msg}");
+        Ok(bdf) => bdf.font,
+        Err(err) => {
+            info!("Couldn't load the font: {err}");
             system_table.boot_services().stall(3_000_000);
             return Status::ABORTED;
         }
@@ -165,11 +145,6 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
         Err(err) => return err.status()
     };
 
-    let mut mode = match gop_protocol.query_mode(0, system_table.boot_services()) {
-        Ok(mode) => mode,
-        Err(err) => return err.status()
-    };
-
     // On my laptop the pixel format is either RGB or BGR, and I can't be bothered checking. Since
     // I initially only support black and white there's no reason to check anyway, it just can't be
     // one of the special formats
@@ -177,58 +152,29 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     // black, presumably the reserved bit) this implies that the pixel format is one byte of
     // reserved memory, and one byte each for red, green and blue, if my understanding of the
     // possible formats is correct
-    match mode.info().pixel_format() {
-        PixelFormat::Bitmask | PixelFormat::BltOnly => return Status::ABORTED,
-        _ => {}
-    }
-
-    let mut frame_buffer = gop_protocol.frame_buffer(); 
-    
-    let (width, height) = mode.info().resolution();
-    let stride = mode.info().stride();
-    
-    let mut cpu_frame_buffer = CPUFrameBuffer {
-        width: width, 
-        height: height, 
-        stride: stride,
-        buffer: {
-            let mut buf = Vec::with_capacity(stride*height);
-            buf.resize(stride*height, 0u32);
-            buf.into_boxed_slice()
-        }
+    // (eta 2: it does get checked now, GopFramebuffer works out the format and the CPUFrameBuffer
+    // converts to it on flush. BltOnly means there's no framebuffer at all so that still fails)
+    let mut target = match GopFramebuffer::new(&mut gop_protocol) {
+        Ok(target) => target,
+        Err(_) => return Status::ABORTED
     };
-    
-    let mut text_buffer = match TextBuffer::new(
-        // Dimensions of text buffer in glyphs, with -2 to account for the padding
-        height / mono_font.height - 2,
-        width / mono_font.width - 2,
-        {
-            let mut tmp = String::with_capacity(
-                (height / mono_font.height - 2) * (width / mono_font.width - 2)
-            );
 
-            for _ in 0..(height / mono_font.height - 2) * (width / mono_font.width - 2) {
-                tmp.push(' ');
-            }
+    // The console keeps drawing to the GOP framebuffer after this, but target isn't used to draw
+    // anything else (apart from filling the screen if the console can't be made) and the
+    // framebuffer stays put after exiting boot services
+    let raw_target = unsafe { RawFramebuffer::from_target(&target) };
 
-            tmp.into_boxed_str()
-        }
-    ) {
-        Ok(buffer) => buffer,
-        Err(msg) => {
-            for x in 0..stride {
-                for y in 0..height {
-                    unsafe {
-                        (frame_buffer.as_mut_ptr() as *mut u32)
-                        .add(y * stride + x)
-                        .write_volatile(match msg {
-                            "The provided string must be ascii" => RED,
-                            "The provided string must have length equal to height * width" => BLUE,
-                            _ => GREEN
-                        })
-                    }
+    let mut console = match Console::new(raw_target, mono_font, (20, 20)) {
+        Ok(console) => console,
+        Err(err) => {
+            fill_frame_buffer(
+                &mut target,
+                match err {
+                    // Too small for any text (or for the padding)
+                    GraphicsError::BadDimensions => Color::RED,
+                    _ => Color::GREEN
                 }
-            }
+            );
             system_table.boot_services().stall(4_000_000);
             return Status::ABORTED;
         }
@@ -237,11 +183,7 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     let kernel_path = match CStr16::from_str_with_buf("kernel.elf", &mut CStr16_buffer) {
         Ok(string) => string,
         Err(_) => {
-            print("Couldn't convert rust str to CStr16\n",
-                &mut text_buffer,
-                &mut cpu_frame_buffer,
-                frame_buffer.as_mut_ptr(),
-                &mono_font);
+            let _ = writeln!(console, "\x1b[31mCouldn't convert rust str to CStr16\x1b[0m");
             system_table.boot_services().stall(3_000_000);
             return Status::ABORTED
         }
@@ -255,11 +197,7 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     ) {
         Ok(handle) => handle,
         Err(err) => {
-            print("Failed to open a file at /kernel.elf\n",
-                &mut text_buffer,
-                &mut cpu_frame_buffer,
-                frame_buffer.as_mut_ptr(),
-                &mono_font);
+            let _ = writeln!(console, "\x1b[31mFailed to open a file at /kernel.elf\x1b[0m");
             system_table.boot_services().stall(3_000_000);
             return err.status() 
         }
@@ -268,22 +206,15 @@ fn main(image_handle: Handle, mut system_table: SystemTable<Boot>) -> Status {
     let kernel = match read_file_from_handle(kernel_handle) {
         Ok(kernel) => kernel,
         Err(status) => {
-            print("Failed to read the data at the file handle into memory\n",
-                &mut text_buffer,
-                &mut cpu_frame_buffer,
-                frame_buffer.as_mut_ptr(),
-                &mono_font);
+            let _ = writeln!(console, "\x1b[31mFailed to read the data at the file handle into memory\x1b[0m");
             system_table.boot_services().stall(3_000_000);
             return status 
         }
     };
 
-    print("Loaded kernel file into memory, now it needs to be loaded as an elf file.",
-        &mut text_buffer,
-        &mut cpu_frame_buffer,
-        frame_buffer.as_mut_ptr(),
-        &mono_font);
-    text_buffer.dbg_print_cursor();
+    let _ = write!(console, "Loaded kernel file into memory ({} bytes), now it needs to be loaded as an elf file.", kernel.len());
+    let _ = console.text().dbg_print_cursor();
+    let _ = console.refresh();
     system_table.boot_services().stall(3_000_000);
     Status::SUCCESS
 }
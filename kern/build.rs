@@ -1,4 +1,42 @@
+use std::fmt::Write;
+use std::path::Path;
+
+/// The font the kernel's console uses. It gets turned into a StaticFont here so the kernel
+/// doesn't have to parse anything (or allocate) to print
+const FONT: &str = "../spleen/spleen-16x32.bdf";
+
 fn main() {
     println!("cargo:rustc-link-arg=-Tkern/linker.ld");
+    println!("cargo:rustc-link-arg=--build-id=sha1");
     //println!("cargo:rustc-link-arg=-T/home/leastinformednerd/Documents/code/regulome/kern/linker.ld");
+
+    println!("cargo:rerun-if-changed={FONT}");
+
+    let source = std::fs::read_to_string(FONT).expect("Couldn't read the console font");
+    let font = graphics::bdf::parse(&source).expect("Couldn't parse the console font").font;
+
+    let mut characters = String::new();
+    let mut bitmaps = String::new();
+
+    for character in font.characters() {
+        write!(characters, "'\\u{{{:x}}}',", character as u32).unwrap();
+
+        for byte in font.glyph(character).bits() {
+            write!(bitmaps, "{byte},").unwrap();
+        }
+    }
+
+    let replacement = font.replacement().bits().iter()
+        .map(|byte| byte.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let generated = format!(
+        "pub static FONT: graphics::StaticFont = graphics::StaticFont::new({}, {}, &[{characters}], &[{bitmaps}], &[{replacement}]);\n",
+        font.width,
+        font.height
+    );
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(Path::new(&out_dir).join("font.rs"), generated).expect("Couldn't write the generated font");
 }
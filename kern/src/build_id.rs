@@ -0,0 +1,31 @@
+use elf::inspect::abi::NT_GNU_BUILD_ID;
+use elf::inspect::BuildId;
+
+extern "C" {
+    static __build_id_start: u8;
+    static __build_id_end: u8;
+}
+
+/// Reads the kernel's own build-id out of the note the linker script keeps at the start of
+/// rodata. There's no whole file in memory to give an Inspector, so the note is read directly
+pub fn build_id() -> Option<BuildId<'static>> {
+    let note: &'static [u8] = unsafe {
+        let start = core::ptr::addr_of!(__build_id_start);
+        let end = core::ptr::addr_of!(__build_id_end);
+        core::slice::from_raw_parts(start, end as usize - start as usize)
+    };
+
+    let word = |offset: usize| note.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+
+    let name_size = word(0)? as usize;
+    let desc_size = word(4)? as usize;
+
+    if word(8)? as u64 != NT_GNU_BUILD_ID || note.get(12..12 + name_size)? != b"GNU\0" {
+        return None
+    }
+
+    // The name is padded out to 4 bytes
+    let desc_start = 12 + name_size.next_multiple_of(4);
+    note.get(desc_start..desc_start + desc_size).map(BuildId)
+}
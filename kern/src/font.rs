@@ -0,0 +1,4 @@
+//! The console font, turned into a StaticFont by build.rs so there's nothing to load or parse and
+//! it can be used before the heap exists
+
+include!(concat!(env!("OUT_DIR"), "/font.rs"));
@@ -0,0 +1,17 @@
+use linked_list_allocator::LockedHeap;
+
+/// Big enough for a CPUFrameBuffer the size of a 4K screen with room to spare. This lives in .bss
+/// until there's a real physical memory manager in the kernel to get frames from
+const HEAP_SIZE: usize = 48 * 1024 * 1024;
+
+static mut HEAP: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+/// Has to be called before anything is allocated
+pub fn init() {
+    unsafe {
+        ALLOCATOR.lock().init(core::ptr::addr_of_mut!(HEAP).cast(), HEAP_SIZE);
+    }
+}
@@ -0,0 +1,36 @@
+use graphics::{ConsoleLogger, StaticFont};
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::serial_println;
+
+/// The framebuffer half of the logger. It stays empty until the heap is set up (a Console needs
+/// one), and until then everything only goes to serial
+pub static CONSOLE: ConsoleLogger<&'static StaticFont> = ConsoleLogger::new();
+
+static LOGGER: KernelLogger = KernelLogger;
+
+/// Sends every record to serial (which always works) and to the framebuffer console (when there
+/// is one)
+struct KernelLogger;
+
+impl Log for KernelLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        serial_println!("[{:<5}] {}: {}", record.level(), record.target(), record.args());
+        CONSOLE.log(record);
+    }
+
+    fn flush(&self) {
+        CONSOLE.flush();
+    }
+}
+
+/// Makes the log macros work. Only the first call does anything
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
@@ -1,16 +1,59 @@
 #![no_std]
 #![no_main]
 
+extern crate alloc;
+
+mod build_id;
+mod font;
+mod heap;
+mod logger;
+#[cfg(feature = "screenshot")]
+mod screenshot;
+mod serial;
+
+use core::fmt::Write;
+
+use graphics::{Color, Console, DirectConsole, RawFramebuffer};
+use log::{error, info};
+
 #[used]
 #[link_section = ".requests"]
 pub static BASE_REVISION: limine::BaseRevision = limine::BaseRevision::new();
 
+/// How many lines that have scrolled off the console are kept to look back at
+const SCROLLBACK_LINES: usize = 1000;
+
 #[used]
 #[link_section = ".requests"]
 static FRAMEBUFFER_REQUEST: limine::request::FramebufferRequest = limine::request::FramebufferRequest::new();
 
 #[panic_handler]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    // Whatever panicked might have been halfway through printing something
+    unsafe {
+        serial::SERIAL.force_unlock();
+        logger::CONSOLE.force_unlock();
+    }
+
+    serial_println!("kernel panic: {}", info);
+    match build_id::build_id() {
+        Some(build_id) => serial_println!("build-id: {}", build_id),
+        None => serial_println!("build-id: none")
+    }
+
+    // Drawn straight to the framebuffer, since the heap (which the logger's console needs) might
+    // be what's broken. The font is a static so this doesn't need anything to have been set up
+    if let Some(mut framebuffer) = FRAMEBUFFER_REQUEST.get_response()
+        .and_then(|response| response.framebuffers().next()) {
+        let mut console = DirectConsole::new(&mut framebuffer, &font::FONT);
+        console.foreground = Color::RED;
+        let _ = writeln!(console, "kernel panic: {}", info);
+
+        if let Some(build_id) = build_id::build_id() {
+            let _ = writeln!(console, "build-id: {}", build_id);
+        }
+    }
+
     loop {}
 }
 
@@ -18,16 +61,32 @@ fn panic(_info: &core::panic::PanicInfo) -> ! {
 pub extern "C" fn _start() -> ! {
     assert!(BASE_REVISION.is_supported());
 
-    if let Some(framebuffer_response) = FRAMEBUFFER_REQUEST.get_response() {
-        if let Some(framebuffer) = framebuffer_response.framebuffers().next() {
-            for i in 0..500_u64 {
-                // Calculate the pixel offset using the framebuffer information we obtained above.
-                // We skip `i` scanlines (pitch is provided in bytes) and add `i * 4` to skip `i` pixels forward.
-                let pixel_offset = i * framebuffer.pitch() + i * 4;
+    logger::init();
+
+    match build_id::build_id() {
+        Some(build_id) => info!("regulome kernel, build-id {}", build_id),
+        None => info!("regulome kernel, no build-id")
+    }
+
+    heap::init();
+
+    if let Some(framebuffer) = FRAMEBUFFER_REQUEST.get_response()
+        .and_then(|response| response.framebuffers().next()) {
+        // Limine's framebuffer is never moved or handed to anything else
+        let target = unsafe { RawFramebuffer::from_target(&framebuffer) };
+
+        match Console::new(target, &font::FONT, (8, 8)) {
+            Ok(mut console) => {
+                // Enough to look back through everything from boot. Even on a 4K screen this is
+                // only a few megabytes of the heap
+                console.text().set_scrollback(SCROLLBACK_LINES);
+                logger::CONSOLE.set_console(console);
+                info!("Framebuffer console is up");
 
-                // Write 0xFFFFFFFF to the provided pixel offset to fill it white.
-                unsafe { *(framebuffer.addr().add(pixel_offset as usize) as *mut u32) = 0x0000FFFF };
-            }
+                #[cfg(feature = "screenshot")]
+                screenshot::send();
+            },
+            Err(msg) => error!("Couldn't make a console on the framebuffer: {}", msg)
         }
     }
 
@@ -0,0 +1,25 @@
+use crate::logger::CONSOLE;
+use crate::serial::SERIAL;
+use crate::serial_println;
+
+/// Sends what's on the console over serial as a PPM. It's framed by a line giving its length and
+/// a line after it, so whatever is reading the serial output (with QEMU that's normally a file)
+/// can cut it out of the log around it:
+///
+/// ```text
+/// screenshot: ppm <length in bytes>
+/// <the file>
+/// screenshot end
+/// ```
+pub fn send() {
+    CONSOLE.with_console(|console| {
+        let frame_buffer = console.frame_buffer();
+
+        let mut length = 0;
+        frame_buffer.write_ppm(|bytes| length += bytes.len());
+
+        serial_println!("screenshot: ppm {}", length);
+        frame_buffer.write_ppm(|bytes| SERIAL.lock().write_bytes(bytes));
+        serial_println!("\nscreenshot end");
+    });
+}
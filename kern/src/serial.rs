@@ -0,0 +1,95 @@
+use core::fmt;
+
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3f8;
+
+/// A 16550 UART, which is what every emulator (and most real machines) has on COM1. This is the
+/// only output the kernel has until there's a console on the framebuffer, and it still works
+/// when something has gone wrong badly enough that the framebuffer can't be trusted
+pub struct SerialPort {
+    base: u16,
+    initialised: bool
+}
+
+impl SerialPort {
+    const fn new(base: u16) -> Self {
+        SerialPort { base, initialised: false }
+    }
+
+    fn initialise(&mut self) {
+        let register = |offset: u16| Port::<u8>::new(self.base + offset);
+
+        // 115200 baud, 8 data bits, no parity, one stop bit, and no interrupts since nothing is
+        // ready to handle them
+        unsafe {
+            register(1).write(0x00);
+            register(3).write(0x80);
+            register(0).write(0x01);
+            register(1).write(0x00);
+            register(3).write(0x03);
+            register(2).write(0xc7);
+            register(4).write(0x0b);
+        }
+
+        self.initialised = true;
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        if !self.initialised {
+            self.initialise();
+        }
+
+        let mut line_status = Port::<u8>::new(self.base + 5);
+        let mut data = Port::<u8>::new(self.base);
+
+        unsafe {
+            // Wait for the transmit buffer to empty
+            while line_status.read() & 0x20 == 0 {}
+            data.write(byte);
+        }
+    }
+}
+
+#[cfg(feature = "screenshot")]
+impl SerialPort {
+    /// Sends bytes exactly as they are, without turning \n into \r\n like write_str does, for
+    /// binary data
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_byte(*byte);
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+pub static SERIAL: Mutex<SerialPort> = Mutex::new(SerialPort::new(COM1));
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use fmt::Write;
+    let _ = SERIAL.lock().write_fmt(args);
+}
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
@@ -0,0 +1,250 @@
+use elf_parser::abi::{
+    ELFCLASS64, ELFDATA2LSB, ELFOSABI_SYSV, EV_CURRENT, ET_CORE, EM_X86_64,
+    PT_LOAD, PT_NOTE, NT_PRSTATUS
+};
+
+use x86_64::VirtAddr;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+/// The size of `struct elf_prstatus` on x86_64 linux, which is the layout gdb expects to find in
+/// an NT_PRSTATUS note
+const PRSTATUS_SIZE: usize = 336;
+
+/// The offset of `pr_reg` inside of `struct elf_prstatus`
+const PRSTATUS_REG_OFFSET: usize = 112;
+
+/// "CORE" plus its null terminator, padded out to 4 bytes like every note name has to be
+const NOTE_NAME: [u8; 8] = *b"CORE\0\0\0\0";
+const NOTE_NAME_SIZE: u32 = 5;
+const NOTE_HEADER_SIZE: usize = 12;
+const NOTE_SIZE: usize = NOTE_HEADER_SIZE + NOTE_NAME.len() + PRSTATUS_SIZE;
+
+/// Segment data gets placed on page boundaries in the file the same way linux lays out its core
+/// files. It costs some padding but some tools get upset otherwise
+const SEGMENT_ALIGN: u64 = 4096;
+
+/// Something that the bytes of a core file can be streamed into, like a file or a serial port.
+/// The dump is always written front to back in one pass so there's no need for seeking
+pub trait CoreDumpSink {
+    type WriteErrorType;
+
+    /// Must write all of `bytes` or fail
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::WriteErrorType>;
+}
+
+#[derive(Debug)]
+pub enum CoreDumpError<SinkError> {
+    /// The sink failed to write some bytes, contains whatever error it gave back
+    Sink(SinkError),
+    /// There are more segments than can be described by e_phnum (the extended numbering scheme
+    /// isn't supported)
+    TooManySegments
+}
+
+/// The general purpose registers of a thread, in the order of linux's `user_regs_struct` since
+/// that's what gdb reads out of NT_PRSTATUS
+#[derive(Clone, Copy, Default)]
+pub struct RegisterState {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64
+}
+
+impl RegisterState {
+    fn as_array(&self) -> [u64; 27] {
+        [
+            self.r15, self.r14, self.r13, self.r12, self.rbp, self.rbx, self.r11, self.r10,
+            self.r9, self.r8, self.rax, self.rcx, self.rdx, self.rsi, self.rdi, self.orig_rax,
+            self.rip, self.cs, self.rflags, self.rsp, self.ss, self.fs_base, self.gs_base,
+            self.ds, self.es, self.fs, self.gs
+        ]
+    }
+}
+
+/// The state of one thread at the time of the crash. Each one becomes its own NT_PRSTATUS note,
+/// and gdb treats the first one as the thread that crashed
+pub struct ThreadState {
+    /// The id of the thread (or process if it only has one thread)
+    pub pid: u32,
+    pub parent_pid: u32,
+    /// The signal (or the closest thing to one) that killed the thread, e.g. 11 for a page fault
+    pub signal: u16,
+    pub registers: RegisterState
+}
+
+/// A mapped region of memory to be included in the dump
+pub struct CoreRegion<'a> {
+    /// The virtual address the region was mapped at
+    pub start: VirtAddr,
+    /// The contents of the region. This can be shorter than `memory_size` if the rest of the
+    /// region is zeroed (or just not worth dumping)
+    pub data: &'a [u8],
+    /// The size of the region in the process's address space
+    pub memory_size: u64,
+    /// Some combination of inspect::abi::PF_R, PF_W and PF_X
+    pub flags: u32
+}
+
+#[inline]
+fn align_up(value: u64, align: u64) -> u64 {
+    (value + align - 1) & !(align - 1)
+}
+
+fn write_zeroes<Sink: CoreDumpSink>(sink: &mut Sink, mut count: usize) -> Result<(), Sink::WriteErrorType> {
+    const ZEROES: [u8; 64] = [0; 64];
+
+    while count > 0 {
+        let amount = count.min(ZEROES.len());
+        sink.write(&ZEROES[..amount])?;
+        count -= amount;
+    }
+
+    Ok(())
+}
+
+fn elf_header(phnum: u16) -> [u8; EHDR_SIZE] {
+    let mut header = [0u8; EHDR_SIZE];
+
+    header[0..4].copy_from_slice(&elf_parser::abi::ELFMAGIC);
+    header[4] = ELFCLASS64;
+    header[5] = ELFDATA2LSB;
+    header[6] = EV_CURRENT;
+    header[7] = ELFOSABI_SYSV;
+    header[16..18].copy_from_slice(&ET_CORE.to_le_bytes());
+    header[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+    header[20..24].copy_from_slice(&(EV_CURRENT as u32).to_le_bytes());
+    // e_entry is left as 0
+    header[32..40].copy_from_slice(&(EHDR_SIZE as u64).to_le_bytes());
+    // e_shoff and e_flags are left as 0 since there are no sections
+    header[52..54].copy_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+    header[54..56].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+    header[56..58].copy_from_slice(&phnum.to_le_bytes());
+    header[58..60].copy_from_slice(&64u16.to_le_bytes());
+    // e_shnum and e_shstrndx are left as 0
+
+    header
+}
+
+fn program_header(p_type: u32, flags: u32, offset: u64, vaddr: u64, file_size: u64, memory_size: u64, align: u64) -> [u8; PHDR_SIZE] {
+    let mut header = [0u8; PHDR_SIZE];
+
+    header[0..4].copy_from_slice(&p_type.to_le_bytes());
+    header[4..8].copy_from_slice(&flags.to_le_bytes());
+    header[8..16].copy_from_slice(&offset.to_le_bytes());
+    header[16..24].copy_from_slice(&vaddr.to_le_bytes());
+    // p_paddr is meaningless for a core file and is left as 0
+    header[32..40].copy_from_slice(&file_size.to_le_bytes());
+    header[40..48].copy_from_slice(&memory_size.to_le_bytes());
+    header[48..56].copy_from_slice(&align.to_le_bytes());
+
+    header
+}
+
+fn prstatus_note(thread: &ThreadState) -> [u8; NOTE_SIZE] {
+    let mut note = [0u8; NOTE_SIZE];
+
+    note[0..4].copy_from_slice(&NOTE_NAME_SIZE.to_le_bytes());
+    note[4..8].copy_from_slice(&(PRSTATUS_SIZE as u32).to_le_bytes());
+    note[8..12].copy_from_slice(&(NT_PRSTATUS as u32).to_le_bytes());
+    note[12..20].copy_from_slice(&NOTE_NAME);
+
+    let prstatus = &mut note[20..];
+
+    // pr_info.si_signo and pr_cursig both hold the signal
+    prstatus[0..4].copy_from_slice(&(thread.signal as u32).to_le_bytes());
+    prstatus[12..14].copy_from_slice(&thread.signal.to_le_bytes());
+    prstatus[32..36].copy_from_slice(&thread.pid.to_le_bytes());
+    prstatus[36..40].copy_from_slice(&thread.parent_pid.to_le_bytes());
+
+    for (index, register) in thread.registers.as_array().iter().enumerate() {
+        let offset = PRSTATUS_REG_OFFSET + index * 8;
+        prstatus[offset..offset + 8].copy_from_slice(&register.to_le_bytes());
+    }
+
+    note
+}
+
+/// Writes an ET_CORE elf file describing a crashed process into `sink` and returns the number of
+/// bytes that were written
+///
+/// # Arguments
+/// * `sink` - where the bytes of the file go
+///
+/// * `threads` - the register state of every thread, the one that faulted should be first
+///
+/// * `regions` - the mapped memory of the process, each becomes a PT_LOAD segment
+pub fn write_core_dump<Sink: CoreDumpSink>(sink: &mut Sink, threads: &[ThreadState], regions: &[CoreRegion]) -> Result<u64, CoreDumpError<Sink::WriteErrorType>> {
+    // One PT_NOTE holding every thread's NT_PRSTATUS, then one PT_LOAD for each region
+    let phnum = u16::try_from(regions.len() + 1)
+        .ok()
+        .filter(|&count| count != u16::MAX)
+        .ok_or(CoreDumpError::TooManySegments)?;
+
+    let notes_offset = (EHDR_SIZE + PHDR_SIZE * phnum as usize) as u64;
+    let notes_size = (NOTE_SIZE * threads.len()) as u64;
+
+    let mut written = 0u64;
+
+    let emit = |sink: &mut Sink, bytes: &[u8]| -> Result<(), CoreDumpError<Sink::WriteErrorType>> {
+        sink.write(bytes).map_err(CoreDumpError::Sink)
+    };
+
+    emit(sink, &elf_header(phnum))?;
+    emit(sink, &program_header(PT_NOTE, 0, notes_offset, 0, notes_size, 0, 4))?;
+    written += (EHDR_SIZE + PHDR_SIZE) as u64;
+
+    let mut data_offset = align_up(notes_offset + notes_size, SEGMENT_ALIGN);
+    for region in regions {
+        emit(sink, &program_header(
+            PT_LOAD,
+            region.flags,
+            data_offset,
+            region.start.as_u64(),
+            region.data.len() as u64,
+            region.memory_size.max(region.data.len() as u64),
+            SEGMENT_ALIGN
+        ))?;
+        written += PHDR_SIZE as u64;
+        data_offset = align_up(data_offset + region.data.len() as u64, SEGMENT_ALIGN);
+    }
+
+    for thread in threads {
+        emit(sink, &prstatus_note(thread))?;
+        written += NOTE_SIZE as u64;
+    }
+
+    for region in regions {
+        let padding = align_up(written, SEGMENT_ALIGN) - written;
+        write_zeroes(sink, padding as usize).map_err(CoreDumpError::Sink)?;
+        emit(sink, region.data)?;
+        written += padding + region.data.len() as u64;
+    }
+
+    Ok(written)
+}
@@ -0,0 +1,492 @@
+use alloc::vec::Vec;
+
+use elf_parser::{ElfBytes, endian::AnyEndian, file::Class};
+use elf_parser::dynamic::DynamicTable;
+use elf_parser::hash::{GnuHashTable, SysVHashTable};
+use elf_parser::relocation::{Rela, RelaIterator};
+use elf_parser::string_table::StringTable;
+use elf_parser::symbol::{Symbol, SymbolTable};
+
+use elf_parser::abi::{
+    DT_NEEDED,
+    DT_STRTAB,
+    DT_STRSZ,
+    DT_SYMTAB,
+    DT_HASH,
+    DT_GNU_HASH,
+    DT_RELA,
+    DT_RELASZ,
+    DT_JMPREL,
+    DT_PLTRELSZ,
+    DT_PLTGOT,
+    DT_BIND_NOW,
+    DT_FLAGS,
+    DT_FLAGS_1,
+    DF_BIND_NOW,
+    DF_1_NOW,
+    SHN_UNDEF,
+    STB_LOCAL,
+    STB_WEAK,
+    R_X86_64_NONE,
+    R_X86_64_64,
+    R_X86_64_COPY,
+    R_X86_64_GLOB_DAT,
+    R_X86_64_JUMP_SLOT,
+    R_X86_64_RELATIVE,
+    ET_DYN,
+    PT_LOAD
+};
+
+use x86_64::VirtAddr;
+
+use mem::{MemoryMapper, PAGE_SIZE};
+
+use crate::{ElfLoadError, LoadLocation, LoadedImage};
+use crate::permissions::{self, WxPolicy};
+
+/// Somewhere that shared libraries can be read from, like a filesystem or a ramdisk
+pub trait LibrarySource<'data> {
+    /// Gives back the whole contents of the file called `name` in `directory`, if there is one
+    fn open(&mut self, directory: &str, name: &str) -> Option<&'data [u8]>;
+}
+
+/// When calls through the PLT get bound to the function they end up at
+#[derive(Clone, Copy)]
+pub enum PltBinding {
+    /// Every PLT entry is bound while loading
+    Eager,
+    /// PLT entries go through `resolver` the first time they're called. It gets the link map
+    /// index of the object from GOT[1] and the relocation index pushed by the PLT stub, and
+    /// should pass them on to `LinkMap::resolve_lazy`. Objects linked with BIND_NOW are always
+    /// bound eagerly
+    Lazy { resolver: VirtAddr }
+}
+
+/// The bits of an object's dynamic section that are needed for linking, pulled out of the file
+/// data so that no section headers are needed (they're often stripped)
+pub(crate) struct DynamicInfo<'data> {
+    class: Class,
+    endian: AnyEndian,
+    dynamic: Option<DynamicTable<'data, AnyEndian>>,
+    symbols: Option<SymbolTable<'data, AnyEndian>>,
+    strings: StringTable<'data>,
+    gnu_hash: Option<GnuHashTable<'data, AnyEndian>>,
+    sysv_hash: Option<SysVHashTable<'data, AnyEndian>>,
+    relocations: &'data [u8],
+    plt_relocations: &'data [u8],
+    got: Option<u64>,
+    bind_now: bool
+}
+
+/// The file data from `vaddr` to the end of whatever segment it's in. Tables like the symbol table
+/// don't say how long they are so this is the best bound there is
+fn data_from<'data>(elf_bytes: &ElfBytes<'data, AnyEndian>, data: &'data [u8], vaddr: u64) -> Option<&'data [u8]> {
+    let phdr = elf_bytes.segments()?
+        .iter()
+        .find(|phdr| phdr.p_type == PT_LOAD && phdr.p_vaddr <= vaddr && vaddr < phdr.p_vaddr + phdr.p_filesz)?;
+
+    let start = (vaddr - phdr.p_vaddr + phdr.p_offset) as usize;
+    data.get(start..(phdr.p_offset + phdr.p_filesz) as usize)
+}
+
+fn data_at<'data>(elf_bytes: &ElfBytes<'data, AnyEndian>, data: &'data [u8], vaddr: u64, size: u64) -> Option<&'data [u8]> {
+    data_from(elf_bytes, data, vaddr)?.get(..size as usize)
+}
+
+impl<'data> DynamicInfo<'data> {
+    pub(crate) fn parse(elf_bytes: &ElfBytes<'data, AnyEndian>, data: &'data [u8]) -> Result<Self, ElfLoadError> {
+        let mut info = DynamicInfo {
+            class: elf_bytes.ehdr.class,
+            endian: elf_bytes.ehdr.endianness,
+            dynamic: None,
+            symbols: None,
+            strings: StringTable::default(),
+            gnu_hash: None,
+            sysv_hash: None,
+            relocations: &[],
+            plt_relocations: &[],
+            got: None,
+            bind_now: false
+        };
+
+        let dynamic = match elf_bytes.dynamic()? {
+            Some(dynamic) => dynamic,
+            None => return Ok(info)
+        };
+
+        // Everything here is an address which needs to be turned into part of the file, and some
+        // of the sizes come in separate entries so they get collected first
+        let (mut strtab, mut strsz, mut rela, mut relasz, mut jmprel, mut pltrelsz) = (None, 0, None, 0, None, 0);
+
+        for entry in dynamic.iter() {
+            match entry.d_tag {
+                DT_STRTAB => strtab = Some(entry.d_ptr()),
+                DT_STRSZ => strsz = entry.d_val(),
+                DT_SYMTAB => info.symbols = Some(SymbolTable::new(
+                    info.endian,
+                    info.class,
+                    data_from(elf_bytes, data, entry.d_ptr()).ok_or(ElfLoadError::MalformedSegment)?
+                )),
+                DT_GNU_HASH => info.gnu_hash = Some(GnuHashTable::new(
+                    info.endian,
+                    info.class,
+                    data_from(elf_bytes, data, entry.d_ptr()).ok_or(ElfLoadError::MalformedSegment)?
+                )?),
+                DT_HASH => info.sysv_hash = Some(SysVHashTable::new(
+                    info.endian,
+                    info.class,
+                    data_from(elf_bytes, data, entry.d_ptr()).ok_or(ElfLoadError::MalformedSegment)?
+                )?),
+                DT_RELA => rela = Some(entry.d_ptr()),
+                DT_RELASZ => relasz = entry.d_val(),
+                DT_JMPREL => jmprel = Some(entry.d_ptr()),
+                DT_PLTRELSZ => pltrelsz = entry.d_val(),
+                DT_PLTGOT => info.got = Some(entry.d_ptr()),
+                DT_BIND_NOW => info.bind_now = true,
+                DT_FLAGS => info.bind_now |= entry.d_val() as i64 & DF_BIND_NOW != 0,
+                DT_FLAGS_1 => info.bind_now |= entry.d_val() as i64 & DF_1_NOW != 0,
+                _ => {}
+            }
+        }
+
+        if let Some(strtab) = strtab {
+            info.strings = StringTable::new(
+                data_at(elf_bytes, data, strtab, strsz).ok_or(ElfLoadError::MalformedSegment)?
+            );
+        }
+
+        if let Some(rela) = rela {
+            info.relocations = data_at(elf_bytes, data, rela, relasz).ok_or(ElfLoadError::MalformedSegment)?;
+        }
+
+        if let Some(jmprel) = jmprel {
+            info.plt_relocations = data_at(elf_bytes, data, jmprel, pltrelsz).ok_or(ElfLoadError::MalformedSegment)?;
+        }
+
+        info.dynamic = Some(dynamic);
+
+        Ok(info)
+    }
+
+    /// The names of the libraries this object depends on
+    pub(crate) fn needed(&self) -> impl Iterator<Item = Result<&'data str, ElfLoadError>> + '_ {
+        self.dynamic
+            .iter()
+            .flat_map(|dynamic| dynamic.iter())
+            .filter(|entry| entry.d_tag == DT_NEEDED)
+            .map(|entry| self.strings.get(entry.d_val() as usize).map_err(ElfLoadError::from))
+    }
+
+    fn symbol(&self, index: u32) -> Result<Symbol, ElfLoadError> {
+        Ok(self.symbols.as_ref().ok_or(ElfLoadError::MalformedSegment)?.get(index as usize)?)
+    }
+
+    fn symbol_name(&self, symbol: &Symbol) -> Result<&'data [u8], ElfLoadError> {
+        Ok(self.strings.get_raw(symbol.st_name as usize)?)
+    }
+
+    /// Finds a symbol this object defines using its hash table. The value is not biased
+    fn lookup(&self, name: &[u8]) -> Option<Symbol> {
+        let symbols = self.symbols.as_ref()?;
+
+        let found = match (&self.gnu_hash, &self.sysv_hash) {
+            (Some(table), _) => table.find(name, symbols, &self.strings).ok()?,
+            (None, Some(table)) => table.find(name, symbols, &self.strings).ok()?,
+            (None, None) => None
+        };
+
+        found
+            .map(|(_, symbol)| symbol)
+            .filter(|symbol| symbol.st_shndx != SHN_UNDEF && symbol.st_bind() != STB_LOCAL)
+    }
+}
+
+/// One object that has been put in an address space
+pub(crate) struct SharedObject<'data> {
+    /// The DT_NEEDED name it was loaded by, or "" for the program itself
+    name: &'data str,
+    data: &'data [u8],
+    image: LoadedImage,
+    /// The page aligned (start, end) of the object in memory. Relocations have to land inside
+    /// this, and the end is used to place the next object
+    span: (u64, u64),
+    info: DynamicInfo<'data>
+}
+
+impl<'data> SharedObject<'data> {
+    pub(crate) fn new(name: &'data str, data: &'data [u8], image: LoadedImage, span: (u64, u64), info: DynamicInfo<'data>) -> Self {
+        SharedObject { name, data, image, span, info }
+    }
+
+    pub(crate) fn image(&self) -> LoadedImage {
+        self.image
+    }
+}
+
+/// Finds the (biased) address and size of the symbol called `name`, searching the objects in
+/// load order. `skip` is left out of the search, which is what copy relocations need
+fn find_in_scope(scope: &[SharedObject], name: &[u8], skip: Option<usize>) -> Option<(u64, u64)> {
+    scope.iter()
+        .enumerate()
+        .filter(|(index, _)| Some(*index) != skip)
+        .find_map(|(_, object)| object.info.lookup(name)
+            .map(|symbol| (symbol.st_value.wrapping_add(object.image.load_bias), symbol.st_size)))
+}
+
+/// Works out what the symbol used by a relocation in `scope[index]` refers to
+fn resolve_symbol(scope: &[SharedObject], index: usize, symbol_index: u32) -> Result<u64, ElfLoadError> {
+    let object = &scope[index];
+    let symbol = object.info.symbol(symbol_index)?;
+
+    if symbol.st_bind() == STB_LOCAL {
+        return Ok(symbol.st_value.wrapping_add(object.image.load_bias))
+    }
+
+    let name = object.info.symbol_name(&symbol)?;
+
+    if let Some((address, _)) = find_in_scope(scope, name, None) {
+        return Ok(address)
+    }
+
+    // Hash tables don't have to contain every symbol, so an object can still define something that
+    // couldn't be found
+    if symbol.st_shndx != SHN_UNDEF {
+        Ok(symbol.st_value.wrapping_add(object.image.load_bias))
+    } else if symbol.st_bind() == STB_WEAK {
+        Ok(0)
+    } else {
+        Err(ElfLoadError::UndefinedSymbol)
+    }
+}
+
+/// The address of the `size` bytes a relocation in `object` writes to, as long as all of them are
+/// inside the object
+fn relocation_target(object: &SharedObject, offset: u64, size: u64) -> Result<VirtAddr, ElfLoadError> {
+    let target = offset.wrapping_add(object.image.load_bias);
+
+    if target < object.span.0 || target.saturating_add(size) > object.span.1 {
+        return Err(ElfLoadError::MalformedSegment)
+    }
+
+    Ok(VirtAddr::new(target))
+}
+
+unsafe fn write_word<Mapper: MemoryMapper>(mapper: &mut Mapper, page_table: &mut Mapper::PageTable, address: VirtAddr, value: u64) {
+    unsafe { mapper.write(page_table, address, &value.to_le_bytes()) }
+}
+
+unsafe fn read_word<Mapper: MemoryMapper>(mapper: &Mapper, page_table: &Mapper::PageTable, address: VirtAddr) -> u64 {
+    let mut word = [0u8; 8];
+    unsafe { mapper.read(page_table, address, &mut word) };
+    u64::from_le_bytes(word)
+}
+
+/// Applies a single relocation from `scope[index]`
+fn apply_relocation<Mapper: MemoryMapper>(scope: &[SharedObject], index: usize, rela: &Rela, lazy: bool, mapper: &mut Mapper, page_table: &mut Mapper::PageTable) -> Result<(), ElfLoadError> {
+    let bias = scope[index].image.load_bias;
+
+    if rela.r_type == R_X86_64_NONE {
+        return Ok(())
+    }
+
+    let target = relocation_target(&scope[index], rela.r_offset, 8)?;
+
+    // The target was just checked to be in the object, which was mapped by this mapper
+    unsafe {
+        match rela.r_type {
+            R_X86_64_RELATIVE => write_word(mapper, page_table, target, bias.wrapping_add_signed(rela.r_addend)),
+            R_X86_64_64 => write_word(
+                mapper,
+                page_table,
+                target,
+                resolve_symbol(scope, index, rela.r_sym)?.wrapping_add_signed(rela.r_addend)
+            ),
+            R_X86_64_GLOB_DAT => write_word(mapper, page_table, target, resolve_symbol(scope, index, rela.r_sym)?),
+            // Lazy slots already point back into the PLT (as if the object was at 0) so they just
+            // need moving with the rest of the object
+            R_X86_64_JUMP_SLOT if lazy => {
+                let unbiased = read_word(mapper, page_table, target);
+                write_word(mapper, page_table, target, unbiased.wrapping_add(bias))
+            },
+            R_X86_64_JUMP_SLOT => write_word(mapper, page_table, target, resolve_symbol(scope, index, rela.r_sym)?),
+            R_X86_64_COPY => {
+                let object = &scope[index];
+                let name = object.info.symbol_name(&object.info.symbol(rela.r_sym)?)?;
+                let (source, size) = find_in_scope(scope, name, Some(index))
+                    .ok_or(ElfLoadError::UndefinedSymbol)?;
+
+                // Only the first 8 bytes were checked above, and the copy can be any size
+                relocation_target(object, rela.r_offset, size)?;
+
+                // Goes through a buffer since the mapper can only copy in and out of the address
+                // space, not within it
+                let mut buffer = [0u8; 64];
+                let mut copied = 0;
+                while copied < size {
+                    let chunk = (size - copied).min(buffer.len() as u64) as usize;
+                    mapper.read(page_table, VirtAddr::new(source + copied), &mut buffer[..chunk]);
+                    mapper.write(page_table, target + copied, &buffer[..chunk]);
+                    copied += chunk as u64;
+                }
+            },
+            other => return Err(ElfLoadError::UnsupportedRelocation(other))
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies every relocation of `scope[index]`, looking up symbols in all of `scope`
+pub(crate) fn relocate<Mapper: MemoryMapper>(scope: &[SharedObject], index: usize, binding: PltBinding, mapper: &mut Mapper, page_table: &mut Mapper::PageTable) -> Result<(), ElfLoadError> {
+    let info = &scope[index].info;
+
+    for rela in RelaIterator::new(info.endian, info.class, info.relocations) {
+        apply_relocation(scope, index, &rela, false, mapper, page_table)?;
+    }
+
+    let resolver = match binding {
+        PltBinding::Lazy { resolver } if !info.bind_now => Some(resolver),
+        _ => None
+    };
+
+    for rela in RelaIterator::new(info.endian, info.class, info.plt_relocations) {
+        apply_relocation(scope, index, &rela, resolver.is_some(), mapper, page_table)?;
+    }
+
+    if let (Some(resolver), Some(got)) = (resolver, info.got) {
+        // GOT[1] identifies the object to the resolver and GOT[2] is the resolver itself
+        let object_slot = relocation_target(&scope[index], got + 8, 8)?;
+        let resolver_slot = relocation_target(&scope[index], got + 16, 8)?;
+
+        unsafe {
+            write_word(mapper, page_table, object_slot, index as u64);
+            write_word(mapper, page_table, resolver_slot, resolver.as_u64());
+        }
+    }
+
+    Ok(())
+}
+
+/// Every object loaded into one address space, in the order symbols are looked up in. There
+/// should be one of these per address space so that each library is only loaded into it once
+pub struct LinkMap<'data> {
+    search_path: &'data [&'data str],
+    binding: PltBinding,
+    policy: WxPolicy,
+    objects: Vec<SharedObject<'data>>
+}
+
+impl<'data> LinkMap<'data> {
+    /// # Arguments
+    /// * `search_path` - the directories that libraries are looked for in, in order
+    ///
+    /// * `binding` - how PLT entries should be bound
+    ///
+    /// * `policy` - what to do with objects that want writable and executable memory
+    pub fn new(search_path: &'data [&'data str], binding: PltBinding, policy: WxPolicy) -> Self {
+        LinkMap { search_path, binding, policy, objects: Vec::new() }
+    }
+
+    /// The name and image of every object that has been loaded so far
+    pub fn objects(&self) -> impl Iterator<Item = (&'data str, &LoadedImage)> {
+        self.objects.iter().map(|object| (object.name, &object.image))
+    }
+
+    /// Finds the address of a global symbol in the same way relocations do
+    pub fn lookup(&self, name: &str) -> Option<VirtAddr> {
+        find_in_scope(&self.objects, name.as_bytes(), None).map(|(address, _)| VirtAddr::new(address))
+    }
+
+    /// Loads a program along with every library it needs (and that they need, and so on), then
+    /// links them all together. Returns the program's image
+    ///
+    /// # Arguments
+    /// * `data` - the program's file
+    ///
+    /// * `mapper` and `page_table` - the same as for `crate::load`
+    ///
+    /// * `load_location` - where the program should go. Libraries are put after it
+    ///
+    /// * `source` - where to read the libraries from
+    pub fn load<Mapper: MemoryMapper, Source: LibrarySource<'data>>(&mut self, data: &'data [u8], mapper: &mut Mapper, page_table: &mut Mapper::PageTable, load_location: LoadLocation, source: &mut Source) -> Result<LoadedImage, ElfLoadError> {
+        let first_new = self.objects.len();
+
+        let program = crate::load_object(data, "", mapper, page_table, load_location, self.policy)?;
+        let image = program.image();
+        self.objects.push(program);
+
+        // Breadth first, which is the order the libraries get searched in
+        let mut next = first_new;
+        while next < self.objects.len() {
+            let needed: Vec<&'data str> = self.objects[next].info.needed().collect::<Result<_, _>>()?;
+
+            for name in needed {
+                if self.objects.iter().any(|object| object.name == name) {
+                    continue
+                }
+
+                let library = self.search_path.iter()
+                    .find_map(|directory| source.open(directory, name))
+                    .ok_or(ElfLoadError::MissingLibrary)?;
+
+                if crate::parse(library)?.ehdr.e_type != ET_DYN {
+                    return Err(ElfLoadError::IncorrectType)
+                }
+
+                let after = self.objects.iter().map(|object| object.span.1).max().unwrap_or(0);
+
+                // An unmapped page is left between libraries to catch anything running off the end
+                let object = crate::load_object(
+                    library,
+                    name,
+                    mapper,
+                    page_table,
+                    LoadLocation::GreaterThan(VirtAddr::new(after + PAGE_SIZE as u64)),
+                    self.policy
+                )?;
+
+                self.objects.push(object);
+            }
+
+            next += 1;
+        }
+
+        // Libraries are relocated before the things that depend on them so that copy relocations
+        // copy data that has already been relocated
+        for index in (first_new..self.objects.len()).rev() {
+            relocate(&self.objects, index, self.binding, mapper, page_table)?;
+        }
+
+        for object in &self.objects[first_new..] {
+            permissions::protect(&crate::parse(object.data)?, mapper, page_table, object.image.load_bias)?;
+        }
+
+        Ok(image)
+    }
+
+    /// Binds a lazy PLT entry and returns the address of the function it was for, which the
+    /// resolver should jump to
+    ///
+    /// # Arguments
+    /// * `mapper` and `page_table` - the same ones the objects were loaded with
+    ///
+    /// * `object` - the index found in GOT[1] of the calling object
+    ///
+    /// * `relocation` - the index the PLT stub pushed
+    pub fn resolve_lazy<Mapper: MemoryMapper>(&self, mapper: &mut Mapper, page_table: &mut Mapper::PageTable, object: usize, relocation: usize) -> Result<VirtAddr, ElfLoadError> {
+        let info = &self.objects.get(object).ok_or(ElfLoadError::UndefinedSymbol)?.info;
+
+        let rela = RelaIterator::new(info.endian, info.class, info.plt_relocations)
+            .nth(relocation)
+            .ok_or(ElfLoadError::MalformedSegment)?;
+
+        if rela.r_type != R_X86_64_JUMP_SLOT {
+            return Err(ElfLoadError::UnsupportedRelocation(rela.r_type))
+        }
+
+        apply_relocation(&self.objects, object, &rela, false, mapper, page_table)?;
+
+        let slot = relocation_target(&self.objects[object], rela.r_offset, 8)?;
+        Ok(VirtAddr::new(unsafe { read_word(mapper, page_table, slot) }))
+    }
+}
@@ -0,0 +1,178 @@
+use core::fmt;
+
+use elf_parser::{ElfBytes, endian::AnyEndian};
+use elf_parser::note::Note;
+
+use elf_parser::abi::{
+    PT_NOTE,
+    SHN_UNDEF,
+    SHT_NOTE,
+    STT_FUNC,
+    STT_OBJECT,
+    STT_GNU_IFUNC
+};
+
+use x86_64::VirtAddr;
+
+use crate::ElfLoadError;
+
+/// The constants from the elf crate, so users can make sense of the types and flags in the
+/// summaries without depending on it themselves
+pub use elf_parser::abi;
+
+/// A section header with its name looked up
+pub struct SectionInfo<'data> {
+    pub name: &'data str,
+    pub section_type: u32,
+    pub flags: u64,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64
+}
+
+pub struct SegmentInfo {
+    pub segment_type: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub address: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub alignment: u64
+}
+
+/// A symbol with its name looked up. The address has the load bias applied
+pub struct SymbolInfo<'data> {
+    pub name: &'data str,
+    pub address: u64,
+    pub size: u64,
+    pub symbol_type: u8,
+    /// False for symbols this file uses but something else defines, whose address is meaningless
+    pub defined: bool
+}
+
+/// The contents of an NT_GNU_BUILD_ID note. Displays as lowercase hex, the same way `file` and
+/// `readelf` print it
+pub struct BuildId<'data>(pub &'data [u8]);
+
+impl fmt::Display for BuildId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read only access to the parts of an elf file that are useful for working out what it is and
+/// what is at a given address. Works on the file whether or not it has been loaded; for a loaded
+/// image give it the load bias so addresses line up with memory
+pub struct Inspector<'data> {
+    elf_bytes: ElfBytes<'data, AnyEndian>,
+    load_bias: u64
+}
+
+impl<'data> Inspector<'data> {
+    /// Inspects the file as it is on disk
+    pub fn new(data: &'data [u8]) -> Result<Self, ElfLoadError> {
+        Ok(Inspector { elf_bytes: crate::parse(data)?, load_bias: 0 })
+    }
+
+    /// Inspects the file as it was loaded by `crate::load` (or a LinkMap)
+    pub fn loaded(data: &'data [u8], load_bias: u64) -> Result<Self, ElfLoadError> {
+        Ok(Inspector { elf_bytes: crate::parse(data)?, load_bias })
+    }
+
+    pub fn object_type(&self) -> u16 {
+        self.elf_bytes.ehdr.e_type
+    }
+
+    pub fn entry(&self) -> VirtAddr {
+        VirtAddr::new(self.elf_bytes.ehdr.e_entry.wrapping_add(self.load_bias))
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = SegmentInfo> + '_ {
+        self.elf_bytes.segments()
+            .into_iter()
+            .flat_map(|segments| segments.iter())
+            .map(|phdr| SegmentInfo {
+                segment_type: phdr.p_type,
+                flags: phdr.p_flags,
+                offset: phdr.p_offset,
+                address: phdr.p_vaddr.wrapping_add(self.load_bias),
+                file_size: phdr.p_filesz,
+                memory_size: phdr.p_memsz,
+                alignment: phdr.p_align
+            })
+    }
+
+    /// Every section header, or nothing if they were stripped. Sections without a name (or in
+    /// files without a section name table) get ""
+    pub fn sections(&self) -> impl Iterator<Item = SectionInfo<'data>> + '_ {
+        let (headers, names) = self.elf_bytes.section_headers_with_strtab().unwrap_or((None, None));
+
+        headers.into_iter()
+            .flat_map(|headers| headers.iter())
+            .map(move |shdr| SectionInfo {
+                name: names.and_then(|names| names.get(shdr.sh_name as usize).ok()).unwrap_or(""),
+                section_type: shdr.sh_type,
+                flags: shdr.sh_flags,
+                address: if shdr.sh_addr != 0 { shdr.sh_addr.wrapping_add(self.load_bias) } else { 0 },
+                offset: shdr.sh_offset,
+                size: shdr.sh_size
+            })
+    }
+
+    /// Finds the GNU build-id, looking in the PT_NOTE segments first since they survive stripping
+    /// and then falling back to SHT_NOTE sections
+    pub fn build_id(&self) -> Option<BuildId<'data>> {
+        let from_notes = |notes: elf_parser::note::NoteIterator<'data, AnyEndian>| {
+            notes.filter_map(|note| match note {
+                Note::GnuBuildId(id) => Some(BuildId(id.0)),
+                _ => None
+            }).next()
+        };
+
+        let in_segments = self.elf_bytes.segments()
+            .into_iter()
+            .flat_map(|segments| segments.iter())
+            .filter(|phdr| phdr.p_type == PT_NOTE)
+            .filter_map(|phdr| self.elf_bytes.segment_data_as_notes(&phdr).ok())
+            .find_map(from_notes);
+
+        in_segments.or_else(|| self.elf_bytes.section_headers()
+            .into_iter()
+            .flat_map(|headers| headers.iter())
+            .filter(|shdr| shdr.sh_type == SHT_NOTE)
+            .filter_map(|shdr| self.elf_bytes.section_data_as_notes(&shdr).ok())
+            .find_map(from_notes))
+    }
+
+    /// Every symbol in .symtab, or .dynsym if the file has been stripped
+    pub fn symbols(&self) -> impl Iterator<Item = SymbolInfo<'data>> + '_ {
+        let table = self.elf_bytes.symbol_table().ok().flatten()
+            .or_else(|| self.elf_bytes.dynamic_symbol_table().ok().flatten());
+
+        table.into_iter()
+            .flat_map(|(symbols, names)| symbols.iter().map(move |symbol| (symbol, names)))
+            .map(|(symbol, names)| SymbolInfo {
+                name: names.get(symbol.st_name as usize).unwrap_or(""),
+                address: symbol.st_value.wrapping_add(self.load_bias),
+                size: symbol.st_size,
+                symbol_type: symbol.st_symtype(),
+                defined: symbol.st_shndx != SHN_UNDEF
+            })
+    }
+
+    /// Finds the function or object that `address` is in, and how far into it the address is
+    pub fn symbolize(&self, address: VirtAddr) -> Option<(SymbolInfo<'data>, u64)> {
+        let address = address.as_u64();
+
+        self.symbols()
+            .filter(|symbol| symbol.defined && matches!(symbol.symbol_type, STT_FUNC | STT_OBJECT | STT_GNU_IFUNC))
+            .find(|symbol| symbol.address <= address && (address < symbol.address.saturating_add(symbol.size) || symbol.address == address))
+            .map(|symbol| {
+                let offset = address - symbol.address;
+                (symbol, offset)
+            })
+    }
+}
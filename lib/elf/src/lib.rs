@@ -1,14 +1,32 @@
 #![no_std]
 
-use elf::{ElfBytes, endian::AnyEndian, parse::ParseError, CommonElfData};
+extern crate alloc;
 
-use x86_64::structures::paging::{
-    RecursivePageTable
+use elf_parser::{ElfBytes, endian::AnyEndian, parse::ParseError, segment::ProgramHeader};
+
+use elf_parser::abi::{
+    PT_LOAD,
+    PT_PHDR,
+    PT_TLS
 };
 
 use x86_64::VirtAddr;
 
-use mem::FrameAllocator;
+use mem::{MemoryMapper, PAGE_SIZE};
+
+pub mod core_dump;
+pub mod dynamic;
+pub mod inspect;
+pub mod permissions;
+pub mod tls;
+pub mod user_stack;
+
+use dynamic::{DynamicInfo, PltBinding, SharedObject};
+use permissions::WxPolicy;
+use tls::TlsTemplate;
+
+/// Where position independent images go when the caller doesn't care (LoadLocation::Any)
+const DEFAULT_DYN_BASE: u64 = 0x40_0000;
 
 pub enum LoadLocation {
     Any,
@@ -18,14 +36,32 @@ pub enum LoadLocation {
     GreaterThan(VirtAddr) // Probably going to be used mostly for sticking kernel in higher half
 }
 
+#[derive(Debug)]
 pub enum ElfLoadError {
     ElfHeaderParseError(ParseError),
     IncorrectType,
     WrongInstructionSet,
     CommonDataNotFound,
-    MissingSymTab
+    MissingSymTab,
+    NoLoadableSegments,
+    /// A segment says it's bigger in the file than in memory, or goes outside the file
+    MalformedSegment,
+    /// The image can't be put anywhere that satisfies the LoadLocation
+    CannotLoadAtLocation,
+    /// The MemoryMapper couldn't map the pages for a segment
+    MapFailed,
+    /// Contains the relocation type
+    UnsupportedRelocation(u32),
+    /// A relocation used a symbol that isn't defined anywhere
+    UndefinedSymbol,
+    /// A DT_NEEDED library wasn't in any directory on the search path
+    MissingLibrary,
+    /// The image depends on shared libraries so it can't be loaded on its own
+    NeedsLibraries,
+    /// The image wants memory that is writable and executable, and the WxPolicy is to refuse
+    WritableExecutable,
 
-    // A panic handler is not guaranteed to exist (and be pretty) so I'm going to leave it up to 
+    // A panic handler is not guaranteed to exist (and be pretty) so I'm going to leave it up to
     // the caller to deal with this
     NotImplemented
 }
@@ -36,56 +72,267 @@ impl From<ParseError> for ElfLoadError {
     }
 }
 
+/// What the caller needs to know about an image once it has been put in memory
+#[derive(Clone, Copy)]
+pub struct LoadedImage {
+    /// The entry point with the load bias applied
+    pub entry: VirtAddr,
+    /// The difference between where the image was linked to run and where it was put. This is
+    /// always 0 for ET_EXEC files
+    pub load_bias: u64,
+    /// Where the program headers ended up in memory, if they were part of a loaded segment
+    pub program_headers: Option<VirtAddr>,
+    pub program_header_size: u16,
+    pub program_header_count: u16,
+    /// The template for each thread's thread local storage, if the image has a PT_TLS segment
+    pub tls: Option<TlsTemplate>,
+    /// Whether PT_GNU_STACK asked for the stack to be executable
+    pub executable_stack: bool
+}
+
+#[inline]
+pub(crate) fn align_down(value: u64, align: u64) -> u64 {
+    value & !(align - 1)
+}
+
+#[inline]
+pub(crate) fn align_up(value: u64, align: u64) -> u64 {
+    align_down(value + align - 1, align)
+}
+
+/// The page aligned (start, end) of the virtual memory the PT_LOAD segments cover, before any
+/// load bias is applied
+fn image_span(elf_bytes: &ElfBytes<AnyEndian>) -> Result<(u64, u64), ElfLoadError> {
+    let segments = elf_bytes.segments().ok_or(ElfLoadError::NoLoadableSegments)?;
+
+    segments.iter()
+        .filter(|phdr| phdr.p_type == PT_LOAD)
+        .map(|phdr| (phdr.p_vaddr, phdr.p_vaddr + phdr.p_memsz))
+        .reduce(|(start, end), (seg_start, seg_end)| (start.min(seg_start), end.max(seg_end)))
+        .map(|(start, end)| (align_down(start, PAGE_SIZE as u64), align_up(end, PAGE_SIZE as u64)))
+        .ok_or(ElfLoadError::NoLoadableSegments)
+}
+
+/// Maps and fills in the memory for every PT_LOAD segment, shifted by `load_bias`
+///
+/// Everything is written through `mapper`, so it's up to the mapper whether `page_table` has to
+/// be the active one
+fn load_segments<Mapper: MemoryMapper>(elf_bytes: &ElfBytes<AnyEndian>, mapper: &mut Mapper, page_table: &mut Mapper::PageTable, load_bias: u64) -> Result<(), ElfLoadError> {
+    let segments = elf_bytes.segments().ok_or(ElfLoadError::NoLoadableSegments)?;
+
+    // PT_LOAD segments are sorted by address, but neighbouring ones can share a page so this
+    // keeps track of where the mapped pages end to avoid mapping one twice
+    let mut mapped_until = 0u64;
+
+    for phdr in segments.iter().filter(|phdr| phdr.p_type == PT_LOAD) {
+        if phdr.p_filesz > phdr.p_memsz {
+            return Err(ElfLoadError::MalformedSegment)
+        }
+
+        let file_data = elf_bytes.segment_data(&phdr).or(Err(ElfLoadError::MalformedSegment))?;
+
+        let start = phdr.p_vaddr.wrapping_add(load_bias);
+        let first_page = align_down(start, PAGE_SIZE as u64).max(mapped_until);
+        let end_page = align_up(start + phdr.p_memsz, PAGE_SIZE as u64);
+
+        if first_page < end_page {
+            let page_count = ((end_page - first_page) / PAGE_SIZE as u64) as u32;
+            mapper.map_alloc(page_table, VirtAddr::new(first_page), page_count)
+                .or(Err(ElfLoadError::MapFailed))?;
+
+            // Fresh frames could contain anything so they get zeroed before the segment goes in
+            unsafe {
+                mapper.fill(page_table, VirtAddr::new(first_page), 0, (end_page - first_page) as usize);
+            }
+
+            mapped_until = end_page;
+        }
+
+        unsafe {
+            mapper.write(page_table, VirtAddr::new(start), file_data);
+
+            // .bss and friends
+            mapper.fill(
+                page_table,
+                VirtAddr::new(start + phdr.p_filesz),
+                0,
+                (phdr.p_memsz - phdr.p_filesz) as usize
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the PT_TLS segment (if there is one) into a TlsTemplate
+fn find_tls(elf_bytes: &ElfBytes<AnyEndian>, load_bias: u64) -> Option<TlsTemplate> {
+    elf_bytes.segments()?
+        .iter()
+        .find(|phdr| phdr.p_type == PT_TLS)
+        .map(|phdr: ProgramHeader| TlsTemplate {
+            image: VirtAddr::new(phdr.p_vaddr.wrapping_add(load_bias)),
+            file_size: phdr.p_filesz,
+            memory_size: phdr.p_memsz,
+            alignment: phdr.p_align
+        })
+}
+
+/// Finds where the program headers ended up, preferring PT_PHDR but falling back to whichever
+/// PT_LOAD segment happened to contain them
+fn find_program_headers(elf_bytes: &ElfBytes<AnyEndian>, load_bias: u64) -> Option<VirtAddr> {
+    let segments = elf_bytes.segments()?;
+
+    if let Some(phdr) = segments.iter().find(|phdr| phdr.p_type == PT_PHDR) {
+        return Some(VirtAddr::new(phdr.p_vaddr.wrapping_add(load_bias)))
+    }
+
+    let phoff = elf_bytes.ehdr.e_phoff;
+
+    segments.iter()
+        .find(|phdr| phdr.p_type == PT_LOAD && phdr.p_offset <= phoff && phoff < phdr.p_offset + phdr.p_filesz)
+        .map(|phdr| VirtAddr::new((phdr.p_vaddr + (phoff - phdr.p_offset)).wrapping_add(load_bias)))
+}
+
+/// Collects up the information about an image that has been loaded with `load_bias`
+fn describe_image(elf_bytes: &ElfBytes<AnyEndian>, load_bias: u64) -> LoadedImage {
+    LoadedImage {
+        entry: VirtAddr::new(elf_bytes.ehdr.e_entry.wrapping_add(load_bias)),
+        load_bias,
+        program_headers: find_program_headers(elf_bytes, load_bias),
+        program_header_size: elf_bytes.ehdr.e_phentsize,
+        program_header_count: elf_bytes.ehdr.e_phnum,
+        tls: find_tls(elf_bytes, load_bias),
+        executable_stack: permissions::wants_executable_stack(elf_bytes)
+    }
+}
+
+/// Picks where a position independent image spanning `span` should start
+fn choose_base(span: (u64, u64), load_location: LoadLocation) -> Result<u64, ElfLoadError> {
+    let size = span.1 - span.0;
+
+    match load_location {
+        LoadLocation::Any => Ok(DEFAULT_DYN_BASE),
+        LoadLocation::Exactly(addr) => {
+            if addr.as_u64() % PAGE_SIZE as u64 != 0 {
+                return Err(ElfLoadError::CannotLoadAtLocation)
+            }
+            Ok(addr.as_u64())
+        },
+        LoadLocation::LessThan(addr) => align_down(addr.as_u64(), PAGE_SIZE as u64)
+            .checked_sub(size)
+            .ok_or(ElfLoadError::CannotLoadAtLocation),
+        LoadLocation::GreaterThan(addr) => Ok(align_up(addr.as_u64(), PAGE_SIZE as u64))
+    }
+}
+
 // Medium priority. Would be good, but like I can just statically link everything to begin with,
 // and any PIE when compiled for Regulome can just be PIC instead
-fn load_shared_library<Allocator: FrameAllocator>(data: &[u8], allocator: &Allocator, page_table: &mut RecursivePageTable, load_location: LoadLocation, elf_bytes: ElfBytes) -> Result<x86_64::VirtAddr, ElfLoadError> {
-    return Err(NotImplemented)
+//
+// (eta: DT_NEEDED libraries go through here as well when loaded by a LinkMap)
+fn load_shared_library<Mapper: MemoryMapper>(mapper: &mut Mapper, page_table: &mut Mapper::PageTable, load_location: LoadLocation, elf_bytes: &ElfBytes<AnyEndian>) -> Result<u64, ElfLoadError> {
+    let span = image_span(elf_bytes)?;
+    let load_bias = choose_base(span, load_location)?.wrapping_sub(span.0);
+
+    load_segments(elf_bytes, mapper, page_table, load_bias)?;
+
+    Ok(load_bias)
 }
 
 // Low priority, I think I get by with just PIC for a bit
-fn load_executable<Allocator: FrameAllocator>(data: &[u8], allocator: &Allocator, page_table: &mut RecursivePageTable, load_location: LoadLocation, elf_bytes: ElfBytes) -> Result<x86_64::VirtAddr, ElfLoadError> {
-    return Err(NotImplemented)
+fn load_executable<Mapper: MemoryMapper>(mapper: &mut Mapper, page_table: &mut Mapper::PageTable, load_location: LoadLocation, elf_bytes: &ElfBytes<AnyEndian>) -> Result<u64, ElfLoadError> {
+    let (start, end) = image_span(elf_bytes)?;
+
+    // An executable can only go where it was linked to so the best that can be done is checking
+    // that's somewhere the caller is happy with
+    let fits = match load_location {
+        LoadLocation::Any => true,
+        LoadLocation::Exactly(addr) => addr.as_u64() == start,
+        LoadLocation::LessThan(addr) => end <= addr.as_u64(),
+        LoadLocation::GreaterThan(addr) => start >= addr.as_u64()
+    };
+
+    if !fits {
+        return Err(ElfLoadError::CannotLoadAtLocation)
+    }
+
+    load_segments(elf_bytes, mapper, page_table, 0)?;
+
+    Ok(0)
 }
 
 // High priroity, the kernel is one of these
-fn load_relocatable<Allocator: FrameAllocator>(data: &[u8], allocator: &Allocator, page_table: &mut RecursivePageTable, load_location: LoadLocation, elf_bytes: ElfBytes) -> Result<x86_64::VirtAddr, ElfLoadError> {
-    
+fn load_relocatable<Mapper: MemoryMapper>(_mapper: &mut Mapper, _page_table: &mut Mapper::PageTable, _load_location: LoadLocation, _elf_bytes: &ElfBytes<AnyEndian>) -> Result<u64, ElfLoadError> {
+    Err(ElfLoadError::NotImplemented)
 }
 
+/// Parses the file header and checks that it's something that can run on this machine
+pub(crate) fn parse(data: &[u8]) -> Result<ElfBytes<'_, AnyEndian>, ElfLoadError> {
+    let elf_bytes = ElfBytes::<AnyEndian>::minimal_parse(data)?;
+
+    if elf_bytes.ehdr.e_machine != elf_parser::abi::EM_X86_64 || elf_bytes.ehdr.class != elf_parser::file::Class::ELF64 {
+        return Err(ElfLoadError::WrongInstructionSet)
+    }
+
+    Ok(elf_bytes)
+}
+
+/// Parses a file and maps its segments, without doing any relocation. Everything is left
+/// writable so that it can be relocated, and `permissions::protect` needs to be called after
+pub(crate) fn load_object<'data, Mapper: MemoryMapper>(data: &'data [u8], name: &'data str, mapper: &mut Mapper, page_table: &mut Mapper::PageTable, load_location: LoadLocation, policy: WxPolicy) -> Result<SharedObject<'data>, ElfLoadError> {
+    let elf_bytes = &parse(data)?;
+    let info = DynamicInfo::parse(elf_bytes, data)?;
+
+    permissions::check(elf_bytes, policy)?;
 
-/// return a VirtAddr that points to the entry point of the file
+    use elf_parser::abi::{
+        ET_DYN,
+        ET_EXEC,
+        ET_REL
+    };
+
+    let load_bias = match elf_bytes.ehdr.e_type {
+          ET_DYN => load_shared_library(mapper, page_table, load_location, elf_bytes),
+          ET_EXEC => load_executable(mapper, page_table, load_location, elf_bytes),
+          ET_REL => load_relocatable(mapper, page_table, load_location, elf_bytes),
+          _ => Err(ElfLoadError::IncorrectType)
+    }?;
+
+    let (start, end) = image_span(elf_bytes)?;
+    let span = (start.wrapping_add(load_bias), end.wrapping_add(load_bias));
+
+    Ok(SharedObject::new(name, data, describe_image(elf_bytes, load_bias), span, info))
+}
+
+
+/// Loads a self contained elf file into memory and returns where it ended up. Anything that
+/// needs shared libraries has to be loaded through a `dynamic::LinkMap` instead
 /// # Arguments
 /// * `data` - the full file loaded in memory
 ///
-/// * `allocator` - an allocator to get frames for page table changes and to copy the segments to
+/// * `mapper` - gets frames for the segments and maps them into the page table
 ///
-/// * `page_table` - the page table for the data to be mapped into (must be recursive)
+/// * `page_table` - the page table for the data to be mapped into. The segments are written
+///   through `mapper`, so whether this has to be the active one depends on the mapper
 ///
 /// * `load_location` - a hint to the location in virtual memory
 ///
+/// * `policy` - what to do if the image wants writable and executable memory
+///
 /// #
-pub fn load<Allocator: FrameAllocator>(data: &[u8], allocator: &Allocator, page_table: &mut RecursivePageTable, load_location: LoadLocation) -> Result<x86_64::VirtAddr, ElfLoadError> {
-    let elf_bytes = match ElfBytes::<AnyEndian>::minimal_parse(data) {
-        Ok(elf_bytes) => elf_bytes,
-        Err(err) => return Err(ElfLoadError::ElfHeaderParseFail(err))
-    };
+pub fn load<Mapper: MemoryMapper>(data: &[u8], mapper: &mut Mapper, page_table: &mut Mapper::PageTable, load_location: LoadLocation, policy: WxPolicy) -> Result<LoadedImage, ElfLoadError> {
+    let elf_bytes = parse(data)?;
+    let info = DynamicInfo::parse(&elf_bytes, data)?;
 
-    if elf_bytes.ehdr.e_machine != elf::abi::EM_X86_64 {
-        return Err(WrongInstructionSet)
+    if info.needed().next().is_some() {
+        return Err(ElfLoadError::NeedsLibraries)
     }
 
-    use elf::abi::{
-        ET_DYN,
-        ET_EXEC,
-        ET_REL
-    };
+    let object = load_object(data, "", mapper, page_table, load_location, policy)?;
 
-    
+    // With nothing else loaded the only symbols it can link against are its own
+    dynamic::relocate(core::slice::from_ref(&object), 0, PltBinding::Eager, mapper, page_table)?;
 
-    return match elf_bytes.ehdr.e_type {
-          ET_DYN => load_shared_library(data, allocator, page_table, load_location, elf_bytes),
-          ET_EXEC => load_executable(data, allocator, page_table, load_location, elf_bytes),
-          ET_REL => load_relocatable(data, allocator, page_table, load_location, elf_bytes),
-          _ => Err(IncorrectType)
-    }
+    permissions::protect(&elf_bytes, mapper, page_table, object.image().load_bias)?;
+
+    Ok(object.image())
 }
@@ -0,0 +1,163 @@
+use elf_parser::{ElfBytes, endian::AnyEndian};
+
+use elf_parser::abi::{
+    PT_LOAD,
+    PT_GNU_STACK,
+    PT_GNU_RELRO,
+    PF_W,
+    PF_X
+};
+
+use log::warn;
+
+use x86_64::VirtAddr;
+
+use mem::{MemoryMapper, PagePermissions, PAGE_SIZE};
+
+use crate::{align_down, align_up, ElfLoadError};
+
+/// What to do with an image that wants memory that is both writable and executable (including
+/// an executable stack)
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WxPolicy {
+    /// Don't load it
+    Refuse,
+    /// Load it with the permissions it asked for, but log a warning
+    Warn
+}
+
+fn segment_permissions(flags: u32) -> PagePermissions {
+    PagePermissions {
+        writable: flags & PF_W != 0,
+        executable: flags & PF_X != 0
+    }
+}
+
+/// Calls `apply` with each run of pages covered by PT_LOAD segments and the permissions they
+/// should end up with, as (first page, page count, permissions). A page shared by two segments
+/// gets the permissions of both of them
+fn for_each_run<E>(elf_bytes: &ElfBytes<AnyEndian>, load_bias: u64, mut apply: impl FnMut(u64, u32, PagePermissions) -> Result<(), E>) -> Result<(), E> {
+    let page_size = PAGE_SIZE as u64;
+    let page_count = |start: u64, end: u64| ((end - start) / page_size) as u32;
+
+    let segments = match elf_bytes.segments() {
+        Some(segments) => segments,
+        None => return Ok(())
+    };
+
+    // The run that hasn't been applied yet, since its last page might be shared with the next one
+    let mut pending: Option<(u64, u64, PagePermissions)> = None;
+
+    for phdr in segments.iter().filter(|phdr| phdr.p_type == PT_LOAD) {
+        let permissions = segment_permissions(phdr.p_flags);
+        let start = phdr.p_vaddr.wrapping_add(load_bias);
+        let first = align_down(start, page_size);
+        let end = align_up(start + phdr.p_memsz, page_size);
+
+        if first >= end {
+            continue
+        }
+
+        pending = match pending {
+            Some((run_start, run_end, run_permissions)) if first < run_end => {
+                let shared = run_end - page_size;
+
+                if shared > run_start {
+                    apply(run_start, page_count(run_start, shared), run_permissions)?;
+                }
+
+                let merged = PagePermissions {
+                    writable: run_permissions.writable || permissions.writable,
+                    executable: run_permissions.executable || permissions.executable
+                };
+
+                if end > run_end {
+                    apply(shared, 1, merged)?;
+                    Some((run_end, end, permissions))
+                } else {
+                    // This segment ends on the shared page, so the next one might start on it too
+                    Some((shared, run_end, merged))
+                }
+            },
+            Some((run_start, run_end, run_permissions)) => {
+                apply(run_start, page_count(run_start, run_end), run_permissions)?;
+                Some((first, end, permissions))
+            },
+            None => Some((first, end, permissions))
+        };
+    }
+
+    if let Some((run_start, run_end, run_permissions)) = pending {
+        apply(run_start, page_count(run_start, run_end), run_permissions)?;
+    }
+
+    Ok(())
+}
+
+/// Whether PT_GNU_STACK asks for an executable stack. Everything built by a toolchain from this
+/// century has a PT_GNU_STACK, so one that is missing is treated as asking for a normal stack
+pub(crate) fn wants_executable_stack(elf_bytes: &ElfBytes<AnyEndian>) -> bool {
+    elf_bytes.segments()
+        .and_then(|segments| segments.iter().find(|phdr| phdr.p_type == PT_GNU_STACK))
+        .is_some_and(|phdr| phdr.p_flags & PF_X != 0)
+}
+
+/// Checks the image against the W^X policy before anything is mapped
+pub(crate) fn check(elf_bytes: &ElfBytes<AnyEndian>, policy: WxPolicy) -> Result<(), ElfLoadError> {
+    let mut writable_executable = false;
+
+    let _ = for_each_run(elf_bytes, 0, |_, _, permissions| {
+        writable_executable |= permissions.writable && permissions.executable;
+        Ok::<(), ()>(())
+    });
+
+    let executable_stack = wants_executable_stack(elf_bytes);
+
+    if !writable_executable && !executable_stack {
+        return Ok(())
+    }
+
+    match policy {
+        WxPolicy::Refuse => Err(ElfLoadError::WritableExecutable),
+        WxPolicy::Warn => {
+            if writable_executable {
+                warn!("Loading an image with memory that is both writable and executable");
+            }
+            if executable_stack {
+                warn!("Loading an image that wants an executable stack");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Gives the pages of a loaded image their final permissions: text R+X, rodata R, data RW+NX and
+/// PT_GNU_RELRO ranges read only. This has to happen after relocation since everything gets
+/// mapped writable to begin with
+pub(crate) fn protect<Mapper: MemoryMapper>(elf_bytes: &ElfBytes<AnyEndian>, mapper: &mut Mapper, page_table: &mut Mapper::PageTable, load_bias: u64) -> Result<(), ElfLoadError> {
+    for_each_run(elf_bytes, load_bias, |page, page_count, permissions| {
+        mapper.protect(page_table, VirtAddr::new(page), page_count, permissions)
+            .or(Err(ElfLoadError::MapFailed))
+    })?;
+
+    let relro = elf_bytes.segments()
+        .and_then(|segments| segments.iter().find(|phdr| phdr.p_type == PT_GNU_RELRO));
+
+    if let Some(phdr) = relro {
+        // Only whole pages can be made read only, and the linker pads RELRO out to the end of a
+        // page, so rounding down on both ends is what's wanted (it's what glibc does too)
+        let start = align_down(phdr.p_vaddr.wrapping_add(load_bias), PAGE_SIZE as u64);
+        let end = align_down(phdr.p_vaddr.wrapping_add(load_bias) + phdr.p_memsz, PAGE_SIZE as u64);
+
+        if start < end {
+            mapper.protect(
+                page_table,
+                VirtAddr::new(start),
+                ((end - start) / PAGE_SIZE as u64) as u32,
+                PagePermissions { writable: false, executable: false }
+            ).or(Err(ElfLoadError::MapFailed))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use x86_64::VirtAddr;
+use x86_64::registers::model_specific::FsBase;
+
+/// The size of the thread control block that sits at the thread pointer. Only the first word
+/// (the pointer to itself) is required by the ABI, but glibc style code expects the stack
+/// protector canary at %fs:0x28 so there's space left for that and a bit more
+pub const TCB_SIZE: usize = 64;
+
+/// The PT_TLS segment of a loaded image. Every thread gets its own copy of this, where the first
+/// `file_size` bytes come from `image` (.tdata) and the rest up to `memory_size` are zeroed (.tbss)
+#[derive(Clone, Copy)]
+pub struct TlsTemplate {
+    /// Where the initialisation image was loaded in memory
+    pub image: VirtAddr,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub alignment: u64
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    /// The memory given for the block was smaller than `TlsTemplate::block_size`
+    BlockTooSmall,
+    /// The memory given for the block wasn't aligned to `TlsTemplate::block_alignment`
+    BlockMisaligned
+}
+
+impl TlsTemplate {
+    /// How far below the thread pointer the TLS data starts. This is what the linker works
+    /// `%fs:` offsets out from, so it only depends on the segment's own alignment
+    pub fn tls_size(&self) -> usize {
+        let alignment = self.alignment.max(1);
+        self.memory_size.next_multiple_of(alignment) as usize
+    }
+
+    /// The offset from the start of a block to the thread pointer. In the x86_64 (variant II)
+    /// layout the TLS data sits `tls_size` below the thread pointer, with any padding needed to
+    /// keep the TCB aligned before it
+    pub fn thread_pointer_offset(&self) -> usize {
+        self.tls_size().next_multiple_of(self.block_alignment())
+    }
+
+    /// The number of bytes needed for one thread's block, including the TCB
+    pub fn block_size(&self) -> usize {
+        self.thread_pointer_offset() + TCB_SIZE
+    }
+
+    /// The alignment a block has to have so that the thread pointer (and therefore the TLS data)
+    /// ends up correctly aligned
+    pub fn block_alignment(&self) -> usize {
+        (self.alignment as usize).max(core::mem::align_of::<u64>())
+    }
+
+    /// Sets up a thread's TLS block in `block` and returns what its thread pointer should be
+    ///
+    /// # Safety
+    /// The image this template came from must still be mapped in the active address space, and
+    /// the returned thread pointer is only valid for as long as `block` is
+    pub unsafe fn initialise_block(&self, block: &mut [u8]) -> Result<VirtAddr, TlsError> {
+        if block.len() < self.block_size() {
+            return Err(TlsError::BlockTooSmall)
+        }
+
+        if !(block.as_ptr() as usize).is_multiple_of(self.block_alignment()) {
+            return Err(TlsError::BlockMisaligned)
+        }
+
+        let tp_offset = self.thread_pointer_offset();
+
+        // Anything not covered by the template (.tbss, alignment padding and the TCB) starts as 0
+        block.fill(0);
+
+        // The linker puts the TLS data at tp - tls_size, so any padding from rounding the block up
+        // to its alignment goes before .tdata
+        let data_start = tp_offset - self.tls_size();
+
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                self.image.as_ptr::<u8>(),
+                block.as_mut_ptr().add(data_start),
+                self.file_size as usize
+            );
+        }
+
+        let thread_pointer = VirtAddr::from_ptr(block.as_ptr()) + tp_offset as u64;
+
+        // The first word of the TCB points to itself so %fs:0 can be used to find the thread pointer
+        block[tp_offset..tp_offset + 8].copy_from_slice(&thread_pointer.as_u64().to_le_bytes());
+
+        Ok(thread_pointer)
+    }
+}
+
+/// Points %fs at a thread pointer returned from `TlsTemplate::initialise_block` for the current
+/// CPU. User threads need this value saved with the rest of their context so it can be restored
+/// whenever they're switched back to
+///
+/// # Safety
+/// Anything using thread locals on this CPU will start using the new block, so it has to be valid
+pub unsafe fn set_thread_pointer(thread_pointer: VirtAddr) {
+    FsBase::write(thread_pointer);
+}
@@ -0,0 +1,120 @@
+use x86_64::VirtAddr;
+
+use mem::PAGE_SIZE;
+
+use crate::LoadedImage;
+
+// Auxiliary vector entry types from the SysV x86_64 ABI. The elf crate doesn't have these
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_BASE: u64 = 7;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_RANDOM: u64 = 25;
+
+/// The number of (type, value) pairs in the auxiliary vector, including the AT_NULL at the end
+const AUXV_ENTRIES: usize = 8;
+
+#[derive(Debug)]
+pub enum StackSetupError {
+    /// The arguments, environment and auxiliary vector don't fit in the given stack
+    StackTooSmall,
+    /// `stack_top` has to be 16 byte aligned
+    StackMisaligned
+}
+
+/// Lays out the initial stack a SysV program expects to find at its entry point and returns what
+/// rsp should be set to. From the returned address upward there is argc, the argv pointers, a
+/// null, the envp pointers, a null, then the auxiliary vector. The strings themselves and the
+/// AT_RANDOM bytes are put at the very top of the stack
+///
+/// # Arguments
+/// * `stack` - the memory that is mapped for the stack. This doesn't need to be accessed through
+///   the same address the program will see it at (e.g. it's in another address space)
+///
+/// * `stack_top` - the address just past the end of `stack` as the program will see it
+///
+/// * `image` - the program, as returned by `crate::load`
+///
+/// * `interpreter` - the dynamic linker that was loaded for the program, if there is one. This
+///   is what AT_BASE points to
+///
+/// * `arguments` and `environment` - argv and envp, without the null terminators
+///
+/// * `random` - 16 random bytes for AT_RANDOM (used by libc for stack protector canaries)
+pub fn build_initial_stack(stack: &mut [u8], stack_top: VirtAddr, image: &LoadedImage, interpreter: Option<&LoadedImage>, arguments: &[&str], environment: &[&str], random: &[u8; 16]) -> Result<VirtAddr, StackSetupError> {
+    if !stack_top.is_aligned(16u64) {
+        return Err(StackSetupError::StackMisaligned)
+    }
+
+    let top = stack_top.as_u64();
+    let bottom = top - stack.len() as u64;
+
+    // Turns an address as the program sees it into an index into `stack`
+    let index = |addr: u64| (addr - bottom) as usize;
+
+    let random_addr = top - random.len() as u64;
+
+    let strings_size: u64 = arguments.iter()
+        .chain(environment.iter())
+        .map(|string| string.len() as u64 + 1)
+        .sum();
+
+    let strings_addr = random_addr.checked_sub(strings_size)
+        .filter(|&addr| addr >= bottom)
+        .ok_or(StackSetupError::StackTooSmall)?;
+
+    let word_count = 1 + arguments.len() + 1 + environment.len() + 1 + AUXV_ENTRIES * 2;
+
+    // rsp has to be 16 byte aligned when it points at argc
+    let table_addr = (strings_addr & !15).checked_sub(word_count as u64 * 8)
+        .map(|addr| addr & !15)
+        .filter(|&addr| addr >= bottom)
+        .ok_or(StackSetupError::StackTooSmall)?;
+
+    stack[index(random_addr)..index(top)].copy_from_slice(random);
+
+    let mut table_cursor = index(table_addr);
+    let mut push_word = |stack: &mut [u8], word: u64| {
+        stack[table_cursor..table_cursor + 8].copy_from_slice(&word.to_le_bytes());
+        table_cursor += 8;
+    };
+
+    push_word(stack, arguments.len() as u64);
+
+    // The strings get copied in the same order their pointers are written, with argv's pointers
+    // ending in a null and then the same for envp
+    let mut string_addr = strings_addr;
+    for strings in [arguments, environment] {
+        for string in strings {
+            let start = index(string_addr);
+            stack[start..start + string.len()].copy_from_slice(string.as_bytes());
+            stack[start + string.len()] = 0;
+
+            push_word(stack, string_addr);
+            string_addr += string.len() as u64 + 1;
+        }
+
+        push_word(stack, 0);
+    }
+
+    let auxv: [(u64, u64); AUXV_ENTRIES] = [
+        (AT_PHDR, image.program_headers.map_or(0, |addr| addr.as_u64())),
+        (AT_PHENT, image.program_header_size as u64),
+        (AT_PHNUM, image.program_header_count as u64),
+        (AT_PAGESZ, PAGE_SIZE as u64),
+        (AT_BASE, interpreter.map_or(0, |interpreter| interpreter.load_bias)),
+        (AT_ENTRY, image.entry.as_u64()),
+        (AT_RANDOM, random_addr),
+        (AT_NULL, 0)
+    ];
+
+    for (entry_type, value) in auxv {
+        push_word(stack, entry_type);
+        push_word(stack, value);
+    }
+
+    Ok(VirtAddr::new(table_addr))
+}
@@ -0,0 +1,138 @@
+//! An in-memory address space for running the loader on the host. Pages are kept in a map instead
+//! of a page table, and all reads and writes go through it, so tests can look at exactly what got
+//! mapped, with what permissions, and what ended up in it
+
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+use mem::{MemoryMapper, PagePermissions, PAGE_SIZE};
+use x86_64::{PhysAddr, VirtAddr};
+
+/// What freshly mapped pages are filled with, so that anything the loader forgets to zero stands
+/// out
+pub const GARBAGE: u8 = 0xcc;
+
+pub struct MockPage {
+    pub frame: PhysAddr,
+    pub permissions: PagePermissions,
+    pub data: Box<[u8; PAGE_SIZE]>
+}
+
+#[derive(Default)]
+pub struct MockAddressSpace {
+    pub pages: BTreeMap<u64, MockPage>
+}
+
+impl MockAddressSpace {
+    /// (page, writable, executable) for every mapped page, in address order
+    pub fn mappings(&self) -> Vec<(u64, bool, bool)> {
+        self.pages.iter()
+            .map(|(&page, mapped)| (page, mapped.permissions.writable, mapped.permissions.executable))
+            .collect()
+    }
+
+    pub fn read(&self, address: u64, size: usize) -> Vec<u8> {
+        (address..address + size as u64)
+            .map(|byte| {
+                let page = self.pages.get(&(byte & !(PAGE_SIZE as u64 - 1)))
+                    .unwrap_or_else(|| panic!("read from unmapped address {byte:#x}"));
+                page.data[(byte % PAGE_SIZE as u64) as usize]
+            })
+            .collect()
+    }
+
+    pub fn read_u64(&self, address: u64) -> u64 {
+        u64::from_le_bytes(self.read(address, 8).try_into().unwrap())
+    }
+
+    fn page_mut(&mut self, byte: u64) -> &mut MockPage {
+        self.pages.get_mut(&(byte & !(PAGE_SIZE as u64 - 1)))
+            .unwrap_or_else(|| panic!("write to unmapped address {byte:#x}"))
+    }
+
+    fn write_byte(&mut self, byte: u64, value: u8) {
+        let page = self.page_mut(byte);
+
+        // The loader should only ever write before it sets the final permissions
+        assert!(page.permissions.writable, "write to read only address {byte:#x}");
+
+        page.data[(byte % PAGE_SIZE as u64) as usize] = value;
+    }
+}
+
+/// Hands out made up frames one after the other, and never reuses them
+#[derive(Default)]
+pub struct MockMapper {
+    next_frame: u64
+}
+
+impl MemoryMapper for MockMapper {
+    type PageTable = MockAddressSpace;
+
+    type MapErrorType = &'static str;
+    type UnmapErrorType = &'static str;
+    type MapAllocErrorType = &'static str;
+    type ProtectErrorType = &'static str;
+
+    fn map(&mut self, page_table: &mut MockAddressSpace, page: VirtAddr, frame: PhysAddr) -> Result<(), &'static str> {
+        if !page.is_aligned(PAGE_SIZE as u64) {
+            return Err("Page start not aligned correctly")
+        }
+
+        if page_table.pages.contains_key(&page.as_u64()) {
+            return Err("Page is already mapped")
+        }
+
+        page_table.pages.insert(page.as_u64(), MockPage {
+            frame,
+            permissions: PagePermissions { writable: true, executable: false },
+            data: Box::new([GARBAGE; PAGE_SIZE])
+        });
+
+        Ok(())
+    }
+
+    fn unmap(&mut self, page_table: &mut MockAddressSpace, page: VirtAddr) -> Result<PhysAddr, &'static str> {
+        page_table.pages.remove(&page.as_u64())
+            .map(|page| page.frame)
+            .ok_or("Page isn't mapped")
+    }
+
+    fn map_alloc(&mut self, page_table: &mut MockAddressSpace, page: VirtAddr, page_count: u32) -> Result<u64, &'static str> {
+        for offset in 0..page_count as u64 {
+            let frame = PhysAddr::new(self.next_frame);
+            self.next_frame += PAGE_SIZE as u64;
+
+            self.map(page_table, page + offset * PAGE_SIZE as u64, frame)?;
+        }
+
+        Ok(page.as_u64())
+    }
+
+    fn protect(&mut self, page_table: &mut MockAddressSpace, page: VirtAddr, page_count: u32, permissions: PagePermissions) -> Result<(), &'static str> {
+        for offset in 0..page_count as u64 {
+            page_table.pages.get_mut(&(page.as_u64() + offset * PAGE_SIZE as u64))
+                .ok_or("Page isn't mapped")?
+                .permissions = permissions;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn write(&mut self, page_table: &mut MockAddressSpace, address: VirtAddr, data: &[u8]) {
+        for (offset, &value) in data.iter().enumerate() {
+            page_table.write_byte(address.as_u64() + offset as u64, value);
+        }
+    }
+
+    unsafe fn fill(&mut self, page_table: &mut MockAddressSpace, address: VirtAddr, value: u8, size: usize) {
+        for offset in 0..size as u64 {
+            page_table.write_byte(address.as_u64() + offset, value);
+        }
+    }
+
+    unsafe fn read(&self, page_table: &MockAddressSpace, address: VirtAddr, buffer: &mut [u8]) {
+        buffer.copy_from_slice(&page_table.read(address.as_u64(), buffer.len()));
+    }
+}
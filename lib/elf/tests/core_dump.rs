@@ -0,0 +1,187 @@
+//! Dumps a process made by loading static.elf into a mock address space, then reads the core file
+//! back with the elf parser, the same way gdb would
+
+mod common;
+
+use common::{MockAddressSpace, MockMapper};
+
+use elf::core_dump::{self, CoreDumpError, CoreDumpSink, CoreRegion, RegisterState, ThreadState};
+use elf::permissions::WxPolicy;
+use elf::LoadLocation;
+use elf_parser::abi::{ET_CORE, EM_X86_64, NT_PRSTATUS, PF_R, PF_W, PF_X, PT_LOAD, PT_NOTE};
+use elf_parser::endian::AnyEndian;
+use elf_parser::note::Note;
+use elf_parser::ElfBytes;
+use x86_64::VirtAddr;
+
+const STATIC: &[u8] = include_bytes!("fixtures/static.elf");
+
+const PAGE_SIZE: usize = 4096;
+
+/// Offsets into `struct elf_prstatus`
+const PR_CURSIG: usize = 12;
+const PR_PID: usize = 32;
+const PR_PPID: usize = 36;
+const PR_REG: usize = 112;
+
+/// Indices into `user_regs_struct`
+const REG_RIP: usize = 16;
+const REG_RSP: usize = 19;
+
+/// Collects the whole file
+struct File(Vec<u8>);
+
+impl CoreDumpSink for File {
+    type WriteErrorType = ();
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Takes `remaining` bytes and then fails
+struct FullSink {
+    remaining: usize
+}
+
+impl CoreDumpSink for FullSink {
+    type WriteErrorType = &'static str;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), &'static str> {
+        self.remaining = self.remaining.checked_sub(bytes.len()).ok_or("disk full")?;
+        Ok(())
+    }
+}
+
+/// (start, contents, flags) of every page static.elf was loaded into
+fn loaded_pages() -> Vec<(u64, Vec<u8>, u32)> {
+    let mut space = MockAddressSpace::default();
+    elf::load(STATIC, &mut MockMapper::default(), &mut space, LoadLocation::Any, WxPolicy::Refuse).unwrap();
+
+    space.mappings()
+        .into_iter()
+        .map(|(page, writable, executable)| {
+            let flags = PF_R | if writable { PF_W } else { 0 } | if executable { PF_X } else { 0 };
+            (page, space.read(page, PAGE_SIZE), flags)
+        })
+        .collect()
+}
+
+fn regions(pages: &[(u64, Vec<u8>, u32)]) -> Vec<CoreRegion<'_>> {
+    pages.iter()
+        .map(|(start, data, flags)| CoreRegion {
+            start: VirtAddr::new(*start),
+            data,
+            memory_size: PAGE_SIZE as u64,
+            flags: *flags
+        })
+        .collect()
+}
+
+fn thread(pid: u32, rip: u64, rsp: u64) -> ThreadState {
+    ThreadState {
+        pid,
+        parent_pid: 1,
+        signal: 11,
+        registers: RegisterState { rip, rsp, ..Default::default() }
+    }
+}
+
+fn dump(threads: &[ThreadState], regions: &[CoreRegion]) -> Vec<u8> {
+    let mut file = File(Vec::new());
+    let written = core_dump::write_core_dump(&mut file, threads, regions).unwrap();
+
+    assert_eq!(written, file.0.len() as u64);
+    file.0
+}
+
+fn word(bytes: &[u8], index: usize) -> u64 {
+    u64::from_le_bytes(bytes[index * 8..index * 8 + 8].try_into().unwrap())
+}
+
+#[test]
+fn every_region_becomes_a_load_segment_with_its_contents() {
+    let pages = loaded_pages();
+    let file = dump(&[thread(7, 0x401000, 0x7fff_0000_0000)], &regions(&pages));
+
+    let core = ElfBytes::<AnyEndian>::minimal_parse(&file).unwrap();
+    assert_eq!(core.ehdr.e_type, ET_CORE);
+    assert_eq!(core.ehdr.e_machine, EM_X86_64);
+
+    let segments: Vec<_> = core.segments().unwrap().iter().collect();
+    assert_eq!(segments.len(), pages.len() + 1);
+    assert_eq!(segments[0].p_type, PT_NOTE);
+
+    for (segment, (start, data, flags)) in segments[1..].iter().zip(&pages) {
+        assert_eq!(segment.p_type, PT_LOAD);
+        assert_eq!(segment.p_vaddr, *start);
+        assert_eq!(segment.p_flags, *flags);
+        assert_eq!(segment.p_filesz, PAGE_SIZE as u64);
+        assert_eq!(segment.p_memsz, PAGE_SIZE as u64);
+        assert_eq!(segment.p_offset % PAGE_SIZE as u64, 0);
+        assert_eq!(core.segment_data(segment).unwrap(), data.as_slice());
+    }
+
+    // The text page, as it was loaded
+    assert_eq!(pages[1].2, PF_R | PF_X);
+    assert_eq!(pages[1].1[..5], [0xb8, 0x3c, 0x00, 0x00, 0x00]);
+}
+
+#[test]
+fn every_thread_gets_a_prstatus_note_in_order() {
+    let pages = loaded_pages();
+    let threads = [thread(7, 0x401000, 0x7fff_0000_0000), thread(8, 0x401005, 0x7ffe_0000_0000)];
+    let file = dump(&threads, &regions(&pages));
+
+    let core = ElfBytes::<AnyEndian>::minimal_parse(&file).unwrap();
+    let notes_segment = core.segments().unwrap().get(0).unwrap();
+
+    let notes: Vec<_> = core.segment_data_as_notes(&notes_segment).unwrap()
+        .map(|note| match note {
+            Note::Unknown(note) => note,
+            _ => panic!("only NT_PRSTATUS notes should be written")
+        })
+        .collect();
+
+    assert_eq!(notes.len(), threads.len());
+
+    for (note, thread) in notes.iter().zip(&threads) {
+        assert_eq!(note.name, "CORE");
+        assert_eq!(note.n_type, NT_PRSTATUS);
+        assert_eq!(note.desc.len(), 336);
+
+        assert_eq!(note.desc[PR_CURSIG..PR_CURSIG + 2], 11u16.to_le_bytes());
+        assert_eq!(note.desc[PR_PID..PR_PID + 4], thread.pid.to_le_bytes());
+        assert_eq!(note.desc[PR_PPID..PR_PPID + 4], 1u32.to_le_bytes());
+
+        let registers = &note.desc[PR_REG..];
+        assert_eq!(word(registers, REG_RIP), thread.registers.rip);
+        assert_eq!(word(registers, REG_RSP), thread.registers.rsp);
+    }
+}
+
+#[test]
+fn zeroes_left_out_of_a_region_are_still_in_memory() {
+    let data = [0xab; 100];
+    let region = CoreRegion { start: VirtAddr::new(0x1000), data: &data, memory_size: 0x3000, flags: PF_R | PF_W };
+
+    let file = dump(&[], &[region]);
+    let core = ElfBytes::<AnyEndian>::minimal_parse(&file).unwrap();
+    let load = core.segments().unwrap().get(1).unwrap();
+
+    assert_eq!(load.p_filesz, 100);
+    assert_eq!(load.p_memsz, 0x3000);
+    assert_eq!(core.segment_data(&load).unwrap(), data);
+}
+
+#[test]
+fn sink_errors_are_passed_back() {
+    let pages = loaded_pages();
+    let mut sink = FullSink { remaining: 1000 };
+
+    assert!(matches!(
+        core_dump::write_core_dump(&mut sink, &[thread(7, 0, 0)], &regions(&pages)),
+        Err(CoreDumpError::Sink("disk full"))
+    ));
+}
@@ -0,0 +1,165 @@
+//! Links dynamic.elf against libgreet.so and libbase.so with a LinkMap (see `fixtures/build.sh`),
+//! checking where everything went and what every relocation wrote
+
+mod common;
+
+use common::{MockAddressSpace, MockMapper};
+
+use elf::dynamic::{LibrarySource, LinkMap, PltBinding};
+use elf::permissions::WxPolicy;
+use elf::{ElfLoadError, LoadLocation};
+use x86_64::VirtAddr;
+
+const DYNAMIC: &[u8] = include_bytes!("fixtures/dynamic.elf");
+const LIBGREET: &[u8] = include_bytes!("fixtures/libgreet.so");
+const LIBBASE: &[u8] = include_bytes!("fixtures/libbase.so");
+const BAD_RELOCATION: &[u8] = include_bytes!("fixtures/bad_relocation.so");
+
+const SEARCH_PATH: &[&str] = &["/lib", "/usr/lib"];
+
+/// The program goes at the default base, then each library a page after the one before it
+const PROGRAM: u64 = 0x40_0000;
+const GREET: u64 = PROGRAM + 0x5000;
+const BASE: u64 = GREET + 0x5000;
+
+const RESOLVER: u64 = 0x7fff_0000_0000;
+
+/// (directory, name, file) for every library there is
+struct Libraries(Vec<(&'static str, &'static str, &'static [u8])>);
+
+impl LibrarySource<'static> for Libraries {
+    fn open(&mut self, directory: &str, name: &str) -> Option<&'static [u8]> {
+        self.0.iter()
+            .find(|library| library.0 == directory && library.1 == name)
+            .map(|library| library.2)
+    }
+}
+
+fn libraries() -> Libraries {
+    Libraries(vec![
+        ("/usr/lib", "libgreet.so", LIBGREET),
+        ("/usr/lib", "libbase.so", LIBBASE)
+    ])
+}
+
+struct Linked {
+    link_map: LinkMap<'static>,
+    mapper: MockMapper,
+    space: MockAddressSpace
+}
+
+fn link(binding: PltBinding, mut source: Libraries) -> Result<Linked, ElfLoadError> {
+    let mut link_map = LinkMap::new(SEARCH_PATH, binding, WxPolicy::Refuse);
+    let mut mapper = MockMapper::default();
+    let mut space = MockAddressSpace::default();
+
+    let image = link_map.load(DYNAMIC, &mut mapper, &mut space, LoadLocation::Any, &mut source)?;
+    assert_eq!(image.load_bias, PROGRAM);
+
+    Ok(Linked { link_map, mapper, space })
+}
+
+#[test]
+fn libraries_are_loaded_breadth_first_after_the_program() {
+    let linked = link(PltBinding::Eager, libraries()).unwrap();
+
+    let objects: Vec<(&str, u64)> = linked.link_map.objects()
+        .map(|(name, image)| (name, image.load_bias))
+        .collect();
+
+    assert_eq!(objects, [("", PROGRAM), ("libgreet.so", GREET), ("libbase.so", BASE)]);
+
+    // Nothing is mapped in the page between objects
+    assert!(!linked.space.pages.contains_key(&(GREET - 0x1000)));
+    assert!(!linked.space.pages.contains_key(&(BASE - 0x1000)));
+}
+
+#[test]
+fn symbols_are_found_through_both_kinds_of_hash_table() {
+    let linked = link(PltBinding::Eager, libraries()).unwrap();
+
+    // libgreet.so only has a GNU hash table and libbase.so only has a SysV one
+    assert_eq!(linked.link_map.lookup("greet"), Some(VirtAddr::new(GREET + 0x1000)));
+    assert_eq!(linked.link_map.lookup("base_value"), Some(VirtAddr::new(BASE + 0x2000)));
+    assert_eq!(linked.link_map.lookup("missing"), None);
+
+    // The program's copy comes first in the search order
+    assert_eq!(linked.link_map.lookup("greeting"), Some(VirtAddr::new(PROGRAM + 0x3008)));
+}
+
+#[test]
+fn relocations_point_across_objects() {
+    let linked = link(PltBinding::Eager, libraries()).unwrap();
+    let space = &linked.space;
+
+    // R_X86_64_GLOB_DAT in libgreet.so against base_value in libbase.so
+    assert_eq!(space.read_u64(GREET + 0x2fe0), BASE + 0x2000);
+
+    // R_X86_64_COPY of greeting into the program, which libgreet.so's own R_X86_64_64 against it
+    // has to point at rather than at the original
+    assert_eq!(space.read(PROGRAM + 0x3008, 16), b"hello, linker!\0\0");
+    assert_eq!(space.read_u64(GREET + 0x3010), PROGRAM + 0x3008);
+
+    // R_X86_64_JUMP_SLOT, bound straight away
+    assert_eq!(space.read_u64(PROGRAM + 0x3000), GREET + 0x1000);
+}
+
+#[test]
+fn lazy_slots_go_through_the_plt_until_resolved() {
+    let Linked { link_map, mut mapper, mut space } = link(PltBinding::Lazy { resolver: VirtAddr::new(RESOLVER) }, libraries()).unwrap();
+
+    // GOT[1] is the program's index in the link map and GOT[2] the resolver
+    assert_eq!(space.read_u64(PROGRAM + 0x2ff0), 0);
+    assert_eq!(space.read_u64(PROGRAM + 0x2ff8), RESOLVER);
+
+    // The slot starts at the push after the jump in greet@plt, moved with the program
+    assert_eq!(space.read_u64(PROGRAM + 0x3000), PROGRAM + 0x1016);
+
+    let greet = link_map.resolve_lazy(&mut mapper, &mut space, 0, 0).unwrap();
+
+    assert_eq!(greet, VirtAddr::new(GREET + 0x1000));
+    assert_eq!(space.read_u64(PROGRAM + 0x3000), GREET + 0x1000);
+}
+
+#[test]
+fn resolving_something_that_isnt_there_fails() {
+    let Linked { link_map, mut mapper, mut space } = link(PltBinding::Lazy { resolver: VirtAddr::new(RESOLVER) }, libraries()).unwrap();
+
+    assert!(matches!(
+        link_map.resolve_lazy(&mut mapper, &mut space, 3, 0),
+        Err(ElfLoadError::UndefinedSymbol)
+    ));
+    assert!(matches!(
+        link_map.resolve_lazy(&mut mapper, &mut space, 0, 1),
+        Err(ElfLoadError::MalformedSegment)
+    ));
+}
+
+#[test]
+fn libraries_are_only_looked_for_on_the_search_path() {
+    let source = Libraries(vec![
+        ("/opt/lib", "libgreet.so", LIBGREET),
+        ("/usr/lib", "libbase.so", LIBBASE)
+    ]);
+
+    assert!(matches!(link(PltBinding::Eager, source), Err(ElfLoadError::MissingLibrary)));
+}
+
+#[test]
+fn libraries_needed_by_libraries_have_to_be_found_too() {
+    let source = Libraries(vec![("/lib", "libgreet.so", LIBGREET)]);
+
+    assert!(matches!(link(PltBinding::Eager, source), Err(ElfLoadError::MissingLibrary)));
+}
+
+#[test]
+fn relocation_outside_the_library_is_rejected() {
+    // The mock address space panics on writes to anything unmapped, so this also makes sure
+    // nothing was written before the relocation was checked
+    let source = Libraries(vec![
+        ("/lib", "libgreet.so", BAD_RELOCATION),
+        ("/lib", "libbase.so", LIBBASE)
+    ]);
+
+    assert!(matches!(link(PltBinding::Eager, source), Err(ElfLoadError::MalformedSegment)));
+}
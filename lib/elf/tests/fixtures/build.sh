@@ -0,0 +1,43 @@
+#!/bin/sh
+# Rebuilds the fixtures the loader tests run against. The outputs are checked in since the tests
+# assert exact addresses, which depend on the linker that made them (these came from GNU ld 2.40)
+set -e
+cd "$(dirname "$0")"
+
+LDFLAGS="--build-id=none -z noexecstack -z separate-code"
+
+as static.s -o static.o
+ld $LDFLAGS -static static.o -o static.elf
+
+# The same again with a build-id
+ld $LDFLAGS --build-id=sha1 -static static.o -o build_id.elf
+
+as pie.s -o pie.o
+ld $LDFLAGS -pie --no-dynamic-linker -z relro -z now pie.o -o pie.elf
+
+as tls.s -o tls.o
+ld $LDFLAGS -static tls.o -o tls.elf
+
+as tls_small.s -o tls_small.o
+ld $LDFLAGS -static tls_small.o -o tls_small.elf
+
+as shared_page.s -o shared_page.o
+ld $LDFLAGS -static -T shared_page.ld shared_page.o -o shared_page.elf
+
+# dynamic.elf needs libgreet.so, which needs libbase.so
+as libbase.s -o libbase.o
+ld $LDFLAGS -shared -soname libbase.so --hash-style=sysv libbase.o -o libbase.so
+
+as libgreet.s -o libgreet.o
+ld $LDFLAGS -shared -soname libgreet.so --hash-style=gnu libgreet.o libbase.so -o libgreet.so
+
+as dynamic.s -o dynamic.o
+ld $LDFLAGS -pie --no-dynamic-linker -z relro -rpath-link . dynamic.o libgreet.so -o dynamic.elf
+
+# ET_REL, which the loader doesn't support yet
+cp static.o relocatable.o
+
+rm static.o pie.o tls.o tls_small.o shared_page.o libbase.o libgreet.o dynamic.o
+
+# Broken copies of static.elf and libgreet.so, and build_id.elf without its PT_NOTE
+python3 malformed.py
@@ -0,0 +1,11 @@
+# A PIE that needs libgreet.so, calling into it through the PLT and with a copy of its data
+
+    .text
+    .globl _start
+_start:
+    # R_X86_64_JUMP_SLOT
+    call greet@PLT
+    # R_X86_64_COPY, since a PIE can't have text relocations
+    lea greeting(%rip), %rdi
+    mov $60, %eax
+    syscall
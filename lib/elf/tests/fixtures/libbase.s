@@ -0,0 +1,9 @@
+# The library at the bottom of the dynamic fixtures, only needed by libgreet.so. It's linked with
+# just a SysV hash table so lookups through both kinds get used
+
+    .data
+    .globl base_value
+    .type base_value, @object
+    .size base_value, 8
+base_value:
+    .quad 0x42
@@ -0,0 +1,25 @@
+# A library needed by dynamic.elf, which itself needs libbase.so. It's linked with just a GNU hash
+# table
+
+    .text
+    .globl greet
+    .type greet, @function
+greet:
+    # R_X86_64_GLOB_DAT against libbase.so
+    mov base_value@GOTPCREL(%rip), %rax
+    mov (%rax), %rax
+    ret
+
+    .data
+    .globl greeting
+    .type greeting, @object
+    .size greeting, 16
+greeting:
+    .ascii "hello, linker!\0\0"
+
+    # R_X86_64_64 against greeting, which dynamic.elf has a copy of that this should point to
+    .globl greeting_pointer
+    .type greeting_pointer, @object
+    .size greeting_pointer, 8
+greeting_pointer:
+    .quad greeting
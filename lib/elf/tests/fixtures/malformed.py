@@ -0,0 +1,119 @@
+# Makes broken copies of static.elf and libgreet.so for the loader and inspector to reject, and
+# a copy of build_id.elf for the inspector
+
+import struct
+
+with open("static.elf", "rb") as f:
+    data = bytearray(f.read())
+
+(phoff,) = struct.unpack_from("<Q", data, 0x20)
+(phentsize, phnum) = struct.unpack_from("<HH", data, 0x36)
+
+PT_LOAD = 1
+PF_X, PF_W = 1, 2
+
+
+def load_headers(data):
+    """The offsets of the PT_LOAD program headers"""
+    for index in range(phnum):
+        offset = phoff + index * phentsize
+        (p_type,) = struct.unpack_from("<I", data, offset)
+        if p_type == PT_LOAD:
+            yield offset
+
+
+def executable_header(data):
+    for offset in load_headers(data):
+        (p_flags,) = struct.unpack_from("<I", data, offset + 4)
+        if p_flags & PF_X:
+            return offset
+
+
+# Cut off partway through the program headers
+with open("truncated.elf", "wb") as f:
+    f.write(data[:phoff + phentsize // 2])
+
+# Built for aarch64 (EM_AARCH64) instead
+wrong_machine = bytearray(data)
+struct.pack_into("<H", wrong_machine, 0x12, 183)
+with open("wrong_machine.elf", "wb") as f:
+    f.write(wrong_machine)
+
+# The text segment says it has more in the file than in memory
+file_too_big = bytearray(data)
+text = executable_header(file_too_big)
+(p_memsz,) = struct.unpack_from("<Q", file_too_big, text + 0x28)
+struct.pack_into("<Q", file_too_big, text + 0x20, p_memsz + 1)
+with open("file_too_big.elf", "wb") as f:
+    f.write(file_too_big)
+
+# The text segment is writable as well as executable
+writable_text = bytearray(data)
+text = executable_header(writable_text)
+(p_flags,) = struct.unpack_from("<I", writable_text, text + 4)
+struct.pack_into("<I", writable_text, text + 4, p_flags | PF_W)
+with open("writable_text.elf", "wb") as f:
+    f.write(writable_text)
+
+# libgreet.so with its first relocation (the GLOB_DAT) pointed far outside the library
+with open("libgreet.so", "rb") as f:
+    bad_relocation = bytearray(f.read())
+
+(shoff,) = struct.unpack_from("<Q", bad_relocation, 0x28)
+(shentsize, shnum) = struct.unpack_from("<HH", bad_relocation, 0x3a)
+
+SHT_RELA = 4
+
+for index in range(shnum):
+    offset = shoff + index * shentsize
+    (sh_type,) = struct.unpack_from("<I", bad_relocation, offset + 4)
+    if sh_type == SHT_RELA:
+        (sh_offset,) = struct.unpack_from("<Q", bad_relocation, offset + 0x18)
+        struct.pack_into("<Q", bad_relocation, sh_offset, 0x1000_0000)
+        break
+
+with open("bad_relocation.so", "wb") as f:
+    f.write(bad_relocation)
+
+# build_id.elf with its PT_NOTE turned into a PT_NULL, so the build-id is only in a section
+with open("build_id.elf", "rb") as f:
+    section_build_id = bytearray(f.read())
+
+(note_phoff,) = struct.unpack_from("<Q", section_build_id, 0x20)
+(note_phentsize, note_phnum) = struct.unpack_from("<HH", section_build_id, 0x36)
+
+PT_NULL, PT_NOTE = 0, 4
+
+for index in range(note_phnum):
+    offset = note_phoff + index * note_phentsize
+    (p_type,) = struct.unpack_from("<I", section_build_id, offset)
+    if p_type == PT_NOTE:
+        struct.pack_into("<I", section_build_id, offset, PT_NULL)
+
+with open("section_build_id.elf", "wb") as f:
+    f.write(section_build_id)
+
+# libgreet.so with greeting (in both symbol tables) claiming to be as big as the address space
+with open("libgreet.so", "rb") as f:
+    huge_symbol = bytearray(f.read())
+
+SHT_SYMTAB, SHT_DYNSYM = 2, 11
+
+for index in range(shnum):
+    offset = shoff + index * shentsize
+    (sh_type,) = struct.unpack_from("<I", huge_symbol, offset + 4)
+    if sh_type not in (SHT_SYMTAB, SHT_DYNSYM):
+        continue
+
+    (sh_link,) = struct.unpack_from("<I", huge_symbol, offset + 0x28)
+    (sh_offset, sh_size) = struct.unpack_from("<QQ", huge_symbol, offset + 0x18)
+    (strtab_offset,) = struct.unpack_from("<Q", huge_symbol, shoff + sh_link * shentsize + 0x18)
+
+    for symbol in range(sh_offset, sh_offset + sh_size, 24):
+        (st_name,) = struct.unpack_from("<I", huge_symbol, symbol)
+        name_start = strtab_offset + st_name
+        if huge_symbol[name_start:huge_symbol.index(0, name_start)] == b"greeting":
+            struct.pack_into("<Q", huge_symbol, symbol + 16, 0xffff_ffff_ffff_ffff)
+
+with open("huge_symbol.so", "wb") as f:
+    f.write(huge_symbol)
@@ -0,0 +1,26 @@
+# A position independent executable. The pointers have to be fixed up with R_X86_64_RELATIVE
+# relocations, and the one in .data.rel.ro ends up inside PT_GNU_RELRO
+
+    .text
+    .globl _start
+_start:
+    lea message(%rip), %rdi
+    mov $60, %eax
+    xor %edi, %edi
+    syscall
+
+    .section .rodata
+    .globl message
+message:
+    .asciz "pie fixture"
+
+    .section .data.rel.ro, "aw"
+    .globl table
+table:
+    .quad message
+    .quad _start
+
+    .data
+    .globl pointer
+pointer:
+    .quad message + 4
@@ -0,0 +1,19 @@
+# Text, rodata and data that all start on the same page, each in its own PT_LOAD (see
+# shared_page.ld), so that page has to end up with the permissions of all three
+
+    .text
+    .globl _start
+_start:
+    mov $60, %eax
+    xor %edi, %edi
+    syscall
+
+    .section .rodata, "a"
+    .align 16
+message:
+    .ascii "shared"
+
+    .data
+    .align 16
+counter:
+    .quad 1
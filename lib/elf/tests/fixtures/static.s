@@ -0,0 +1,24 @@
+# A statically linked executable with one of everything: text, rodata, data (holding a pointer
+# into rodata) and a .bss that spans more than a page
+
+    .text
+    .globl _start
+_start:
+    mov $60, %eax
+    xor %edi, %edi
+    syscall
+
+    .section .rodata
+    .globl message
+message:
+    .asciz "static fixture"
+
+    .data
+    .globl pointer
+pointer:
+    .quad message
+
+    .bss
+    .globl buffer
+buffer:
+    .zero 8192
@@ -0,0 +1,21 @@
+# A static executable with thread local storage: 8 bytes of .tdata and 16 of .tbss, aligned to 16
+
+    .text
+    .globl _start
+_start:
+    mov %fs:tls_value@tpoff, %rax
+    mov $60, %eax
+    xor %edi, %edi
+    syscall
+
+    .section .tdata, "awT", @progbits
+    .align 16
+    .globl tls_value
+tls_value:
+    .quad 0x1122334455667788
+
+    .section .tbss, "awT", @nobits
+    .align 8
+    .globl tls_zeroed
+tls_zeroed:
+    .zero 16
@@ -0,0 +1,17 @@
+# Thread local storage with only 4 bytes of .tdata, aligned to 4. That's less than the 8 the TCB
+# needs, so the block gets padding that has to go below the data rather than between it and the
+# thread pointer
+
+    .text
+    .globl _start
+_start:
+    mov %fs:small_value@tpoff, %eax
+    mov $60, %eax
+    xor %edi, %edi
+    syscall
+
+    .section .tdata, "awT", @progbits
+    .align 4
+    .globl small_value
+small_value:
+    .long 0x55667788
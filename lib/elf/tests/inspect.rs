@@ -0,0 +1,154 @@
+//! Checks what the Inspector finds in the fixtures (see `fixtures/build.sh`) against what readelf
+//! says about them
+
+use elf::inspect::abi::{ET_DYN, ET_EXEC, PF_R, PF_W, PF_X, PT_GNU_STACK, PT_LOAD, SHT_NOBITS, SHT_PROGBITS};
+use elf::inspect::Inspector;
+use x86_64::VirtAddr;
+
+const STATIC: &[u8] = include_bytes!("fixtures/static.elf");
+const PIE: &[u8] = include_bytes!("fixtures/pie.elf");
+const BUILD_ID: &[u8] = include_bytes!("fixtures/build_id.elf");
+const SECTION_BUILD_ID: &[u8] = include_bytes!("fixtures/section_build_id.elf");
+const LIBGREET: &[u8] = include_bytes!("fixtures/libgreet.so");
+const HUGE_SYMBOL: &[u8] = include_bytes!("fixtures/huge_symbol.so");
+const DYNAMIC: &[u8] = include_bytes!("fixtures/dynamic.elf");
+const TRUNCATED: &[u8] = include_bytes!("fixtures/truncated.elf");
+
+const BIAS: u64 = 0x7000_0000;
+
+/// (type, flags, address, file size, memory size) of every segment
+fn segments(inspector: &Inspector) -> Vec<(u32, u32, u64, u64, u64)> {
+    inspector.segments()
+        .map(|segment| (segment.segment_type, segment.flags, segment.address, segment.file_size, segment.memory_size))
+        .collect()
+}
+
+fn symbols(inspector: &Inspector) -> Vec<(String, u64)> {
+    inspector.symbols()
+        .map(|symbol| (symbol.name.to_string(), symbol.address))
+        .collect()
+}
+
+#[test]
+fn static_executable_headers() {
+    let inspector = Inspector::new(STATIC).unwrap();
+
+    assert_eq!(inspector.object_type(), ET_EXEC);
+    assert_eq!(inspector.entry(), VirtAddr::new(0x401000));
+
+    assert_eq!(segments(&inspector), [
+        (PT_LOAD, PF_R, 0x400000, 0x158, 0x158),
+        (PT_LOAD, PF_R | PF_X, 0x401000, 0x9, 0x9),
+        (PT_LOAD, PF_R, 0x402000, 0xf, 0xf),
+        (PT_LOAD, PF_R | PF_W, 0x40300f, 0x8, 0x2009),
+        (PT_GNU_STACK, PF_R | PF_W, 0, 0, 0)
+    ]);
+}
+
+#[test]
+fn static_executable_sections() {
+    let inspector = Inspector::new(STATIC).unwrap();
+
+    let names: Vec<&str> = inspector.sections().map(|section| section.name).collect();
+    assert_eq!(names, ["", ".text", ".rodata", ".data", ".bss", ".symtab", ".strtab", ".shstrtab"]);
+
+    let bss = inspector.sections().find(|section| section.name == ".bss").unwrap();
+    assert_eq!(bss.section_type, SHT_NOBITS);
+    assert_eq!(bss.address, 0x403017);
+    assert_eq!(bss.size, 0x2001);
+}
+
+#[test]
+fn static_executable_symbols() {
+    let inspector = Inspector::new(STATIC).unwrap();
+
+    assert_eq!(symbols(&inspector)[1..5], [
+        ("message".to_string(), 0x402000),
+        ("pointer".to_string(), 0x40300f),
+        ("_start".to_string(), 0x401000),
+        ("buffer".to_string(), 0x403017)
+    ]);
+
+    // Nothing in the fixture has a type, so there's nothing to say an address is part of
+    assert!(inspector.symbolize(VirtAddr::new(0x401000)).is_none());
+}
+
+#[test]
+fn loaded_pie_addresses_have_the_bias() {
+    let inspector = Inspector::loaded(PIE, BIAS).unwrap();
+
+    assert_eq!(inspector.object_type(), ET_DYN);
+    assert_eq!(inspector.entry(), VirtAddr::new(BIAS + 0x1000));
+
+    let loads: Vec<_> = segments(&inspector).into_iter()
+        .filter(|segment| segment.0 == PT_LOAD)
+        .map(|segment| segment.2)
+        .collect();
+    assert_eq!(loads, [BIAS, BIAS + 0x1000, BIAS + 0x2000, BIAS + 0x3ed0]);
+
+    // Sections that aren't loaded stay at 0
+    let text = inspector.sections().find(|section| section.name == ".text").unwrap();
+    assert_eq!((text.section_type, text.address, text.offset), (SHT_PROGBITS, BIAS + 0x1000, 0x1000));
+    let symtab = inspector.sections().find(|section| section.name == ".symtab").unwrap();
+    assert_eq!(symtab.address, 0);
+
+    let symbols = symbols(&inspector);
+    assert!(symbols.contains(&("table".to_string(), BIAS + 0x3ed0)));
+    assert!(symbols.contains(&("_start".to_string(), BIAS + 0x1000)));
+}
+
+#[test]
+fn build_id_is_found_in_the_notes() {
+    let expected = "75442109723b48dbc5ed287de533962cfa46965d";
+
+    let inspector = Inspector::new(BUILD_ID).unwrap();
+    assert_eq!(inspector.build_id().unwrap().to_string(), expected);
+
+    // Without a PT_NOTE it comes from the .note.gnu.build-id section instead
+    let inspector = Inspector::new(SECTION_BUILD_ID).unwrap();
+    assert!(segments(&inspector).iter().all(|segment| segment.0 != elf::inspect::abi::PT_NOTE));
+    assert_eq!(inspector.build_id().unwrap().to_string(), expected);
+
+    assert!(Inspector::new(STATIC).unwrap().build_id().is_none());
+}
+
+#[test]
+fn addresses_are_symbolized_inside_typed_symbols() {
+    let inspector = Inspector::loaded(LIBGREET, BIAS).unwrap();
+
+    // greeting is a 16 byte object
+    let (symbol, offset) = inspector.symbolize(VirtAddr::new(BIAS + 0x3004)).unwrap();
+    assert_eq!((symbol.name, symbol.address, offset), ("greeting", BIAS + 0x3000, 4));
+
+    let (symbol, offset) = inspector.symbolize(VirtAddr::new(BIAS + 0x3010)).unwrap();
+    assert_eq!((symbol.name, offset), ("greeting_pointer", 0));
+
+    // greet has no size, so only its first byte is known to be in it
+    let (symbol, offset) = inspector.symbolize(VirtAddr::new(BIAS + 0x1000)).unwrap();
+    assert_eq!((symbol.name, offset), ("greet", 0));
+    assert!(inspector.symbolize(VirtAddr::new(BIAS + 0x1001)).is_none());
+
+    assert!(inspector.symbolize(VirtAddr::new(BIAS + 0x3018)).is_none());
+}
+
+#[test]
+fn undefined_symbols_arent_symbolized() {
+    let inspector = Inspector::new(DYNAMIC).unwrap();
+
+    // greet is an undefined function with a value of 0
+    assert!(inspector.symbols().any(|symbol| symbol.name == "greet" && !symbol.defined && symbol.address == 0));
+    assert!(inspector.symbolize(VirtAddr::new(0)).is_none());
+}
+
+#[test]
+fn symbol_running_off_the_end_of_memory_doesnt_overflow() {
+    let inspector = Inspector::loaded(HUGE_SYMBOL, BIAS).unwrap();
+
+    let (symbol, offset) = inspector.symbolize(VirtAddr::new(BIAS + 0x3010)).unwrap();
+    assert_eq!((symbol.name, offset), ("greeting", 0x10));
+}
+
+#[test]
+fn broken_file_cant_be_inspected() {
+    assert!(Inspector::new(TRUNCATED).is_err());
+}
@@ -0,0 +1,274 @@
+//! Runs the loader against the fixtures in `fixtures/` (see `fixtures/build.sh`), checking the
+//! exact pages it maps, their permissions and what ends up in them
+
+mod common;
+
+use common::{MockAddressSpace, MockMapper, GARBAGE};
+
+use elf::permissions::WxPolicy;
+use elf::{ElfLoadError, LoadLocation, LoadedImage};
+use x86_64::VirtAddr;
+
+const STATIC: &[u8] = include_bytes!("fixtures/static.elf");
+const PIE: &[u8] = include_bytes!("fixtures/pie.elf");
+const TLS: &[u8] = include_bytes!("fixtures/tls.elf");
+const TLS_SMALL: &[u8] = include_bytes!("fixtures/tls_small.elf");
+const RELOCATABLE: &[u8] = include_bytes!("fixtures/relocatable.o");
+const TRUNCATED: &[u8] = include_bytes!("fixtures/truncated.elf");
+const WRONG_MACHINE: &[u8] = include_bytes!("fixtures/wrong_machine.elf");
+const FILE_TOO_BIG: &[u8] = include_bytes!("fixtures/file_too_big.elf");
+const WRITABLE_TEXT: &[u8] = include_bytes!("fixtures/writable_text.elf");
+const SHARED_PAGE: &[u8] = include_bytes!("fixtures/shared_page.elf");
+
+const READ: (bool, bool) = (false, false);
+const READ_EXECUTE: (bool, bool) = (false, true);
+const READ_WRITE: (bool, bool) = (true, false);
+const READ_WRITE_EXECUTE: (bool, bool) = (true, true);
+
+fn load(data: &[u8], load_location: LoadLocation, policy: WxPolicy) -> Result<(LoadedImage, MockAddressSpace), ElfLoadError> {
+    let mut mapper = MockMapper::default();
+    let mut space = MockAddressSpace::default();
+
+    let image = elf::load(data, &mut mapper, &mut space, load_location, policy)?;
+    Ok((image, space))
+}
+
+/// Each (page, (writable, executable)) in order
+fn expected(pages: &[(u64, (bool, bool))]) -> Vec<(u64, bool, bool)> {
+    pages.iter()
+        .map(|&(page, (writable, executable))| (page, writable, executable))
+        .collect()
+}
+
+#[test]
+fn static_executable_is_loaded_where_it_was_linked() {
+    let (image, space) = load(STATIC, LoadLocation::Any, WxPolicy::Refuse).unwrap();
+
+    assert_eq!(image.entry, VirtAddr::new(0x401000));
+    assert_eq!(image.load_bias, 0);
+    assert_eq!(image.program_headers, Some(VirtAddr::new(0x400040)));
+    assert_eq!(image.program_header_count, 5);
+    assert!(image.tls.is_none());
+    assert!(!image.executable_stack);
+
+    assert_eq!(space.mappings(), expected(&[
+        (0x400000, READ),
+        (0x401000, READ_EXECUTE),
+        (0x402000, READ),
+        (0x403000, READ_WRITE),
+        (0x404000, READ_WRITE),
+        (0x405000, READ_WRITE)
+    ]));
+
+    // mov $60, %eax
+    assert_eq!(space.read(0x401000, 5), [0xb8, 0x3c, 0x00, 0x00, 0x00]);
+    assert_eq!(space.read(0x402000, 15), b"static fixture\0");
+    assert_eq!(space.read_u64(0x40300f), 0x402000);
+}
+
+#[test]
+fn static_executable_memory_not_in_the_file_is_zeroed() {
+    let (_, space) = load(STATIC, LoadLocation::Any, WxPolicy::Refuse).unwrap();
+
+    // Before .data in its page, the whole of .bss, and the rest of the last page
+    assert!(space.read(0x403000, 0xf).iter().all(|&byte| byte == 0));
+    assert!(space.read(0x403017, 8192).iter().all(|&byte| byte == 0));
+    assert!(space.read(0x405017, 0x1000 - 0x17).iter().all(|&byte| byte == 0));
+
+    // Nothing the mapper filled the frames with should be left anywhere
+    assert!(!space.read(0x400000, 0x6000).contains(&GARBAGE));
+}
+
+#[test]
+fn static_executable_cant_be_moved() {
+    assert!(matches!(
+        load(STATIC, LoadLocation::Exactly(VirtAddr::new(0x1000_0000)), WxPolicy::Refuse),
+        Err(ElfLoadError::CannotLoadAtLocation)
+    ));
+    assert!(matches!(
+        load(STATIC, LoadLocation::GreaterThan(VirtAddr::new(0x8000_0000)), WxPolicy::Refuse),
+        Err(ElfLoadError::CannotLoadAtLocation)
+    ));
+    assert!(load(STATIC, LoadLocation::LessThan(VirtAddr::new(0x8000_0000)), WxPolicy::Refuse).is_ok());
+}
+
+#[test]
+fn pie_is_relocated_to_where_it_was_put() {
+    let base = 0x7000_0000;
+    let (image, space) = load(PIE, LoadLocation::Exactly(VirtAddr::new(base)), WxPolicy::Refuse).unwrap();
+
+    assert_eq!(image.load_bias, base);
+    assert_eq!(image.entry, VirtAddr::new(base + 0x1000));
+
+    // .data.rel.ro is made read only again after relocation since it's covered by PT_GNU_RELRO,
+    // but .data after it isn't
+    assert_eq!(space.mappings(), expected(&[
+        (base, READ),
+        (base + 0x1000, READ_EXECUTE),
+        (base + 0x2000, READ),
+        (base + 0x3000, READ),
+        (base + 0x4000, READ_WRITE)
+    ]));
+
+    // The three R_X86_64_RELATIVE relocations
+    assert_eq!(space.read_u64(base + 0x3ed0), base + 0x2000);
+    assert_eq!(space.read_u64(base + 0x3ed8), base + 0x1000);
+    assert_eq!(space.read_u64(base + 0x4000), base + 0x2004);
+
+    assert_eq!(space.read(base + 0x2000, 12), b"pie fixture\0");
+}
+
+#[test]
+fn pie_goes_to_the_default_base_when_anywhere_will_do() {
+    let (image, space) = load(PIE, LoadLocation::Any, WxPolicy::Refuse).unwrap();
+
+    assert_eq!(image.load_bias, 0x40_0000);
+    assert_eq!(space.read_u64(0x40_3ed0), 0x40_2000);
+}
+
+#[test]
+fn pie_below_a_limit_ends_before_it() {
+    let (image, space) = load(PIE, LoadLocation::LessThan(VirtAddr::new(0x10_0000)), WxPolicy::Refuse).unwrap();
+
+    assert_eq!(image.load_bias, 0x10_0000 - 0x5000);
+    assert_eq!(space.mappings().last().unwrap().0, 0x10_0000 - 0x1000);
+}
+
+#[test]
+fn pie_must_be_page_aligned() {
+    assert!(matches!(
+        load(PIE, LoadLocation::Exactly(VirtAddr::new(0x7000_0010)), WxPolicy::Refuse),
+        Err(ElfLoadError::CannotLoadAtLocation)
+    ));
+}
+
+#[test]
+fn tls_template_comes_from_pt_tls() {
+    let (image, space) = load(TLS, LoadLocation::Any, WxPolicy::Refuse).unwrap();
+
+    let tls = image.tls.unwrap();
+    assert_eq!(tls.image, VirtAddr::new(0x402ff0));
+    assert_eq!(tls.file_size, 8);
+    assert_eq!(tls.memory_size, 0x18);
+    assert_eq!(tls.alignment, 0x10);
+
+    assert_eq!(tls.thread_pointer_offset(), 0x20);
+    assert_eq!(tls.block_size(), 0x20 + elf::tls::TCB_SIZE);
+    assert_eq!(tls.block_alignment(), 0x10);
+
+    assert_eq!(space.read_u64(0x402ff0), 0x1122334455667788);
+
+    // .tdata is all of PT_GNU_RELRO, so its page ends up read only
+    assert_eq!(space.mappings(), expected(&[
+        (0x400000, READ),
+        (0x401000, READ_EXECUTE),
+        (0x402000, READ)
+    ]));
+}
+
+#[test]
+fn tls_block_puts_tdata_where_the_code_reads_it() {
+    #[repr(C, align(16))]
+    struct Block([u8; 0x20 + elf::tls::TCB_SIZE]);
+
+    let (image, space) = load(TLS, LoadLocation::Any, WxPolicy::Refuse).unwrap();
+    let tls = image.tls.unwrap();
+
+    // initialise_block copies from the image in the active address space, which here is the host's
+    let tdata = space.read(tls.image.as_u64(), tls.file_size as usize);
+    let tls = elf::tls::TlsTemplate { image: VirtAddr::from_ptr(tdata.as_ptr()), ..tls };
+
+    let mut block = Block([GARBAGE; 0x20 + elf::tls::TCB_SIZE]);
+    let thread_pointer = unsafe { tls.initialise_block(&mut block.0) }.unwrap();
+    let tp = (thread_pointer - VirtAddr::from_ptr(block.0.as_ptr())) as usize;
+
+    assert_eq!(tp, 0x20);
+
+    // _start reads tls_value with `mov %fs:-0x20, %rax`, and tls_zeroed (.tbss) follows it
+    assert_eq!(block.0[tp - 0x20..tp - 0x18], 0x1122334455667788u64.to_le_bytes());
+    assert!(block.0[tp - 0x18..tp].iter().all(|&byte| byte == 0));
+
+    // The TCB starts with a pointer to itself and is zeroed after that
+    assert_eq!(block.0[tp..tp + 8], thread_pointer.as_u64().to_le_bytes());
+    assert!(block.0[tp + 8..].iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn tls_block_with_less_alignment_than_the_tcb_pads_below_the_data() {
+    #[repr(C, align(8))]
+    struct Block([u8; 8 + elf::tls::TCB_SIZE]);
+
+    let (image, space) = load(TLS_SMALL, LoadLocation::Any, WxPolicy::Refuse).unwrap();
+    let tls = image.tls.unwrap();
+
+    assert_eq!((tls.file_size, tls.memory_size, tls.alignment), (4, 4, 4));
+    assert_eq!(tls.tls_size(), 4);
+    assert_eq!(tls.thread_pointer_offset(), 8);
+
+    let tdata = space.read(tls.image.as_u64(), tls.file_size as usize);
+    let tls = elf::tls::TlsTemplate { image: VirtAddr::from_ptr(tdata.as_ptr()), ..tls };
+
+    let mut block = Block([GARBAGE; 8 + elf::tls::TCB_SIZE]);
+    let thread_pointer = unsafe { tls.initialise_block(&mut block.0) }.unwrap();
+    let tp = (thread_pointer - VirtAddr::from_ptr(block.0.as_ptr())) as usize;
+
+    // _start reads small_value with `mov %fs:-0x4, %eax`
+    assert_eq!(block.0[tp - 4..tp], 0x55667788u32.to_le_bytes());
+    assert_eq!(block.0[..tp - 4], [0; 4]);
+}
+
+#[test]
+fn relocatable_objects_arent_supported_yet() {
+    assert!(matches!(
+        load(RELOCATABLE, LoadLocation::Any, WxPolicy::Refuse),
+        Err(ElfLoadError::NotImplemented)
+    ));
+}
+
+#[test]
+fn truncated_file_is_rejected() {
+    assert!(matches!(
+        load(TRUNCATED, LoadLocation::Any, WxPolicy::Refuse),
+        Err(ElfLoadError::ElfHeaderParseError(_))
+    ));
+}
+
+#[test]
+fn other_architectures_are_rejected() {
+    assert!(matches!(
+        load(WRONG_MACHINE, LoadLocation::Any, WxPolicy::Refuse),
+        Err(ElfLoadError::WrongInstructionSet)
+    ));
+}
+
+#[test]
+fn segment_bigger_in_the_file_than_in_memory_is_rejected() {
+    assert!(matches!(
+        load(FILE_TOO_BIG, LoadLocation::Any, WxPolicy::Refuse),
+        Err(ElfLoadError::MalformedSegment)
+    ));
+}
+
+#[test]
+fn writable_text_depends_on_the_policy() {
+    assert!(matches!(
+        load(WRITABLE_TEXT, LoadLocation::Any, WxPolicy::Refuse),
+        Err(ElfLoadError::WritableExecutable)
+    ));
+
+    let (_, space) = load(WRITABLE_TEXT, LoadLocation::Any, WxPolicy::Warn).unwrap();
+    assert_eq!(space.mappings()[1], (0x401000, READ_WRITE_EXECUTE.0, READ_WRITE_EXECUTE.1));
+}
+
+#[test]
+fn page_shared_by_three_segments_gets_all_their_permissions() {
+    // Text, rodata and data all on 0x401000, so it has to be writable and executable
+    assert!(matches!(
+        load(SHARED_PAGE, LoadLocation::Any, WxPolicy::Refuse),
+        Err(ElfLoadError::WritableExecutable)
+    ));
+
+    let (_, space) = load(SHARED_PAGE, LoadLocation::Any, WxPolicy::Warn).unwrap();
+    assert_eq!(space.mappings(), expected(&[(0x401000, READ_WRITE_EXECUTE)]));
+    assert_eq!(space.read_u64(0x401020), 1);
+}
@@ -0,0 +1,177 @@
+//! Builds initial stacks for the fixtures (see `fixtures/build.sh`) and reads them back the way a
+//! program's _start would, from rsp upward
+
+mod common;
+
+use common::{MockAddressSpace, MockMapper};
+
+use elf::permissions::WxPolicy;
+use elf::user_stack::{self, StackSetupError};
+use elf::{LoadLocation, LoadedImage};
+use x86_64::VirtAddr;
+
+const STATIC: &[u8] = include_bytes!("fixtures/static.elf");
+const PIE: &[u8] = include_bytes!("fixtures/pie.elf");
+
+const STACK_TOP: u64 = 0x7fff_0000_0000;
+const STACK_SIZE: usize = 4096;
+
+const RANDOM: [u8; 16] = *b"0123456789abcdef";
+
+fn load(data: &[u8], load_location: LoadLocation) -> LoadedImage {
+    elf::load(data, &mut MockMapper::default(), &mut MockAddressSpace::default(), load_location, WxPolicy::Refuse).unwrap()
+}
+
+/// A stack that has been built, read back through the addresses the program would see
+struct Stack {
+    memory: Vec<u8>,
+    rsp: u64
+}
+
+impl Stack {
+    fn build(image: &LoadedImage, interpreter: Option<&LoadedImage>, arguments: &[&str], environment: &[&str]) -> Self {
+        let mut memory = vec![0; STACK_SIZE];
+        let rsp = user_stack::build_initial_stack(&mut memory, VirtAddr::new(STACK_TOP), image, interpreter, arguments, environment, &RANDOM)
+            .unwrap()
+            .as_u64();
+
+        Stack { memory, rsp }
+    }
+
+    fn index(&self, address: u64) -> usize {
+        let bottom = STACK_TOP - STACK_SIZE as u64;
+        assert!((bottom..STACK_TOP).contains(&address), "{address:#x} is outside the stack");
+        (address - bottom) as usize
+    }
+
+    fn word(&self, address: u64) -> u64 {
+        let index = self.index(address);
+        u64::from_le_bytes(self.memory[index..index + 8].try_into().unwrap())
+    }
+
+    fn string(&self, address: u64) -> &str {
+        let start = self.index(address);
+        let length = self.memory[start..].iter().position(|&byte| byte == 0).unwrap();
+        std::str::from_utf8(&self.memory[start..start + length]).unwrap()
+    }
+
+    /// The null terminated list of string pointers starting at `address`, and the address after
+    /// the null
+    fn strings(&self, mut address: u64) -> (Vec<&str>, u64) {
+        let mut strings = Vec::new();
+
+        while self.word(address) != 0 {
+            strings.push(self.string(self.word(address)));
+            address += 8;
+        }
+
+        (strings, address + 8)
+    }
+
+    /// (argv, envp, auxv)
+    fn read(&self) -> (Vec<&str>, Vec<&str>, Vec<(u64, u64)>) {
+        let argc = self.word(self.rsp);
+        let (arguments, envp) = self.strings(self.rsp + 8);
+        assert_eq!(arguments.len() as u64, argc);
+
+        let (environment, mut address) = self.strings(envp);
+
+        let mut auxv = Vec::new();
+        loop {
+            let entry = (self.word(address), self.word(address + 8));
+            auxv.push(entry);
+            address += 16;
+
+            if entry.0 == user_stack::AT_NULL {
+                break
+            }
+        }
+
+        (arguments, environment, auxv)
+    }
+}
+
+#[test]
+fn arguments_environment_and_auxv_are_laid_out_from_rsp() {
+    let image = load(STATIC, LoadLocation::Any);
+    let stack = Stack::build(&image, None, &["/bin/init", "-v"], &["HOME=/", "TERM=vt100", "X=1"]);
+
+    let (arguments, environment, auxv) = stack.read();
+
+    assert_eq!(arguments, ["/bin/init", "-v"]);
+    assert_eq!(environment, ["HOME=/", "TERM=vt100", "X=1"]);
+
+    let random = STACK_TOP - 16;
+    assert_eq!(auxv, [
+        (user_stack::AT_PHDR, 0x400040),
+        (user_stack::AT_PHENT, 56),
+        (user_stack::AT_PHNUM, 5),
+        (user_stack::AT_PAGESZ, 4096),
+        (user_stack::AT_BASE, 0),
+        (user_stack::AT_ENTRY, 0x401000),
+        (user_stack::AT_RANDOM, random),
+        (user_stack::AT_NULL, 0)
+    ]);
+
+    assert_eq!(stack.memory[stack.index(random)..], RANDOM);
+}
+
+#[test]
+fn rsp_is_16_byte_aligned_whatever_goes_on_the_stack() {
+    let image = load(STATIC, LoadLocation::Any);
+    let strings = ["a", "bc", "def", "ghij", "klmnopqrstu"];
+
+    // Odd and even numbers of pointers, and strings that end anywhere in a 16 byte block
+    for argument_count in 0..strings.len() {
+        for environment_count in 0..strings.len() {
+            let stack = Stack::build(&image, None, &strings[..argument_count], &strings[..environment_count]);
+
+            assert_eq!(stack.rsp % 16, 0);
+
+            let (arguments, environment, _) = stack.read();
+            assert_eq!(arguments, strings[..argument_count]);
+            assert_eq!(environment, strings[..environment_count]);
+        }
+    }
+}
+
+#[test]
+fn at_base_is_where_the_interpreter_went() {
+    let image = load(STATIC, LoadLocation::Any);
+    let interpreter = load(PIE, LoadLocation::Exactly(VirtAddr::new(0x7000_0000)));
+
+    let (_, _, auxv) = Stack::build(&image, Some(&interpreter), &["prog"], &[]).read();
+
+    assert!(auxv.contains(&(user_stack::AT_BASE, 0x7000_0000)));
+    assert!(auxv.contains(&(user_stack::AT_ENTRY, 0x401000)));
+}
+
+#[test]
+fn stack_that_is_too_small_is_an_error() {
+    let image = load(STATIC, LoadLocation::Any);
+
+    // Enough for the strings but not the pointers to them
+    let mut memory = [0; 64];
+    assert!(matches!(
+        user_stack::build_initial_stack(&mut memory, VirtAddr::new(STACK_TOP), &image, None, &["program"], &["A=1"], &RANDOM),
+        Err(StackSetupError::StackTooSmall)
+    ));
+
+    // Not even enough for AT_RANDOM
+    let mut memory = [0; 8];
+    assert!(matches!(
+        user_stack::build_initial_stack(&mut memory, VirtAddr::new(STACK_TOP), &image, None, &[], &[], &RANDOM),
+        Err(StackSetupError::StackTooSmall)
+    ));
+}
+
+#[test]
+fn stack_top_has_to_be_aligned() {
+    let image = load(STATIC, LoadLocation::Any);
+    let mut memory = vec![0; STACK_SIZE];
+
+    assert!(matches!(
+        user_stack::build_initial_stack(&mut memory, VirtAddr::new(STACK_TOP - 8), &image, None, &[], &[], &RANDOM),
+        Err(StackSetupError::StackMisaligned)
+    ));
+}
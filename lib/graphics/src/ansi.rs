@@ -0,0 +1,259 @@
+use crate::Color;
+
+/// The most parameters a CSI sequence can have. Anything past this gets dropped, which nothing
+/// sensible ever gets near (truecolour SGR is the longest common one at 5)
+const MAX_PARAMETERS: usize = 16;
+
+/// The colours for SGR 30-37, 40-47, 90-97 and 100-107, and the first 16 of the 256 colour
+/// palette. These are the VGA ones, which is about as standard as it gets
+const PALETTE: [Color; 16] = [
+    Color::from_hex(0x000000),
+    Color::from_hex(0xaa0000),
+    Color::from_hex(0x00aa00),
+    Color::from_hex(0xaa5500),
+    Color::from_hex(0x0000aa),
+    Color::from_hex(0xaa00aa),
+    Color::from_hex(0x00aaaa),
+    Color::from_hex(0xaaaaaa),
+    Color::from_hex(0x555555),
+    Color::from_hex(0xff5555),
+    Color::from_hex(0x55ff55),
+    Color::from_hex(0xffff55),
+    Color::from_hex(0x5555ff),
+    Color::from_hex(0xff55ff),
+    Color::from_hex(0x55ffff),
+    Color::from_hex(0xffffff)
+];
+
+/// How a cell gets drawn. The console keeps one of these as the current style, which SGR
+/// sequences change and every character written picks up
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Style {
+    pub foreground: Color,
+    pub background: Color,
+    /// Drawn by using the bright version of the foreground, like most terminals do, since there's
+    /// no bold font to switch to. Only does anything for the first 8 palette colours
+    pub bold: bool,
+    /// Swaps the foreground and background
+    pub inverse: bool
+}
+
+impl Style {
+    /// White on black, which is what the console has always been
+    pub const DEFAULT: Style = Style {
+        foreground: Color::WHITE,
+        background: Color::BLACK,
+        bold: false,
+        inverse: false
+    };
+
+    /// The (foreground, background) colours to actually draw with once bold and inverse are taken
+    /// into account
+    pub fn colors(&self) -> (Color, Color) {
+        let mut foreground = self.foreground;
+
+        if self.bold {
+            if let Some(index) = PALETTE[..8].iter().position(|color| *color == foreground) {
+                foreground = PALETTE[index + 8];
+            }
+        }
+
+        if self.inverse {
+            (self.background, foreground)
+        } else {
+            (foreground, self.background)
+        }
+    }
+
+    /// Applies the parameters of an SGR (`CSI ... m`) sequence. Anything it doesn't understand is
+    /// skipped
+    pub(crate) fn apply_sgr(&mut self, parameters: &[u16]) {
+        // No parameters at all means reset, same as a 0
+        if parameters.is_empty() {
+            *self = Style::DEFAULT;
+            return
+        }
+
+        let mut parameters = parameters.iter().copied();
+
+        while let Some(parameter) = parameters.next() {
+            match parameter {
+                0 => *self = Style::DEFAULT,
+                1 => self.bold = true,
+                22 => self.bold = false,
+                7 => self.inverse = true,
+                27 => self.inverse = false,
+                30..=37 => self.foreground = PALETTE[parameter as usize - 30],
+                38 => if let Some(color) = extended_color(&mut parameters) {
+                    self.foreground = color
+                },
+                39 => self.foreground = Style::DEFAULT.foreground,
+                40..=47 => self.background = PALETTE[parameter as usize - 40],
+                48 => if let Some(color) = extended_color(&mut parameters) {
+                    self.background = color
+                },
+                49 => self.background = Style::DEFAULT.background,
+                90..=97 => self.foreground = PALETTE[parameter as usize - 90 + 8],
+                100..=107 => self.background = PALETTE[parameter as usize - 100 + 8],
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style::DEFAULT
+    }
+}
+
+/// Reads the rest of a 38 or 48 SGR parameter, which is either `5;n` for one of the 256 palette
+/// colours or `2;r;g;b` for truecolour
+fn extended_color(parameters: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match parameters.next()? {
+        5 => Some(palette_256(parameters.next()?)),
+        2 => {
+            let red = parameters.next()?;
+            let green = parameters.next()?;
+            let blue = parameters.next()?;
+            Some(Color::rgb(red as u8, green as u8, blue as u8))
+        },
+        _ => None
+    }
+}
+
+/// The xterm 256 colour palette: the 16 normal colours, then a 6x6x6 colour cube, then 24 greys
+fn palette_256(index: u16) -> Color {
+    match index {
+        0..=15 => PALETTE[index as usize],
+        16..=231 => {
+            let cube = index - 16;
+            let level = |value: u16| if value == 0 { 0 } else { (55 + value * 40) as u8 };
+            Color::rgb(level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+        },
+        232..=255 => {
+            let grey = (8 + (index - 232) * 10) as u8;
+            Color::rgb(grey, grey, grey)
+        },
+        _ => Style::DEFAULT.foreground
+    }
+}
+
+/// Something the console should do, worked out from the characters written to it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Action {
+    /// An ordinary character to put at the cursor
+    Print(char),
+    /// A C0 control character like `\n` or backspace
+    Control(char),
+    /// A finished `ESC [` sequence. `parameters` are the numbers between the `[` and the final
+    /// byte, with missing ones as 0
+    Csi {
+        parameters: [u16; MAX_PARAMETERS],
+        count: usize,
+        /// Set for sequences that start with `?`, which are all private modes nothing here
+        /// supports
+        private: bool,
+        final_byte: char
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Ground,
+    /// Just seen an ESC
+    Escape,
+    /// Inside `ESC [`
+    Csi
+}
+
+/// A VT100 style escape sequence parser. It's fed one character at a time since sequences can be
+/// split across separate writes (and often are, by anything using core::fmt)
+///
+/// This only covers the bits of ECMA-48 a console that logs things needs. Anything else is parsed
+/// so it doesn't end up on the screen, then ignored
+#[derive(Clone, Debug)]
+pub(crate) struct Parser {
+    state: State,
+    parameters: [u16; MAX_PARAMETERS],
+    count: usize,
+    private: bool
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            parameters: [0; MAX_PARAMETERS],
+            count: 0,
+            private: false
+        }
+    }
+
+    /// Feeds in the next character, giving back what to do if it finished something
+    pub fn advance(&mut self, character: char) -> Option<Action> {
+        match self.state {
+            State::Ground => match character {
+                '\x1b' => {
+                    self.state = State::Escape;
+                    None
+                },
+                '\0'..='\x1f' | '\x7f' => Some(Action::Control(character)),
+                _ => Some(Action::Print(character))
+            },
+            State::Escape => {
+                if character == '[' {
+                    self.parameters = [0; MAX_PARAMETERS];
+                    self.count = 0;
+                    self.private = false;
+                    self.state = State::Csi;
+                } else {
+                    // Some other kind of escape sequence, all of which are two characters long
+                    // and none of which mean anything here
+                    self.state = State::Ground;
+                }
+                None
+            },
+            State::Csi => match character {
+                '0'..='9' => {
+                    // The first digit is when a parameter actually starts existing
+                    if self.count == 0 {
+                        self.count = 1;
+                    }
+
+                    if let Some(parameter) = self.parameters.get_mut(self.count - 1) {
+                        *parameter = parameter
+                            .saturating_mul(10)
+                            .saturating_add(character as u16 - '0' as u16);
+                    }
+                    None
+                },
+                ';' => {
+                    // An empty first parameter still counts, so `;5` is (0, 5)
+                    self.count = self.count.max(1) + 1;
+                    None
+                },
+                '?' => {
+                    self.private = true;
+                    None
+                },
+                '\x40'..='\x7e' => {
+                    self.state = State::Ground;
+                    Some(Action::Csi {
+                        parameters: self.parameters,
+                        count: self.count.min(MAX_PARAMETERS),
+                        private: self.private,
+                        final_byte: character
+                    })
+                },
+                // Intermediate bytes, which none of the supported sequences use
+                '\x20'..='\x2f' => None,
+                // Anything else isn't allowed in a sequence, so give up on it
+                _ => {
+                    self.state = State::Ground;
+                    None
+                }
+            }
+        }
+    }
+}
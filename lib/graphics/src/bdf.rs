@@ -0,0 +1,235 @@
+//! A parser for BDF 2.1 (Glyph Bitmap Distribution Format) fonts, which is what spleen and most
+//! other bitmap fonts come as. Only monospaced fonts are supported since that's all a MonoFont can
+//! hold
+
+use alloc::vec::Vec;
+
+use crate::{FontError, Glyph, MonoFont, ParseErrorKind};
+
+/// A font loaded from a BDF file, with the bits of its header that aren't part of a MonoFont
+pub struct BdfFont {
+    pub font: MonoFont,
+    /// Pixels above the baseline, including the baseline's row
+    pub ascent: usize,
+    /// Pixels below the baseline
+    pub descent: usize,
+    /// What the font says to draw characters it doesn't have with. This is already set as the
+    /// font's replacement glyph
+    pub default_char: Option<char>
+}
+
+/// A bounding box as BDF gives them: the size, and where the bottom left corner is relative to the
+/// origin (which is on the baseline)
+#[derive(Clone, Copy, Debug)]
+struct BoundingBox {
+    width: usize,
+    height: usize,
+    x_offset: i64,
+    y_offset: i64
+}
+
+impl BoundingBox {
+    fn parse<'a>(mut atoms: impl Iterator<Item = &'a str>) -> Result<Self, ParseErrorKind> {
+        let mut number = || parse_number(atoms.next());
+
+        let width = number()?;
+        let height = number()?;
+        let x_offset = number()?;
+        let y_offset = number()?;
+
+        if width < 0 || height < 0 {
+            return Err(ParseErrorKind::NegativeSize)
+        }
+
+        Ok(BoundingBox {
+            width: width as usize,
+            height: height as usize,
+            x_offset,
+            y_offset
+        })
+    }
+}
+
+/// The bits of a glyph that have been read so far
+struct PartialGlyph {
+    encoding: Option<char>,
+    bounding_box: Option<BoundingBox>,
+    /// With the line it was on, for errors
+    device_width: Option<(i64, usize)>
+}
+
+/// Parses a BDF file into a font. Each glyph is put at the character its ENCODING says (glyphs
+/// with no unicode encoding are skipped), and placed inside the font's cell using its own BBX so
+/// glyphs smaller than the cell end up in the right place
+///
+/// The cell is the width of FONTBOUNDINGBOX and FONT_ASCENT + FONT_DESCENT tall (or the height of
+/// FONTBOUNDINGBOX if those properties aren't there)
+pub fn parse(source: &str) -> Result<BdfFont, FontError> {
+    // Numbered from 1 for errors
+    let mut lines = source.lines().zip(1..);
+    let mut line_number = 0;
+
+    let mut font_bounding_box = None;
+    let mut ascent = None;
+    let mut descent = None;
+    let mut default_char = None;
+
+    // The header, up to the first glyph
+    for (line, number) in &mut lines {
+        line_number = number;
+        let error = |kind| FontError::Parse { line: number, kind };
+        let mut atoms = line.split_ascii_whitespace();
+
+        match atoms.next() {
+            Some("FONTBOUNDINGBOX") => font_bounding_box = Some(BoundingBox::parse(atoms).map_err(error)?),
+            Some("FONT_ASCENT") => ascent = Some(parse_number(atoms.next()).map_err(error)?),
+            Some("FONT_DESCENT") => descent = Some(parse_number(atoms.next()).map_err(error)?),
+            Some("DEFAULT_CHAR") => {
+                default_char = char::from_u32(parse_number(atoms.next()).map_err(error)? as u32)
+            },
+            Some("CHARS") => break,
+            _ => {}
+        }
+    }
+
+    let error = |kind| FontError::Parse { line: line_number, kind };
+
+    let font_bounding_box = font_bounding_box.ok_or(error(ParseErrorKind::MissingBoundingBox))?;
+
+    // Without the properties the bounding box says where the baseline is
+    let ascent = ascent.unwrap_or(font_bounding_box.height as i64 + font_bounding_box.y_offset);
+    let descent = descent.unwrap_or(-font_bounding_box.y_offset);
+
+    if ascent < 0 || descent < 0 || ascent + descent == 0 {
+        return Err(error(ParseErrorKind::BadMetrics))
+    }
+
+    let width = font_bounding_box.width;
+    let height = (ascent + descent) as usize;
+
+    let mut font = MonoFont::new(width, height);
+
+    let mut glyph = PartialGlyph { encoding: None, bounding_box: None, device_width: None };
+
+    while let Some((line, number)) = lines.next() {
+        let error = |kind| FontError::Parse { line: number, kind };
+        let mut atoms = line.split_ascii_whitespace();
+
+        match atoms.next() {
+            Some("STARTCHAR") => {
+                glyph = PartialGlyph { encoding: None, bounding_box: None, device_width: None };
+            },
+            Some("ENCODING") => {
+                // -1 means the glyph isn't in the font's encoding (the number after it, if there
+                // is one, is some other encoding) so it can't be placed
+                let encoding = parse_number(atoms.next()).map_err(error)?;
+                glyph.encoding = u32::try_from(encoding).ok().and_then(char::from_u32);
+            },
+            Some("DWIDTH") => glyph.device_width = Some((parse_number(atoms.next()).map_err(error)?, number)),
+            Some("BBX") => glyph.bounding_box = Some(BoundingBox::parse(atoms).map_err(error)?),
+            Some("BITMAP") => {
+                // BBX is optional, and defaults to the font's
+                let bounding_box = glyph.bounding_box.unwrap_or(font_bounding_box);
+
+                if let Some((device_width, line)) = glyph.device_width {
+                    if device_width != width as i64 {
+                        return Err(FontError::NotMonospaced { line })
+                    }
+                }
+
+                let rows = read_bitmap(&mut lines, number, bounding_box)?;
+
+                let Some(character) = glyph.encoding else {
+                    continue
+                };
+
+                let placed = place(&rows, bounding_box, font_bounding_box.x_offset, ascent, width, height);
+                font.insert(character, placed)?;
+            },
+            Some("ENDFONT") => break,
+            _ => {}
+        }
+    }
+
+    if font.is_empty() {
+        return Err(FontError::Empty)
+    }
+
+    if let Some(replacement) = default_char.and_then(|character| font.get(character)).cloned() {
+        font.set_replacement(replacement)?;
+    }
+
+    Ok(BdfFont {
+        font,
+        ascent: ascent as usize,
+        descent: descent as usize,
+        default_char
+    })
+}
+
+fn parse_number(atom: Option<&str>) -> Result<i64, ParseErrorKind> {
+    atom.and_then(|atom| atom.parse().ok()).ok_or(ParseErrorKind::ExpectedNumber)
+}
+
+/// Reads the hex rows after BITMAP (and the ENDCHAR after them) as packed bytes, the same way a
+/// Glyph stores them. Any width works, since each pair of hex digits is just the next byte
+///
+/// `bitmap_line` is the line BITMAP was on, for when the file ends straight after it
+fn read_bitmap<'a>(lines: &mut impl Iterator<Item = (&'a str, usize)>, bitmap_line: usize, bounding_box: BoundingBox) -> Result<Vec<u8>, FontError> {
+    let bytes_per_row = bounding_box.width.div_ceil(8);
+    let mut bytes = Vec::with_capacity(bytes_per_row * bounding_box.height);
+    let mut last_line = bitmap_line;
+
+    for _ in 0..bounding_box.height {
+        let (line, number) = lines.next()
+            .ok_or(FontError::Parse { line: last_line, kind: ParseErrorKind::UnexpectedEnd })?;
+        let line = line.trim();
+        let error = |kind| FontError::Parse { line: number, kind };
+        last_line = number;
+
+        // Some fonts pad rows out further than they need to, which is allowed
+        if line.len() < bytes_per_row * 2 {
+            return Err(error(ParseErrorKind::RowTooShort))
+        }
+
+        for pair in line.as_bytes().chunks(2).take(bytes_per_row) {
+            let byte = core::str::from_utf8(pair).ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or(error(ParseErrorKind::InvalidHex))?;
+            bytes.push(byte);
+        }
+    }
+
+    match lines.next() {
+        Some((line, _)) if line.trim() == "ENDCHAR" => Ok(bytes),
+        Some((_, number)) => Err(FontError::Parse { line: number, kind: ParseErrorKind::MissingEndChar }),
+        None => Err(FontError::Parse { line: last_line, kind: ParseErrorKind::UnexpectedEnd })
+    }
+}
+
+/// Puts a glyph's bitmap where it belongs in the cell. The bitmap's bottom left corner is at
+/// (x_offset, y_offset) from the origin, and the origin is `font_x_offset` in from the left of the
+/// cell and `ascent` down from the top. Anything that ends up outside the cell is cut off
+fn place(rows: &[u8], bounding_box: BoundingBox, font_x_offset: i64, ascent: i64, width: usize, height: usize) -> Glyph {
+    let mut glyph = Glyph::blank(width, height);
+
+    let left = bounding_box.x_offset - font_x_offset;
+    let top = ascent - bounding_box.y_offset - bounding_box.height as i64;
+    let bytes_per_row = bounding_box.width.div_ceil(8);
+
+    for y in 0..bounding_box.height {
+        for x in 0..bounding_box.width {
+            if rows[y * bytes_per_row + x / 8] & (0x80 >> (x % 8)) == 0 {
+                continue
+            }
+
+            let (cell_x, cell_y) = (left + x as i64, top + y as i64);
+
+            if (0..width as i64).contains(&cell_x) && (0..height as i64).contains(&cell_y) {
+                glyph.set(cell_x as usize, cell_y as usize, true);
+            }
+        }
+    }
+
+    glyph
+}
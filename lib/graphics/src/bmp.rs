@@ -0,0 +1,168 @@
+//! A decoder for uncompressed Windows bitmaps (BMP), which is what most image editors will save
+//! a logo as without any fuss. 1, 4 and 8 bit paletted images are supported, as are 16, 24 and 32
+//! bit ones, with or without BITFIELDS masks. RLE and embedded JPEG/PNG aren't
+//!
+//! Only 32 bit images with an alpha mask (V4 headers or ALPHABITFIELDS) have transparency, since
+//! the spare byte in a plain 32 bit image is usually just left as zero
+
+use alloc::vec::Vec;
+
+use crate::image::{check_size, expand_bits, read_u16, read_u32};
+use crate::{AlphaColor, Channel, Color, Image, ImageError};
+
+pub(crate) const MAGIC: &[u8] = b"BM";
+
+/// The file header that comes before the info header
+const FILE_HEADER_SIZE: usize = 14;
+/// The original OS/2 header, with 16 bit sizes and 3 byte palette entries
+const CORE_HEADER_SIZE: usize = 12;
+/// BITMAPINFOHEADER, the smallest of the Windows ones
+const INFO_HEADER_SIZE: usize = 40;
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// How each pixel's bits turn into a colour
+enum Layout {
+    Paletted(Vec<Color>),
+    Masks { red: u32, green: u32, blue: u32, alpha: u32 }
+}
+
+/// Decodes a BMP file
+pub fn parse(bytes: &[u8]) -> Result<Image, ImageError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(ImageError::WrongFormat)
+    }
+
+    let data_offset = read_u32(bytes, 10)? as usize;
+    let header_size = read_u32(bytes, FILE_HEADER_SIZE)? as usize;
+
+    let (width, height, bits_per_pixel, compression, palette_size) = if header_size == CORE_HEADER_SIZE {
+        (
+            read_u16(bytes, 18)? as i64,
+            read_u16(bytes, 20)? as i16 as i64,
+            read_u16(bytes, 24)?,
+            BI_RGB,
+            0
+        )
+    } else if header_size >= INFO_HEADER_SIZE {
+        (
+            read_u32(bytes, 18)? as i32 as i64,
+            read_u32(bytes, 22)? as i32 as i64,
+            read_u16(bytes, 28)?,
+            read_u32(bytes, 30)?,
+            read_u32(bytes, 46)? as usize
+        )
+    } else {
+        return Err(ImageError::Unsupported)
+    };
+
+    // A negative height means the rows go from the top down instead of the usual bottom up
+    let top_down = height < 0;
+    let height = height.unsigned_abs() as usize;
+
+    if width < 0 {
+        return Err(ImageError::Corrupt)
+    }
+
+    let width = width as usize;
+    let size = check_size(width, height)?;
+
+    let layout = match (bits_per_pixel, compression) {
+        (1 | 4 | 8, BI_RGB) => Layout::Paletted(read_palette(bytes, header_size, bits_per_pixel, palette_size)?),
+        // 16 bit without masks is 5 bits each with the top bit unused
+        (16, BI_RGB) => Layout::Masks { red: 0x7c00, green: 0x03e0, blue: 0x001f, alpha: 0 },
+        (24 | 32, BI_RGB) => Layout::Masks { red: 0xff_0000, green: 0xff00, blue: 0xff, alpha: 0 },
+        (16 | 32, BI_BITFIELDS | BI_ALPHABITFIELDS) => {
+            // The masks come straight after a BITMAPINFOHEADER, and are part of the later headers
+            let masks = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+            let has_alpha = compression == BI_ALPHABITFIELDS || header_size >= INFO_HEADER_SIZE + 16;
+
+            Layout::Masks {
+                red: read_u32(bytes, masks)?,
+                green: read_u32(bytes, masks + 4)?,
+                blue: read_u32(bytes, masks + 8)?,
+                alpha: if has_alpha { read_u32(bytes, masks + 12)? } else { 0 }
+            }
+        },
+        (1 | 4 | 8 | 16 | 24 | 32, _) => return Err(ImageError::Unsupported),
+        _ => return Err(ImageError::Corrupt)
+    };
+
+    let bits_per_pixel = bits_per_pixel as usize;
+    // Rows are padded out to a multiple of 4 bytes
+    let stride = (width * bits_per_pixel).div_ceil(32) * 4;
+    let row_bytes = (width * bits_per_pixel).div_ceil(8);
+
+    let data = bytes.get(data_offset..).ok_or(ImageError::Truncated)?;
+
+    let mut pixels = Vec::with_capacity(size);
+
+    for y in 0..height {
+        let row = if top_down { y } else { height - 1 - y };
+
+        // Some encoders leave the padding off the last row, so only the pixels have to be there
+        let row = data.get(row * stride..row * stride + row_bytes).ok_or(ImageError::Truncated)?;
+
+        for x in 0..width {
+            pixels.push(decode_pixel(row, x, bits_per_pixel, &layout)?);
+        }
+    }
+
+    Image::new(width, height, pixels.into_boxed_slice())
+}
+
+/// Reads the palette from after the info header. `palette_size` is how many entries the header
+/// says it has, where 0 means as many as the bit depth allows
+fn read_palette(bytes: &[u8], header_size: usize, bits_per_pixel: u16, palette_size: usize) -> Result<Vec<Color>, ImageError> {
+    let max = 1 << bits_per_pixel;
+
+    let count = match palette_size {
+        0 => max,
+        count if count <= max => count,
+        _ => return Err(ImageError::Corrupt)
+    };
+
+    // OS/2 palettes are blue, green, red and Windows ones have a spare byte after that
+    let entry_size = if header_size == CORE_HEADER_SIZE { 3 } else { 4 };
+    let start = FILE_HEADER_SIZE + header_size;
+
+    let palette = bytes.get(start..start + count * entry_size).ok_or(ImageError::Truncated)?;
+
+    Ok(palette.chunks_exact(entry_size)
+        .map(|entry| Color::rgb(entry[2], entry[1], entry[0]))
+        .collect())
+}
+
+fn decode_pixel(row: &[u8], x: usize, bits_per_pixel: usize, layout: &Layout) -> Result<AlphaColor, ImageError> {
+    match layout {
+        Layout::Paletted(palette) => {
+            // Packed most significant bits first, the same as a Glyph
+            let bit = x * bits_per_pixel;
+            let index = (row[bit / 8] >> (8 - bits_per_pixel - bit % 8)) & ((1 << bits_per_pixel) - 1) as u8;
+
+            palette.get(index as usize)
+                .map(|color| AlphaColor::from(*color))
+                .ok_or(ImageError::Corrupt)
+        },
+        Layout::Masks { red, green, blue, alpha } => {
+            let start = x * bits_per_pixel / 8;
+            let mut value = [0; 4];
+            value[..bits_per_pixel / 8].copy_from_slice(&row[start..start + bits_per_pixel / 8]);
+            let value = u32::from_le_bytes(value);
+
+            let channel = |mask: u32| {
+                let Channel { shift, size } = Channel::from_mask(mask);
+                expand_bits((value & mask) >> shift, size as u32)
+            };
+
+            Ok(AlphaColor::rgba(
+                channel(*red),
+                channel(*green),
+                channel(*blue),
+                if *alpha == 0 { 0xff } else { channel(*alpha) }
+            ))
+        }
+    }
+}
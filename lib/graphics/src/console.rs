@@ -0,0 +1,166 @@
+use core::fmt::{self, Write};
+
+use spin::Mutex;
+
+use crate::{Color, CPUFrameBuffer, Font, GraphicsError, MonoFont, RawFramebuffer, Style, TextBuffer};
+
+/// Everything needed to get text onto a framebuffer in one place: the text, the font, the buffer
+/// it gets rendered into and the framebuffer that gets flushed to. Writing to it (with `write!`
+/// or anything else that takes a `fmt::Write`) draws straight away
+///
+/// The font can be anything that implements Font, which is usually a MonoFont that was loaded at
+/// runtime, or a reference to a StaticFont
+pub struct Console<F: Font = MonoFont> {
+    text: TextBuffer,
+    frame_buffer: CPUFrameBuffer,
+    font: F,
+    target: RawFramebuffer,
+    padding: (usize, usize)
+}
+
+impl<F: Font> Console<F> {
+    /// Makes a console covering all of `target` apart from `padding` pixels around the edge
+    ///
+    /// # Arguments
+    /// * `target` - The framebuffer to draw to
+    /// * `font` - The font to draw with
+    /// * `padding` - The gap (x, y) between the edge of the screen and the text
+    pub fn new(target: RawFramebuffer, font: F, padding: (usize, usize)) -> Result<Self, GraphicsError> {
+        use crate::FramebufferTarget;
+
+        // write_pixels wants at least a pixel spare past the text, hence the + 1
+        let width = target.width().saturating_sub(padding.0 * 2 + 1) / font.width();
+        let height = target.height().saturating_sub(padding.1 * 2 + 1) / font.height();
+
+        if width == 0 || height == 0 {
+            return Err(GraphicsError::BadDimensions)
+        }
+
+        Ok(Console {
+            text: TextBuffer::new(height, width)?,
+            frame_buffer: CPUFrameBuffer::for_target(&target),
+            font,
+            target,
+            padding
+        })
+    }
+
+    /// The text being shown. Anything written through here only shows up after `refresh`
+    pub fn text(&mut self) -> &mut TextBuffer {
+        &mut self.text
+    }
+
+    /// The buffer the text is rendered into, for drawing anything else alongside it
+    pub fn frame_buffer(&mut self) -> &mut CPUFrameBuffer {
+        &mut self.frame_buffer
+    }
+
+    /// Draws whatever has changed in the text and flushes it to the framebuffer
+    pub fn refresh(&mut self) -> Result<(), GraphicsError> {
+        self.text.write_pixels(&mut self.frame_buffer, &self.font, self.padding)?;
+        self.frame_buffer.flush(&mut self.target)
+    }
+
+    /// Blinks the cursor, if it's blinking. See `TextBuffer::tick`
+    pub fn tick(&mut self) -> Result<(), GraphicsError> {
+        if self.text.tick() {
+            self.refresh()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<F: Font> Write for Console<F> {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        Write::write_str(&mut self.text, string)?;
+        self.refresh().or(Err(fmt::Error))
+    }
+}
+
+/// A `log::Log` that writes to a Console, with the level coloured in. It's made empty so it can
+/// be a static and registered with `log::set_logger` before there's anything to draw to, and
+/// records logged before a console is given to it are dropped
+///
+/// The console is behind a lock, so logging from an interrupt handler while something else is
+/// logging will deadlock (the same as the serial port in the kernel)
+pub struct ConsoleLogger<F: Font = MonoFont> {
+    console: Mutex<Option<Console<F>>>
+}
+
+impl<F: Font> ConsoleLogger<F> {
+    pub const fn new() -> Self {
+        ConsoleLogger {
+            console: Mutex::new(None)
+        }
+    }
+
+    /// Starts logging to `console`, giving back the one that was being used before (if any)
+    pub fn set_console(&self, console: Console<F>) -> Option<Console<F>> {
+        self.console.lock().replace(console)
+    }
+
+    /// Stops logging to the screen, giving the console back
+    pub fn take_console(&self) -> Option<Console<F>> {
+        self.console.lock().take()
+    }
+
+    /// Runs `function` with the console, if there is one, for writing to it directly
+    pub fn with_console<R>(&self, function: impl FnOnce(&mut Console<F>) -> R) -> Option<R> {
+        self.console.lock().as_mut().map(function)
+    }
+
+    /// Unlocks the console without a guard, for panic handlers
+    ///
+    /// # Safety
+    /// Whatever was holding the lock can't be using the console any more (because it panicked)
+    pub unsafe fn force_unlock(&self) {
+        unsafe { self.console.force_unlock() }
+    }
+}
+
+impl<F: Font> Default for ConsoleLogger<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The colour each level is shown in, so errors and warnings stand out when scrolling past
+fn level_color(level: log::Level) -> Color {
+    match level {
+        log::Level::Error => Color::RED,
+        log::Level::Warn => Color::YELLOW,
+        log::Level::Info => Color::WHITE,
+        log::Level::Debug => Color::CYAN,
+        log::Level::Trace => Color::GREY
+    }
+}
+
+impl<F: Font + Send> log::Log for ConsoleLogger<F> {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        // Filtering is left to log::set_max_level
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let mut console = self.console.lock();
+
+        let Some(console) = console.as_mut() else {
+            return
+        };
+
+        let text = console.text();
+        let style = text.style();
+
+        // There's nowhere to report the errors to (this is the logger), so they're just dropped
+        text.set_style(Style { foreground: level_color(record.level()), ..style });
+        let _ = write!(text, "[{:<5}]", record.level());
+        text.set_style(style);
+        let _ = writeln!(text, " {}: {}", record.target(), record.args());
+
+        // Rendered once for the whole record rather than for every piece it was written in
+        let _ = console.refresh();
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,109 @@
+use core::fmt;
+
+use crate::{Color, Font, FramebufferTarget};
+
+/// Writes text straight onto a framebuffer, one pixel at a time, without a back buffer and
+/// without allocating anything. That makes it slow and it can't scroll (it goes back to the top
+/// instead), but it works before there's a heap, and when the heap (or whatever was holding the
+/// Console) is what broke, which is what it's for
+pub struct DirectConsole<'a, T: FramebufferTarget, F: Font> {
+    target: &'a mut T,
+    font: &'a F,
+    /// Size in characters
+    columns: usize,
+    rows: usize,
+    /// Where the next character goes, in characters
+    cursor: (usize, usize),
+    pub foreground: Color,
+    pub background: Color
+}
+
+impl<'a, T: FramebufferTarget, F: Font> DirectConsole<'a, T, F> {
+    /// Starts writing at the top left, white on black. The first line is cleared, and every other
+    /// line is cleared when the cursor gets to it, so whatever was on the screen stays until it's
+    /// written over
+    pub fn new(target: &'a mut T, font: &'a F) -> Self {
+        let mut console = DirectConsole {
+            columns: target.width() / font.width().max(1),
+            rows: target.height() / font.height().max(1),
+            target,
+            font,
+            cursor: (0, 0),
+            foreground: Color::WHITE,
+            background: Color::BLACK
+        };
+
+        console.clear_line(0);
+        console
+    }
+
+    fn new_line(&mut self) {
+        self.cursor = (0, (self.cursor.1 + 1) % self.rows);
+        self.clear_line(self.cursor.1);
+    }
+
+    fn clear_line(&mut self, row: usize) {
+        let height = self.font.height();
+
+        for y in row * height..(row + 1) * height {
+            for x in 0..self.columns * self.font.width() {
+                self.put_pixel(x, y, self.background);
+            }
+        }
+    }
+
+    fn draw(&mut self, character: char) {
+        let font = self.font;
+        let bitmap = font.bitmap(character);
+        let (width, height) = (font.width(), font.height());
+        let bytes_per_row = font.bytes_per_row();
+        let (left, top) = (self.cursor.0 * width, self.cursor.1 * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let set = bitmap[y * bytes_per_row + x / 8] & (0x80 >> (x % 8)) != 0;
+                self.put_pixel(left + x, top + y, if set { self.foreground } else { self.background });
+            }
+        }
+    }
+
+    fn put_pixel(&mut self, x: usize, y: usize, color: Color) {
+        let format = self.target.format();
+
+        // In bounds since columns and rows are worked out from the target's size, and the
+        // target's safety contract says all of that is writable
+        unsafe {
+            format.write(
+                self.target.address().add(y * self.target.pitch() + x * format.bytes_per_pixel()),
+                color
+            )
+        }
+    }
+}
+
+impl<T: FramebufferTarget, F: Font> fmt::Write for DirectConsole<'_, T, F> {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        // Too small for even one character, so there's nowhere to write to
+        if self.columns == 0 || self.rows == 0 {
+            return Ok(())
+        }
+
+        for character in string.chars() {
+            match character {
+                '\n' => self.new_line(),
+                '\r' => self.cursor.0 = 0,
+                _ if character.is_control() => {},
+                _ => {
+                    if self.cursor.0 == self.columns {
+                        self.new_line();
+                    }
+
+                    self.draw(character);
+                    self.cursor.0 += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
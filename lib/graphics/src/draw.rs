@@ -0,0 +1,251 @@
+//! Shapes and blits for CPUFrameBuffer. Unlike `write_rect_pixel_map` none of these fail when
+//! they go off the edge of the buffer, whatever doesn't fit is just cut off. Positions are signed
+//! so shapes can start off the top or left too (a circle centred on the corner, say)
+
+use crate::{AlphaColor, Color, CPUFrameBuffer, GraphicsError, Rect};
+
+impl CPUFrameBuffer {
+    /// Sets one pixel, if it's inside the buffer
+    pub fn set_pixel(&mut self, x: isize, y: isize, color: Color) {
+        if self.put(x, y, color) {
+            self.mark_dirty(Rect::new(x as usize, y as usize, 1, 1));
+        }
+    }
+
+    /// The colour at (x, y), or None if that's outside the buffer
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None
+        }
+
+        Some(self.buffer[y * self.stride + x])
+    }
+
+    /// Fills every pixel of `rect` that's inside the buffer with `color`
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let rect = rect.clamp(self.width, self.height);
+
+        for y in rect.y..rect.bottom() {
+            let start = y * self.stride + rect.x;
+            self.buffer[start..start + rect.width].fill(color);
+        }
+
+        self.mark_dirty(rect);
+    }
+
+    /// Draws the outline of `rect`, `thickness` pixels thick on the inside of it. A thickness that
+    /// covers the whole rect fills it
+    pub fn draw_rect(&mut self, rect: Rect, thickness: usize, color: Color) {
+        let thickness_x = thickness.min(rect.width.div_ceil(2));
+        let thickness_y = thickness.min(rect.height.div_ceil(2));
+
+        // Top and bottom go all the way across, the sides fill in between them
+        self.fill_rect(Rect::new(rect.x, rect.y, rect.width, thickness_y), color);
+        self.fill_rect(Rect::new(rect.x, rect.bottom() - thickness_y, rect.width, thickness_y), color);
+
+        let side_height = rect.height.saturating_sub(thickness_y * 2);
+        self.fill_rect(Rect::new(rect.x, rect.y + thickness_y, thickness_x, side_height), color);
+        self.fill_rect(Rect::new(rect.right() - thickness_x, rect.y + thickness_y, thickness_x, side_height), color);
+    }
+
+    /// Draws a one pixel wide line from `start` to `end`, including both ends, with Bresenham's
+    /// algorithm
+    pub fn draw_line(&mut self, start: (isize, isize), end: (isize, isize), color: Color) {
+        let (mut x, mut y) = start;
+
+        let dx = (end.0 - x).abs();
+        let dy = -(end.1 - y).abs();
+        let step_x = if x < end.0 { 1 } else { -1 };
+        let step_y = if y < end.1 { 1 } else { -1 };
+
+        // How far off the real line the next pixel would be, scaled up so it stays an integer
+        let mut error = dx + dy;
+
+        loop {
+            self.put(x, y, color);
+
+            if (x, y) == end {
+                break
+            }
+
+            let doubled = error * 2;
+
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+
+        self.mark_dirty_between(start.0.min(end.0), start.1.min(end.1), start.0.max(end.0) + 1, start.1.max(end.1) + 1);
+    }
+
+    /// Draws the outline of a circle, one pixel wide, with the midpoint circle algorithm
+    pub fn draw_circle(&mut self, center: (isize, isize), radius: usize, color: Color) {
+        let (center_x, center_y) = center;
+
+        self.for_each_octant_point(radius, |frame_buffer, x, y| {
+            // Each point on one eighth of the circle gives one on all of the others by symmetry
+            for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                frame_buffer.put(center_x + dx, center_y + dy, color);
+            }
+        });
+
+        self.mark_circle_dirty(center, radius);
+    }
+
+    /// Draws a filled in circle. It covers exactly the same pixels as `draw_circle` does, plus
+    /// everything inside
+    pub fn fill_circle(&mut self, center: (isize, isize), radius: usize, color: Color) {
+        let (center_x, center_y) = center;
+
+        // Spans between the symmetric points, so every row of the circle gets filled in. Some get
+        // filled more than once, which doesn't matter since it's all the same colour
+        self.for_each_octant_point(radius, |frame_buffer, x, y| {
+            for (half_width, dy) in [(x, y), (x, -y), (y, x), (y, -x)] {
+                frame_buffer.fill_span(center_x - half_width, center_x + half_width, center_y + dy, color);
+            }
+        });
+
+        self.mark_circle_dirty(center, radius);
+    }
+
+    /// The same as `write_rect_pixel_map`, except anything outside the buffer is cut off rather than
+    /// it failing. The only thing that can fail is `pixel_map` not being `width` * `height` long
+    pub fn blit(&mut self, pixel_map: &[Color], width: usize, height: usize, x: isize, y: isize) -> Result<(), GraphicsError> {
+        self.blit_with(pixel_map, width, height, x, y, |_, over| *over)
+    }
+
+    /// Draws `pixel_map` over what's in the buffer, blending each pixel in by its alpha. Anything
+    /// outside the buffer is cut off, the same as `blit`
+    pub fn blit_blended(&mut self, pixel_map: &[AlphaColor], width: usize, height: usize, x: isize, y: isize) -> Result<(), GraphicsError> {
+        self.blit_with(pixel_map, width, height, x, y, |under, over| match over.alpha {
+            0 => under,
+            0xff => over.color,
+            alpha => under.blend(over.color, alpha)
+        })
+    }
+
+    /// Blends a whole rectangle with one colour, for darkening or tinting what's behind something
+    /// (behind a panic message, say)
+    pub fn blend_rect(&mut self, rect: Rect, color: AlphaColor) {
+        let rect = rect.clamp(self.width, self.height);
+
+        for y in rect.y..rect.bottom() {
+            let start = y * self.stride + rect.x;
+
+            for pixel in &mut self.buffer[start..start + rect.width] {
+                *pixel = pixel.blend(color.color, color.alpha);
+            }
+        }
+
+        self.mark_dirty(rect);
+    }
+
+    /// Goes over the part of `pixel_map` that's inside the buffer, setting each pixel to whatever
+    /// `combine` gives from the pixel underneath and the one from the map
+    fn blit_with<P>(&mut self, pixel_map: &[P], width: usize, height: usize, x: isize, y: isize, combine: impl Fn(Color, &P) -> Color) -> Result<(), GraphicsError> {
+        if pixel_map.len() != width * height {
+            return Err(GraphicsError::BadDimensions)
+        }
+
+        // Where the visible part starts in the map, and in the buffer
+        let skip_x = x.min(0).unsigned_abs();
+        let skip_y = y.min(0).unsigned_abs();
+        let left = x.max(0) as usize;
+        let top = y.max(0) as usize;
+
+        let visible_width = width.saturating_sub(skip_x).min(self.width.saturating_sub(left));
+        let visible_height = height.saturating_sub(skip_y).min(self.height.saturating_sub(top));
+
+        if visible_width == 0 || visible_height == 0 {
+            return Ok(())
+        }
+
+        for row in 0..visible_height {
+            let source = &pixel_map[(skip_y + row) * width + skip_x..][..visible_width];
+            let start = (top + row) * self.stride + left;
+
+            for (pixel, over) in self.buffer[start..start + visible_width].iter_mut().zip(source) {
+                *pixel = combine(*pixel, over);
+            }
+        }
+
+        self.mark_dirty(Rect::new(left, top, visible_width, visible_height));
+
+        Ok(())
+    }
+
+    /// Sets a pixel without marking it dirty, so shapes can mark their whole area once instead.
+    /// Gives back whether the pixel was inside the buffer
+    fn put(&mut self, x: isize, y: isize, color: Color) -> bool {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return false
+        }
+
+        self.buffer[y as usize * self.stride + x as usize] = color;
+        true
+    }
+
+    /// Fills row `y` from `left` to `right` (both included), cut down to the buffer. Doesn't mark
+    /// anything dirty
+    fn fill_span(&mut self, left: isize, right: isize, y: isize, color: Color) {
+        if y < 0 || y as usize >= self.height || right < 0 {
+            return
+        }
+
+        let left = left.max(0) as usize;
+        let right = (right as usize).min(self.width.saturating_sub(1));
+
+        if left > right {
+            return
+        }
+
+        let start = y as usize * self.stride;
+        self.buffer[start + left..=start + right].fill(color);
+    }
+
+    /// Walks one eighth of a circle of `radius` around (0, 0), from straight down to 45 degrees,
+    /// giving `visit` each point as an (x, y) offset with x <= y
+    fn for_each_octant_point(&mut self, radius: usize, mut visit: impl FnMut(&mut Self, isize, isize)) {
+        let mut x = 0;
+        let mut y = radius as isize;
+        // Whether the midpoint between the next two candidate pixels is inside the circle
+        let mut decision = 1 - y;
+
+        while x <= y {
+            visit(self, x, y);
+
+            x += 1;
+
+            if decision < 0 {
+                decision += 2 * x + 1;
+            } else {
+                y -= 1;
+                decision += 2 * (x - y) + 1;
+            }
+        }
+    }
+
+    fn mark_circle_dirty(&mut self, center: (isize, isize), radius: usize) {
+        let radius = radius as isize;
+        self.mark_dirty_between(center.0 - radius, center.1 - radius, center.0 + radius + 1, center.1 + radius + 1);
+    }
+
+    /// Marks everything from (left, top) up to but not including (right, bottom) that's inside the
+    /// buffer as dirty
+    fn mark_dirty_between(&mut self, left: isize, top: isize, right: isize, bottom: isize) {
+        let clip = |value: isize, limit: usize| (value.max(0) as usize).min(limit);
+
+        let (left, right) = (clip(left, self.width), clip(right, self.width));
+        let (top, bottom) = (clip(top, self.height), clip(bottom, self.height));
+
+        if left < right && top < bottom {
+            self.mark_dirty(Rect::new(left, top, right - left, bottom - top));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use core::fmt;
+
+/// What can go wrong drawing to a framebuffer or writing to a TextBuffer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GraphicsError {
+    /// Something would have been drawn (or scrolled) at least partly outside of the buffer
+    OutOfBounds,
+    /// A size that doesn't work: zero, too small to fit anything in, or not agreeing with the
+    /// length of the data that came with it
+    BadDimensions,
+    /// A TextBuffer was given a control character that it doesn't handle. Contains the character
+    NonPrintableChar(char),
+    /// The GOP mode doesn't have a framebuffer (it's BltOnly), or it said the pixels were described
+    /// by a bitmask and then didn't give one
+    NoFramebuffer
+}
+
+impl fmt::Display for GraphicsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphicsError::OutOfBounds => write!(f, "tried to draw outside of the framebuffer"),
+            GraphicsError::BadDimensions => write!(f, "the dimensions don't fit the framebuffer or the data given"),
+            GraphicsError::NonPrintableChar(character) => write!(f, "{:?} isn't a printable character", character),
+            GraphicsError::NoFramebuffer => write!(f, "the graphics mode doesn't have a usable framebuffer")
+        }
+    }
+}
+
+/// What can go wrong loading a font
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FontError {
+    /// A line of a BDF file couldn't be made sense of. Lines count from 1
+    Parse { line: usize, kind: ParseErrorKind },
+    /// A glyph in a BDF file (at `line`) is a different width from the rest of the font
+    NotMonospaced { line: usize },
+    /// A glyph isn't the same size as the font, or its bitmap is the wrong length for its size
+    WrongGlyphSize,
+    /// The file isn't in the format it was read as (for PSF, the magic number is wrong)
+    WrongFormat,
+    /// A PSF file ends before everything its header says is in it
+    Truncated,
+    /// A PSF2 unicode table has something in it that isn't UTF-8
+    InvalidUnicodeTable,
+    /// The font doesn't have any glyphs
+    Empty
+}
+
+/// The ways a line of a BDF file can be wrong
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParseErrorKind {
+    /// A number was missing, or wasn't a number
+    ExpectedNumber,
+    /// A bounding box had a negative width or height
+    NegativeSize,
+    /// The header ended without a FONTBOUNDINGBOX
+    MissingBoundingBox,
+    /// FONT_ASCENT and FONT_DESCENT (or the bounding box) don't give a cell with any height
+    BadMetrics,
+    /// A bitmap row has something in it that isn't hex
+    InvalidHex,
+    /// A bitmap row is shorter than its glyph's BBX says it should be
+    RowTooShort,
+    /// A bitmap has more rows than its glyph's BBX says
+    MissingEndChar,
+    /// The file ended in the middle of a glyph
+    UnexpectedEnd
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseErrorKind::ExpectedNumber => "expected a number",
+            ParseErrorKind::NegativeSize => "a bounding box has a negative size",
+            ParseErrorKind::MissingBoundingBox => "the font has no FONTBOUNDINGBOX",
+            ParseErrorKind::BadMetrics => "the font's ascent and descent don't make sense",
+            ParseErrorKind::InvalidHex => "a bitmap row isn't hex",
+            ParseErrorKind::RowTooShort => "a bitmap row is too short",
+            ParseErrorKind::MissingEndChar => "a bitmap has more rows than its BBX says",
+            ParseErrorKind::UnexpectedEnd => "the file ends in the middle of a glyph"
+        })
+    }
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontError::Parse { line, kind } => write!(f, "line {}: {}", line, kind),
+            FontError::NotMonospaced { line } => write!(f, "line {}: the font isn't monospaced", line),
+            FontError::WrongGlyphSize => write!(f, "a glyph is the wrong size"),
+            FontError::WrongFormat => write!(f, "the file isn't in the expected font format"),
+            FontError::Truncated => write!(f, "the file ends before all of the font"),
+            FontError::InvalidUnicodeTable => write!(f, "the unicode table isn't valid UTF-8"),
+            FontError::Empty => write!(f, "the font doesn't have any glyphs")
+        }
+    }
+}
+
+/// What can go wrong decoding an image
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageError {
+    /// The file isn't in the format it was read as, or (for `Image::decode`) in any format that's
+    /// supported
+    WrongFormat,
+    /// The file ends before everything its header says is in it
+    Truncated,
+    /// The image is zero pixels in either direction, or so big the size overflows
+    BadDimensions,
+    /// A valid image, but using something that isn't supported (like compression, or an odd bit
+    /// depth)
+    Unsupported,
+    /// The header or the data contradicts itself, like a palette index past the end of the
+    /// palette
+    Corrupt
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ImageError::WrongFormat => "the file isn't in the expected image format",
+            ImageError::Truncated => "the file ends before all of the image",
+            ImageError::BadDimensions => "the image's size is zero or too big",
+            ImageError::Unsupported => "the image uses a feature that isn't supported",
+            ImageError::Corrupt => "the image data doesn't make sense"
+        })
+    }
+}
@@ -0,0 +1,269 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::FontError;
+
+/// A character's shape as a 1 bit mask, with no colour of its own. Colours get picked when it's
+/// drawn (see `CPUFrameBuffer::draw_glyph`) so the same font works for any text style
+///
+/// Rows are packed most significant bit first and padded to a whole byte, the same as the bitmaps
+/// in a BDF file, so a 12 pixel wide glyph has 2 bytes per row with the bottom 4 bits unused
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Glyph {
+    width: usize,
+    height: usize,
+    bits: Box<[u8]>
+}
+
+impl Glyph {
+    /// Fails if `bits` isn't exactly `height` rows of `width` bits rounded up to bytes
+    pub fn new(width: usize, height: usize, bits: Box<[u8]>) -> Result<Self, FontError> {
+        if bits.len() != width.div_ceil(8) * height {
+            return Err(FontError::WrongGlyphSize)
+        }
+
+        Ok(Glyph { width, height, bits })
+    }
+
+    /// A glyph with nothing set, which draws as just the background
+    pub fn blank(width: usize, height: usize) -> Self {
+        Glyph {
+            width,
+            height,
+            bits: alloc::vec![0; width.div_ceil(8) * height].into_boxed_slice()
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bytes_per_row(&self) -> usize {
+        self.width.div_ceil(8)
+    }
+
+    /// All of the rows, packed one after another
+    pub fn bits(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// The packed bits of row `y`
+    pub fn row(&self, y: usize) -> &[u8] {
+        &self.bits[y * self.bytes_per_row()..][..self.bytes_per_row()]
+    }
+
+    /// Whether the pixel at (x, y) is part of the character rather than the background
+    pub fn is_set(&self, x: usize, y: usize) -> bool {
+        self.row(y)[x / 8] & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Sets or clears the pixel at (x, y)
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        let index = y * self.bytes_per_row() + x / 8;
+
+        if value {
+            self.bits[index] |= 0x80 >> (x % 8);
+        } else {
+            self.bits[index] &= !(0x80 >> (x % 8));
+        }
+    }
+}
+
+/// Anything text can be drawn with. Glyphs are handed out as packed rows laid out the same way as
+/// in a Glyph, so fonts don't need to keep Glyphs around (a StaticFont can't, it has no allocator)
+pub trait Font {
+    /// Width and height of every glyph, in pixels
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// The packed rows of the glyph for `character`, or of the replacement glyph if the font
+    /// doesn't have it
+    fn bitmap(&self, character: char) -> &[u8];
+
+    fn bytes_per_row(&self) -> usize {
+        self.width().div_ceil(8)
+    }
+}
+
+impl<F: Font + ?Sized> Font for &F {
+    fn width(&self) -> usize {
+        (**self).width()
+    }
+
+    fn height(&self) -> usize {
+        (**self).height()
+    }
+
+    fn bitmap(&self, character: char) -> &[u8] {
+        (**self).bitmap(character)
+    }
+}
+
+/// A set of glyphs that all have the same width and height, for any characters at all. Fonts
+/// only cover a small part of unicode so this is a sparse table (sorted by character, and looked
+/// up with a binary search) rather than an array indexed by codepoint
+///
+/// Anything the font doesn't have gets drawn with the replacement glyph
+pub struct MonoFont {
+    pub width: usize,
+    pub height: usize,
+    glyphs: Vec<(char, Glyph)>,
+    replacement: Glyph
+}
+
+impl MonoFont {
+    /// An empty font. Until it's given one, the replacement glyph is an outlined box (what's
+    /// usually called tofu)
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut replacement = Glyph::blank(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+                    replacement.set(x, y, true);
+                }
+            }
+        }
+
+        MonoFont {
+            width,
+            height,
+            glyphs: Vec::new(),
+            replacement
+        }
+    }
+
+    /// Adds the glyph for `character`, giving back the one it replaced if there was one. Fails if
+    /// the glyph isn't the same size as the font
+    pub fn insert(&mut self, character: char, glyph: Glyph) -> Result<Option<Glyph>, FontError> {
+        self.check_size(&glyph)?;
+
+        match self.glyphs.binary_search_by_key(&character, |(character, _)| *character) {
+            Ok(index) => Ok(Some(core::mem::replace(&mut self.glyphs[index].1, glyph))),
+            Err(index) => {
+                self.glyphs.insert(index, (character, glyph));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Changes what characters the font doesn't have are drawn with
+    pub fn set_replacement(&mut self, glyph: Glyph) -> Result<(), FontError> {
+        self.check_size(&glyph)?;
+        self.replacement = glyph;
+        Ok(())
+    }
+
+    fn check_size(&self, glyph: &Glyph) -> Result<(), FontError> {
+        if glyph.width() != self.width || glyph.height() != self.height {
+            return Err(FontError::WrongGlyphSize)
+        }
+
+        Ok(())
+    }
+
+    /// The glyph for `character`, if the font has one
+    pub fn get(&self, character: char) -> Option<&Glyph> {
+        self.glyphs.binary_search_by_key(&character, |(character, _)| *character)
+            .ok()
+            .map(|index| &self.glyphs[index].1)
+    }
+
+    /// The glyph to draw `character` with, which is the replacement glyph if the font doesn't
+    /// have it
+    pub fn glyph(&self, character: char) -> &Glyph {
+        self.get(character).unwrap_or(&self.replacement)
+    }
+
+    pub fn replacement(&self) -> &Glyph {
+        &self.replacement
+    }
+
+    pub fn contains(&self, character: char) -> bool {
+        self.get(character).is_some()
+    }
+
+    /// Every character the font has a glyph for, in order
+    pub fn characters(&self) -> impl Iterator<Item = char> + '_ {
+        self.glyphs.iter().map(|(character, _)| *character)
+    }
+
+    /// How many characters the font has a glyph for
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+}
+
+impl Font for MonoFont {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn bitmap(&self, character: char) -> &[u8] {
+        self.glyph(character).bits()
+    }
+}
+
+/// A font made of nothing but borrowed tables, so it can be a `static` and be used without an
+/// allocator. The kernel gets one generated from a BDF file by its build script, which is what lets
+/// it print before its heap exists (and when the heap is what broke)
+pub struct StaticFont {
+    width: usize,
+    height: usize,
+    characters: &'static [char],
+    bitmaps: &'static [u8],
+    replacement: &'static [u8]
+}
+
+impl StaticFont {
+    /// # Arguments
+    /// * `characters` - Every character the font has, sorted
+    /// * `bitmaps` - The glyph for each character in `characters`, one after another, with each
+    ///   laid out the same way as a Glyph
+    /// * `replacement` - The glyph for characters the font doesn't have
+    ///
+    /// Panics (which is a compile error in a static) if the tables are the wrong size
+    pub const fn new(width: usize, height: usize, characters: &'static [char], bitmaps: &'static [u8], replacement: &'static [u8]) -> Self {
+        let glyph_size = width.div_ceil(8) * height;
+
+        assert!(bitmaps.len() == characters.len() * glyph_size, "The bitmaps don't match the characters");
+        assert!(replacement.len() == glyph_size, "The replacement glyph is the wrong size");
+
+        StaticFont { width, height, characters, bitmaps, replacement }
+    }
+
+    /// The glyph for `character`, if the font has one
+    pub fn get(&self, character: char) -> Option<&'static [u8]> {
+        let glyph_size = self.bytes_per_row() * self.height;
+
+        self.characters.binary_search(&character)
+            .ok()
+            .map(|index| &self.bitmaps[index * glyph_size..][..glyph_size])
+    }
+}
+
+impl Font for StaticFont {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn bitmap(&self, character: char) -> &[u8] {
+        self.get(character).unwrap_or(self.replacement)
+    }
+}
@@ -0,0 +1,138 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::{bmp, qoi, tga, AlphaColor, Color, CPUFrameBuffer, GraphicsError, ImageError};
+
+/// The most pixels an image can have, which is what QOI limits itself to. Sizes come straight
+/// out of files, so without this a broken (or malicious) header could ask for gigabytes
+pub(crate) const MAX_PIXELS: usize = 400_000_000;
+
+/// A decoded image, with the pixels in rows from the top left. Every format gets decoded to this,
+/// with formats that don't have transparency coming out fully opaque
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Box<[AlphaColor]>
+}
+
+impl Image {
+    /// Fails if `pixels` isn't exactly `width` * `height` long
+    pub fn new(width: usize, height: usize, pixels: Box<[AlphaColor]>) -> Result<Self, ImageError> {
+        if width.checked_mul(height) != Some(pixels.len()) {
+            return Err(ImageError::BadDimensions)
+        }
+
+        Ok(Image { width, height, pixels })
+    }
+
+    /// Decodes a BMP, QOI or TGA image, working out which from the start of the file. TGA doesn't
+    /// have a magic number so anything that isn't one of the others is tried as one
+    pub fn decode(bytes: &[u8]) -> Result<Self, ImageError> {
+        if bytes.starts_with(bmp::MAGIC) {
+            bmp::parse(bytes)
+        } else if bytes.starts_with(qoi::MAGIC) {
+            qoi::parse(bytes)
+        } else {
+            tga::parse(bytes)
+        }
+    }
+
+    pub fn pixels(&self) -> &[AlphaColor] {
+        &self.pixels
+    }
+
+    /// The colour at (x, y), or None if that's outside the image
+    pub fn pixel(&self, x: usize, y: usize) -> Option<AlphaColor> {
+        if x >= self.width || y >= self.height {
+            return None
+        }
+
+        Some(self.pixels[y * self.width + x])
+    }
+
+    /// The pixels as a pixel map for `CPUFrameBuffer::write_rect_pixel_map`, with anything
+    /// transparent blended onto `background`
+    pub fn to_colors(&self, background: Color) -> Vec<Color> {
+        self.pixels.iter()
+            .map(|pixel| background.blend(pixel.color, pixel.alpha))
+            .collect()
+    }
+
+    /// A copy stretched (or shrunk) to `width` by `height`, by picking the nearest pixel. That's
+    /// the right thing for pixel art logos, and it's cheap
+    pub fn scaled(&self, width: usize, height: usize) -> Result<Image, ImageError> {
+        if self.pixels.is_empty() || width == 0 || height == 0 || width.checked_mul(height).is_none_or(|size| size > MAX_PIXELS) {
+            return Err(ImageError::BadDimensions)
+        }
+
+        let mut pixels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            let source_y = y * self.height / height;
+
+            for x in 0..width {
+                pixels.push(self.pixels[source_y * self.width + x * self.width / width]);
+            }
+        }
+
+        Image::new(width, height, pixels.into_boxed_slice())
+    }
+
+    /// A copy scaled as big as it can be while fitting in `max_width` by `max_height`, keeping
+    /// its shape
+    pub fn fitted(&self, max_width: usize, max_height: usize) -> Result<Image, ImageError> {
+        if self.pixels.is_empty() {
+            return Err(ImageError::BadDimensions)
+        }
+
+        // Whichever direction runs out of room first decides the scale
+        let (width, height) = if self.width * max_height <= max_width * self.height {
+            ((self.width * max_height / self.height).max(1), max_height)
+        } else {
+            (max_width, (self.height * max_width / self.width).max(1))
+        };
+
+        self.scaled(width, height)
+    }
+}
+
+impl CPUFrameBuffer {
+    /// Draws an image with its top left corner at (x, y), blending in the transparent parts and
+    /// cutting off anything outside the buffer
+    pub fn draw_image(&mut self, image: &Image, x: isize, y: isize) -> Result<(), GraphicsError> {
+        self.blit_blended(image.pixels(), image.width, image.height, x, y)
+    }
+}
+
+/// Reads a little endian u16 at `offset`
+pub(crate) fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ImageError> {
+    bytes.get(offset..offset + 2)
+        .map(|field| u16::from_le_bytes([field[0], field[1]]))
+        .ok_or(ImageError::Truncated)
+}
+
+/// Reads a little endian u32 at `offset`
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ImageError> {
+    bytes.get(offset..offset + 4)
+        .map(|field| u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+        .ok_or(ImageError::Truncated)
+}
+
+/// Checks a size from a header is something that can be allocated, giving back the pixel count
+pub(crate) fn check_size(width: usize, height: usize) -> Result<usize, ImageError> {
+    match width.checked_mul(height) {
+        Some(size) if size != 0 && size <= MAX_PIXELS => Ok(size),
+        _ => Err(ImageError::BadDimensions)
+    }
+}
+
+/// Scales a value of `size` bits up to 8, so the biggest value becomes 255 rather than something
+/// a bit dimmer. Anything above the bottom `size` bits is ignored
+pub(crate) fn expand_bits(value: u32, size: u32) -> u8 {
+    match size {
+        0 => 0,
+        size if size >= 8 => (value >> (size - 8)) as u8,
+        size => ((value & ((1 << size) - 1)) * 255 / ((1 << size) - 1)) as u8
+    }
+}
@@ -3,9 +3,43 @@
 extern crate alloc;
 
 use alloc::boxed::Box;
-use core::ops::Deref;
-
-use uefi::proto::console::gop::FrameBuffer;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub mod bdf;
+pub mod bmp;
+pub mod psf;
+pub mod qoi;
+pub mod tga;
+
+mod ansi;
+mod console;
+mod direct;
+mod draw;
+mod error;
+mod font;
+mod image;
+mod pixel;
+mod rect;
+mod screenshot;
+mod scrollback;
+mod target;
+
+use ansi::{Action, Parser};
+use scrollback::Scrollback;
+
+pub use ansi::Style;
+pub use console::{Console, ConsoleLogger};
+pub use direct::DirectConsole;
+pub use error::{FontError, GraphicsError, ImageError, ParseErrorKind};
+pub use font::{Font, Glyph, MonoFont, StaticFont};
+pub use image::Image;
+pub use pixel::{AlphaColor, Channel, Color, PixelFormat};
+pub use rect::Rect;
+pub use target::{FramebufferTarget, RawFramebuffer};
+
+#[cfg(feature = "uefi")]
+pub use target::GopFramebuffer;
 
 /// This is a struct to contain the kernel's frame buffer (in the CPU's ram as opposed to the GOP
 /// memory mapped frame buffer we get from UEFI which is mapped to VRAM somewhere). This is good
@@ -16,83 +50,234 @@ use uefi::proto::console::gop::FrameBuffer;
 /// kernel when needed. This interface is particularly barebones since the access should be
 /// controlled by a higher level user-space available interface (that may or may not implement
 /// windows as a concept [at some point copium]).
+///
+/// Everything in here is a `Color`, and it only gets converted to whatever the hardware wants when
+/// it's flushed to a `FramebufferTarget`, so nothing drawing into it needs to care
+///
+/// Writing to the hardware framebuffer is slow (especially reading back from it, which is why this
+/// exists) so the buffer keeps track of which parts have changed and only flushes those
 pub struct CPUFrameBuffer {
     pub width: usize,
     pub height: usize,
+    /// Pixels per row, which can be more than `width`
     pub stride: usize,
-    pub buffer: Box<[u32]>
+    /// Anything writing straight into this needs to call `mark_dirty` for it to be flushed
+    pub buffer: Box<[Color]>,
+    /// The areas that have changed since the last flush. None of these touch each other
+    dirty: Vec<Rect>,
+    /// Where rows get converted to the target's format when it isn't the same as Color
+    scratch: Vec<u8>
 }
 
+/// Past this many separate dirty rectangles they all get merged into one, since keeping track of
+/// them stops being worth it
+const MAX_DIRTY_RECTS: usize = 16;
+
 impl CPUFrameBuffer {
-    /// # Safety
-    /// This inherits the `uefi::proto::console::gop::FrameBuffer::write_value`'s safety
-    /// considerations 
-    pub unsafe fn write_to_graphics(&self, frame_buffer: &mut FrameBuffer) {
-        unsafe {
-            frame_buffer.write_value(0, self.buffer.deref())
+    /// Makes a black buffer. It all starts dirty so the first flush clears whatever was on the
+    /// screen before
+    pub fn new(width: usize, height: usize) -> Self {
+        CPUFrameBuffer {
+            width,
+            height,
+            stride: width,
+            buffer: vec![Color::BLACK; width * height].into_boxed_slice(),
+            dirty: vec![Rect::new(0, 0, width, height)],
+            scratch: Vec::new()
         }
     }
 
-    /// Writes a linear array of u32 (GOP pixels) to the buffer as though it was a rectangle with
-    /// width and height as given, with the top right corner at (x,y)
+    /// Makes a black buffer the same size as `target`
+    pub fn for_target(target: &impl FramebufferTarget) -> Self {
+        Self::new(target.width(), target.height())
+    }
+
+    /// Records that `rect` has changed so the next flush copies it. The draw functions on here
+    /// already do this
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        let mut rect = rect.clamp(self.width, self.height);
+
+        if rect.is_empty() {
+            return
+        }
+
+        // Merging can make it big enough to touch something else, so this keeps going until
+        // nothing does
+        while let Some(index) = self.dirty.iter().position(|other| other.touches(&rect)) {
+            rect = rect.union(&self.dirty.swap_remove(index));
+        }
+
+        if self.dirty.len() == MAX_DIRTY_RECTS {
+            rect = self.dirty.drain(..).fold(rect, |all, other| all.union(&other));
+        }
+
+        self.dirty.push(rect);
+    }
+
+    /// Makes the next flush copy everything, for when the screen might have been drawn over by
+    /// something else
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.clear();
+        self.dirty.push(Rect::new(0, 0, self.width, self.height));
+    }
+
+    /// Writes a linear array of colours to the buffer as though it was a rectangle with width and
+    /// height as given, with the top right corner at (x,y)
     ///
     /// # Arguments
-    /// * `pixel_map` - A linear array of pixels in GOP format
+    /// * `pixel_map` - A linear array of colours
     /// * `width` - The width of the rectangle to be drawn
     /// * `height` - The height of the drawn rectangle
     /// * `x` - The x position of the top right pixel of the rectangle
     /// * `y` - The y position of the top right pixel of the rectangle
-    pub fn write_rect_pixel_map(&mut self, pixel_map: &[u32], width: usize, height: usize, x: usize, y:usize) -> Result<(), &'static str>{
+    pub fn write_rect_pixel_map(&mut self, pixel_map: &[Color], width: usize, height: usize, x: usize, y:usize) -> Result<(), GraphicsError>{
         if x + width > self.width {
-            return Err("The given the x position + pixel map width would go off the screen")
+            return Err(GraphicsError::OutOfBounds)
         }
 
         if y + height > self.height {
-            return Err("The given y position + pixel map height would go off the screen")
+            return Err(GraphicsError::OutOfBounds)
         }
 
         if pixel_map.len() != width * height {
-            return Err("The pixel map dimensions do not agree with its linear length")
-        }
-        unsafe {
-            let framebuf_base_addr = self.buffer.as_mut_ptr().add(y * self.stride + x) as *mut u32;
-            for row_index in 0..height {
-                let row_frame_offset = row_index * self.stride;
-                let row_pixmap_offset = row_index * width;
-                for col_index in 0..width {
-                    framebuf_base_addr
-                        .add( row_frame_offset + col_index)
-                        .write_volatile(pixel_map[row_pixmap_offset + col_index])
+            return Err(GraphicsError::BadDimensions)
+        }
+
+        // This is just normal memory so it can be copied a row at a time (it used to be written a
+        // pixel at a time with volatile writes, which was very slow for no reason)
+        for (row_index, row) in pixel_map.chunks_exact(width.max(1)).enumerate() {
+            let start = (y + row_index) * self.stride + x;
+            self.buffer[start..start + width].copy_from_slice(row);
+        }
+
+        self.mark_dirty(Rect::new(x, y, width, height));
+
+        Ok(())
+    }
+
+    /// Draws a glyph with its top left corner at (x,y), with the set bits in `foreground` and the
+    /// rest in `background`
+    pub fn draw_glyph(&mut self, glyph: &Glyph, x: usize, y: usize, foreground: Color, background: Color) -> Result<(), GraphicsError> {
+        self.draw_bitmap(glyph.bits(), (glyph.width(), glyph.height()), x, y, foreground, background)
+    }
+
+    /// The same as draw_glyph, but for a character from any Font
+    pub fn draw_char(&mut self, font: &impl Font, character: char, x: usize, y: usize, foreground: Color, background: Color) -> Result<(), GraphicsError> {
+        self.draw_bitmap(font.bitmap(character), (font.width(), font.height()), x, y, foreground, background)
+    }
+
+    /// Draws packed 1 bit rows (laid out the same way as a Glyph) that are `size` (width, height)
+    fn draw_bitmap(&mut self, bits: &[u8], size: (usize, usize), x: usize, y: usize, foreground: Color, background: Color) -> Result<(), GraphicsError> {
+        let (width, height) = size;
+
+        if x + width > self.width || y + height > self.height {
+            return Err(GraphicsError::OutOfBounds)
+        }
+
+        let bytes_per_row = width.div_ceil(8);
+
+        for (row, row_bits) in bits.chunks_exact(bytes_per_row.max(1)).take(height).enumerate() {
+            let start = (y + row) * self.stride + x;
+            let pixels = &mut self.buffer[start..start + width];
+
+            // Going a byte of the mask at a time rather than checking every pixel separately
+            for (chunk, bits) in pixels.chunks_mut(8).zip(row_bits) {
+                for (offset, pixel) in chunk.iter_mut().enumerate() {
+                    *pixel = if bits & (0x80 >> offset) != 0 { foreground } else { background };
                 }
             }
         }
+
+        self.mark_dirty(Rect::new(x, y, width, height));
+
+        Ok(())
+    }
+
+    /// Moves everything inside `rect` up by `rows` pixels. Whatever was in the top `rows` rows is
+    /// lost and the bottom `rows` rows are left as they were, for the caller to draw over
+    pub fn scroll_up(&mut self, rect: Rect, rows: usize) -> Result<(), GraphicsError> {
+        if rect.right() > self.width || rect.bottom() > self.height {
+            return Err(GraphicsError::OutOfBounds)
+        }
+
+        if rows >= rect.height {
+            return Ok(())
+        }
+
+        for y in rect.y..rect.bottom() - rows {
+            let source = (y + rows) * self.stride + rect.x;
+            self.buffer.copy_within(source..source + rect.width, y * self.stride + rect.x);
+        }
+
+        self.mark_dirty(rect);
+
         Ok(())
     }
 
-    // This is not particularly performant I think but I am not knowledgable enough to optimize it
-    pub fn flush(&self, framebuffer: *mut u32) {
-        for x in 0..self.stride {
-            for y in 0..self.height {
+    /// Copies everything that has changed since the last flush to `target`, converting it to the
+    /// target's format on the way. Each changed row goes over in one copy
+    pub fn flush(&mut self, target: &mut impl FramebufferTarget) -> Result<(), GraphicsError> {
+        if self.width > target.width() || self.height > target.height() {
+            return Err(GraphicsError::BadDimensions)
+        }
+
+        let format = target.format();
+        let bytes_per_pixel = format.bytes_per_pixel();
+
+        // When the hardware wants pixels laid out the same way as Color (which is most of the
+        // time) there's nothing to convert and the rows can be copied straight over
+        let direct = format.matches_color();
+
+        let mut dirty = core::mem::take(&mut self.dirty);
+
+        for rect in dirty.drain(..) {
+            let row_bytes = rect.width * bytes_per_pixel;
+
+            if !direct {
+                self.scratch.resize(row_bytes, 0);
+            }
+
+            for y in rect.y..rect.bottom() {
+                let start = y * self.stride + rect.x;
+                let colors = &self.buffer[start..start + rect.width];
+
+                // The target's safety contract guarantees the whole of every row is writable, and
+                // the rect was clamped to this buffer which is no bigger than the target
                 unsafe {
-                    framebuffer
-                        .add(y * self.stride + x)
-                        .write_volatile(self.buffer[y * self.stride + x])
+                    let destination = target.address().add(y * target.pitch() + rect.x * bytes_per_pixel);
+
+                    if direct {
+                        core::ptr::copy_nonoverlapping(colors.as_ptr() as *const u8, destination, row_bytes);
+                    } else {
+                        format.encode_row(colors, &mut self.scratch[..row_bytes]);
+                        core::ptr::copy_nonoverlapping(self.scratch.as_ptr(), destination, row_bytes);
+                    }
                 }
             }
         }
+
+        // Given back so its allocation gets reused
+        self.dirty = dirty;
+
+        Ok(())
     }
 }
 
-/// An array of pixelmaps that all have the same width and height
-/// Has all the ascii printable characters (32 - 126)
-pub struct MonoFont {
-    pub characters: [
-        Box<[u32]>; 126-32
-    ],
-    pub width: usize,
-    pub height: usize
+/// How the cursor gets drawn
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CursorShape {
+    /// The whole cell, with the character under it drawn in the opposite colours
+    #[default]
+    Block,
+    /// A line along the bottom of the cell
+    Underline,
+    /// A line down the left of the cell, like most text editors use
+    Bar
 }
 
+/// A text console. Besides printable characters it understands enough of the VT100/ANSI escape codes
+/// (cursor movement, erasing, SGR colours) for the output of ordinary logging crates to come out
+/// right
 pub struct TextBuffer {
     /// Height and width is in characters
     width: usize,
@@ -101,120 +286,523 @@ pub struct TextBuffer {
     /// Cursor position is (x,y) where x and y are zero indexed character positions
     cursor: (usize, usize),
 
-    text: Box<str>
+    /// Every cell, row by row. Anything the font doesn't have is still kept, and drawn with the
+    /// font's replacement glyph
+    text: Box<[char]>,
+
+    /// The style each cell in `text` was written with
+    styles: Box<[Style]>,
+
+    /// The style anything written from now on gets, which SGR sequences (or set_style) change
+    style: Style,
+
+    /// Where escape sequences get put back together, since they can be split across writes
+    parser: Parser,
+
+    /// Which cells have changed since they were last drawn by write_pixels. These move up with the
+    /// text when it scrolls
+    dirty: Box<[bool]>,
+
+    /// How many lines the text has moved up since write_pixels last ran. Rather than drawing every
+    /// glyph again, write_pixels moves the pixels that are already there up by this much
+    scrolled: usize,
+
+    /// Set when nothing has been drawn yet (or it was invalidated), so everything gets drawn
+    redraw: bool,
+
+    cursor_shape: CursorShape,
+    /// Whether the cursor is drawn at all. It's off by default since a console that's only used
+    /// for logging doesn't need one
+    cursor_visible: bool,
+    cursor_blinking: bool,
+    /// Which half of a blink the cursor is in. Only matters when it's blinking
+    blink_on: bool,
+    /// The cell the cursor was last drawn in, which needs drawing again without it once the
+    /// cursor moves (or blinks off)
+    drawn_cursor: Option<(usize, usize)>,
+
+    /// Lines that have scrolled off the top, kept so they can be scrolled back to. It doesn't
+    /// keep any until it's given a size with set_scrollback
+    scrollback: Scrollback,
+
+    /// How many lines back into the scrollback is being shown, where 0 is the live text
+    view_offset: usize
 }
 
 impl TextBuffer {
     /// Construct a new TextBuffer. Height and width are in characters.
-    /// The Box needs to be passed in since this library doesn't know what allocator to use (or if
-    /// one even exists) and therefore needs to be given ownership of a part of the heap to have a
-    /// size that is unknown at compile time.
-    pub fn new(height: usize, width: usize, mut text: Box<str>) -> Result<Self, &'static str>{
-        if !text.is_ascii() {
-            return Err("The provided string must be ascii")
-        }
-
-        if text.len() != height * width {
-            return Err("The provided string must have length equal to height * width")
-        }
-
-        // Now we blank out the text by making it all spaces. This is just an aesthetic choice so
-        // any correctly sized inputted string can be used to make an easy to use text buffer
-        
-        unsafe {
-            for byte in text.as_bytes_mut() {
-                // 32u8 is ascii space
-                *byte = 32u8;
-            }
+    /// This used to need the text passed in as a Box<str> since the library didn't know what
+    /// allocator to use (or if one even existed), but it uses alloc now and the text is chars
+    /// rather than ASCII bytes anyway
+    pub fn new(height: usize, width: usize) -> Result<Self, GraphicsError>{
+        if height == 0 || width == 0 {
+            return Err(GraphicsError::BadDimensions)
         }
 
         Ok (
             Self {
-                height: height,
-                width: width,
+                height,
+                width,
                 cursor: (0,0),
-                text: text
+                text: vec![' '; width * height].into_boxed_slice(),
+                styles: vec![Style::DEFAULT; width * height].into_boxed_slice(),
+                style: Style::DEFAULT,
+                parser: Parser::new(),
+                dirty: vec![false; width * height].into_boxed_slice(),
+                scrolled: 0,
+                redraw: true,
+                cursor_shape: CursorShape::Block,
+                cursor_visible: false,
+                cursor_blinking: false,
+                blink_on: true,
+                drawn_cursor: None,
+                scrollback: Scrollback::new(width, 0),
+                view_offset: 0
             }
         )
     }
 
+    /// Width in characters
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height in characters
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Where the next character goes, as (column, row). The column is one past the end of the line
+    /// after the last column has been written, until the next character wraps it
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    pub fn cursor_shape(&self) -> CursorShape {
+        self.cursor_shape
+    }
+
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Shows or hides the cursor. This is the same as writing `ESC [ ? 25 h` or `ESC [ ? 25 l`
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+    }
+
+    /// Makes the cursor blink (or stop blinking). It only blinks as fast as `tick` is called
+    pub fn set_cursor_blinking(&mut self, blinking: bool) {
+        self.cursor_blinking = blinking;
+        self.blink_on = true;
+    }
+
+    /// Whether the next write_pixels will draw the cursor, taking blinking into account. It's
+    /// never drawn while looking back through the scrollback
+    pub fn cursor_showing(&self) -> bool {
+        self.cursor_visible && (self.blink_on || !self.cursor_blinking) && self.view_offset == 0
+    }
+
+    /// Flips a blinking cursor between showing and not. This is meant to be called from a timer
+    /// (every half a second or so), and gives back whether anything changed and so whether it's
+    /// worth calling write_pixels
+    pub fn tick(&mut self) -> bool {
+        if !self.cursor_visible || !self.cursor_blinking {
+            return false
+        }
+
+        self.blink_on = !self.blink_on;
+        true
+    }
+
+    /// The characters on row `y`, with blank cells as spaces
+    pub fn line(&self, y: usize) -> &[char] {
+        &self.text[y * self.width..(y + 1) * self.width]
+    }
+
+    /// The style the character at (x, y) was written with
+    pub fn style_at(&self, x: usize, y: usize) -> Style {
+        self.styles[y * self.width + x]
+    }
+
+    /// Keeps up to `lines` lines that scroll off the top, so they can be looked back at with
+    /// scroll_view_up. Changing it throws away the lines that were already kept
+    pub fn set_scrollback(&mut self, lines: usize) {
+        self.scrollback = Scrollback::new(self.width, lines);
+        self.scroll_to_live();
+    }
+
+    /// How many lines are in the scrollback right now
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    /// How many lines back from the live text is being shown
+    pub fn view_offset(&self) -> usize {
+        self.view_offset
+    }
+
+    /// Shows `lines` further back into the scrollback, stopping at the oldest line. Anything
+    /// written meanwhile still goes into the live text, and the view stays on the same lines
+    /// while it scrolls
+    pub fn scroll_view_up(&mut self, lines: usize) {
+        self.set_view_offset(self.view_offset.saturating_add(lines));
+    }
+
+    /// Shows `lines` less far back, stopping at the live text
+    pub fn scroll_view_down(&mut self, lines: usize) {
+        self.set_view_offset(self.view_offset.saturating_sub(lines));
+    }
+
+    /// Goes back a whole screen, the same as the page up key does in a terminal
+    pub fn page_up(&mut self) {
+        self.scroll_view_up(self.height);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_view_down(self.height);
+    }
+
+    /// Goes back to showing the live text
+    pub fn scroll_to_live(&mut self) {
+        self.set_view_offset(0);
+    }
+
+    fn set_view_offset(&mut self, offset: usize) {
+        let offset = offset.min(self.scrollback.len());
+
+        // Everything on the screen changes, and the pixels can't be moved since the lines
+        // coming into view haven't been drawn anywhere
+        if offset != self.view_offset {
+            self.view_offset = offset;
+            self.redraw = true;
+        }
+    }
+
+    /// What's shown on row `y`, which is the same as `line` unless the view is scrolled back
+    pub fn visible_line(&self, y: usize) -> &[char] {
+        self.visible_row(y).0
+    }
+
+    /// The style of what's shown at (x, y), which is the same as `style_at` unless the view is
+    /// scrolled back
+    pub fn visible_style_at(&self, x: usize, y: usize) -> Style {
+        self.visible_row(y).1[x]
+    }
+
+    /// The characters and styles shown on row `y`. The scrollback and the live text are one long
+    /// list of lines, and the view is the `height` of them that end `view_offset` from the bottom
+    fn visible_row(&self, y: usize) -> (&[char], &[Style]) {
+        let history = self.scrollback.len() - self.view_offset + y;
+
+        if history < self.scrollback.len() {
+            self.scrollback.line(history)
+        } else {
+            let line = (history - self.scrollback.len()) * self.width;
+            (&self.text[line..line + self.width], &self.styles[line..line + self.width])
+        }
+    }
+
+    /// The style that text written from now on gets
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    /// Changes the style of text written from now on. This is the same as writing an SGR sequence,
+    /// just without having to build one
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    /// Makes the next write_pixels draw every cell, for when it's drawing to a different
+    /// framebuffer (or with a different font or padding) than last time, or something else has
+    /// drawn over the text
+    pub fn invalidate(&mut self) {
+        self.redraw = true;
+    }
+
     #[inline]
     /// Moves the text upward one line. This happens when the cursor tries to move beyond the
     /// bottom of the buffer.
     fn shift_up(&mut self) {
-        unsafe {
-            core::intrinsics::copy(
-                // The source, the start of the second line
-                self.text.as_ptr().add(self.width),
+        self.scrollback.push(&self.text[..self.width], &self.styles[..self.width]);
 
-                // The destination, the start of the buffer
-                self.text.as_mut_ptr(),
+        // While looking back through the scrollback the view stays on the same lines, rather than
+        // being dragged along by whatever is being written
+        if self.view_offset > 0 {
+            self.view_offset = (self.view_offset + 1).min(self.scrollback.len());
+        }
 
-                // The number of bytes to copy: the width times the number of lines other than the
-                // first
-                self.width * (self.height - 1)
-            );
+        self.text.copy_within(self.width.., 0);
 
-            // Blank out the final line with spaces
-            core::intrinsics::write_bytes(
-                // A ptr to the start of the first line
-                self.text.as_mut_ptr().add(self.width * (self.height-1)),
-                
-                // ASCII space
-                32u8,
-                
-                // The number of bytes to write (the width of a line)
-                self.width
-            )
-        }
+        // The dirty cells move with the text, and the new line (which is blank) needs drawing
+        // since the pixels that get moved up into it are whatever used to be at the top
+        self.dirty.copy_within(self.width.., 0);
+        self.styles.copy_within(self.width.., 0);
+        let last_line = self.width * (self.height - 1);
+        self.text[last_line..].fill(' ');
+        self.dirty[last_line..].fill(true);
+        self.styles[last_line..].fill(self.style);
+
+        self.scrolled += 1;
     }
 
     #[inline]
-    /// Mutates self's cursor position and returns the previous location (that may not be the x,y
-    /// value that was stored if the buffer had to be moved up).
+    /// Mutates self's cursor position and returns where the next character should go (that may
+    /// not be the x,y value that was stored if the line was full).
+    ///
+    /// Writing in the last column leaves the cursor one past the end of the line rather than
+    /// wrapping straight away, the same as a terminal does, so a line that exactly fills the width
+    /// followed by a newline doesn't leave a blank line. The wrap happens when the next character
+    /// is written. (eta: this used to let x get to the width and write there, which went into the
+    /// next line or past the end of the buffer, and it wrapped to column 1 instead of 0)
     fn next(&mut self) -> (usize, usize){
-        let (x,y) = self.cursor;
-        
-        match (x==self.width, y==self.height) {
-            (false, _) => self.cursor = (x+1, y),
-            (true, false)  => self.cursor = (1, y+1),
-            (true, true) => {
-                self.cursor = (0,y);
+        let (mut x, mut y) = self.cursor;
+
+        if x >= self.width {
+            x = 0;
+
+            if y + 1 == self.height {
                 self.shift_up();
-                return (0, y-1);
+            } else {
+                y += 1;
+            }
+        }
+
+        self.cursor = (x + 1, y);
+        (x, y)
+    }
+
+    /// Write a character at the current cursor position and then advance the cursor. Control
+    /// characters and escape sequences are acted on instead of being written. A sequence can be
+    /// split over several calls, in which case nothing happens until the last bit of it arrives
+    pub fn write_char(&mut self, character: char) -> Result<(), GraphicsError>{
+        // The cursor stays on while something's being written, the same as in a terminal, so it
+        // doesn't disappear while someone is typing
+        self.blink_on = true;
+
+        match self.parser.advance(character) {
+            None => Ok(()),
+            Some(Action::Print(character)) => self.print(character),
+            Some(Action::Control(character)) => {
+                self.control(character);
+                Ok(())
             },
+            Some(Action::Csi { parameters, count, private, final_byte }) => {
+                if private {
+                    self.private_csi(&parameters[..count], final_byte);
+                } else {
+                    self.csi(&parameters[..count], final_byte);
+                }
+                Ok(())
+            }
         }
+    }
 
-        return (x,y);
+    /// Puts a character at the cursor and moves it along
+    fn print(&mut self, character: char) -> Result<(), GraphicsError> {
+        // The parser has already taken out the C0 controls, but not the C1 ones (0x80 - 0x9f)
+        if character.is_control() {
+            return Err(GraphicsError::NonPrintableChar(character))
+        }
+
+        let cursor = self.next();
+        let index = cursor.1 * self.width + cursor.0;
+        self.text[index] = character;
+        self.styles[index] = self.style;
+        self.dirty[index] = true;
+
+        Ok(())
     }
 
-    /// Write a character at the current cursor position and then advance the cursor
-    pub fn write_char(&mut self, character: char) -> Result<(), &'static str>{
-        if character == '\n' {
-            if self.cursor.1 + 1 == self.height {
-                self.shift_up();
-                self.cursor.0 = 0;
-            } else {
-                self.cursor = (0, self.cursor.1 + 1);
+    /// Handles the C0 control characters that mean something to a console. The rest (like BEL)
+    /// are dropped
+    fn control(&mut self, character: char) {
+        match character {
+            '\n' => {
+                if self.cursor.1 + 1 == self.height {
+                    self.shift_up();
+                    self.cursor.0 = 0;
+                } else {
+                    self.cursor = (0, self.cursor.1 + 1);
+                }
+            },
+            '\r' => self.cursor.0 = 0,
+            // Tab stops are every 8 columns, and a tab never wraps onto the next line
+            '\t' => self.cursor.0 = ((self.cursor.0 / 8 + 1) * 8).min(self.width - 1),
+            // Backspace only moves the cursor, it's up to whoever sent it to write over the
+            // character (which is what terminals do too)
+            '\x08' => self.cursor.0 = self.cursor.0.min(self.width - 1).saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    /// Handles a finished CSI (`ESC [`) sequence. Anything that isn't supported is ignored
+    ///
+    /// # Arguments
+    /// * `parameters` - The numbers in the sequence, with ones that were left out as 0
+    /// * `final_byte` - The character that ended the sequence, which says what it does
+    fn csi(&mut self, parameters: &[u16], final_byte: char) {
+        // Most sequences treat a missing (or 0) parameter as 1
+        let parameter = |index: usize, default: usize| match parameters.get(index) {
+            Some(0) | None => default,
+            Some(value) => *value as usize
+        };
+
+        let (x, y) = self.cursor;
+        // The cursor can be one past the end of a line when it's waiting to wrap
+        let x = x.min(self.width - 1);
+        let cursor = y * self.width + x;
+
+        match final_byte {
+            // Cursor up, down, forward and back
+            'A' => self.cursor = (x, y.saturating_sub(parameter(0, 1))),
+            'B' => self.cursor = (x, (y + parameter(0, 1)).min(self.height - 1)),
+            'C' => self.cursor = ((x + parameter(0, 1)).min(self.width - 1), y),
+            'D' => self.cursor = (x.saturating_sub(parameter(0, 1)), y),
+            // Start of the next or previous line
+            'E' => self.cursor = (0, (y + parameter(0, 1)).min(self.height - 1)),
+            'F' => self.cursor = (0, y.saturating_sub(parameter(0, 1))),
+            // Column, and then row and column. These count from 1
+            'G' => self.cursor = ((parameter(0, 1) - 1).min(self.width - 1), y),
+            'H' | 'f' => self.cursor = (
+                (parameter(1, 1) - 1).min(self.width - 1),
+                (parameter(0, 1) - 1).min(self.height - 1)
+            ),
+            // Erase in display: from the cursor to the end, from the start to the cursor, or all
+            'J' => match parameter(0, 0) {
+                0 => self.erase(cursor..self.width * self.height),
+                1 => self.erase(0..cursor + 1),
+                2 | 3 => self.erase(0..self.width * self.height),
+                _ => {}
+            },
+            // Erase in line, the same but only for the cursor's line
+            'K' => match parameter(0, 0) {
+                0 => self.erase(cursor..(y + 1) * self.width),
+                1 => self.erase(y * self.width..cursor + 1),
+                2 => self.erase(y * self.width..(y + 1) * self.width),
+                _ => {}
+            },
+            // Insert and delete characters, the same as insert_blanks and delete_chars
+            '@' => self.insert_blanks(parameter(0, 1)),
+            'P' => self.delete_chars(parameter(0, 1)),
+            'm' => self.style.apply_sgr(parameters),
+            _ => {}
+        }
+    }
+
+    /// Handles a finished `ESC [ ?` sequence. The only one that means anything here is 25, which
+    /// shows (h) or hides (l) the cursor
+    fn private_csi(&mut self, parameters: &[u16], final_byte: char) {
+        if parameters.contains(&25) {
+            match final_byte {
+                'h' => self.cursor_visible = true,
+                'l' => self.cursor_visible = false,
+                _ => {}
             }
-            return Ok(())
+        }
+    }
+
+    /// The cursor's column as somewhere that can be edited. It can be one past the end of the
+    /// line when it's waiting to wrap, and then it counts as being on the last column
+    fn cursor_column(&self) -> usize {
+        self.cursor.0.min(self.width - 1)
+    }
+
+    /// Moves the cursor one column left, stopping at the start of the line
+    pub fn move_left(&mut self) {
+        self.cursor.0 = self.cursor_column().saturating_sub(1);
+    }
+
+    /// Moves the cursor one column right, stopping at the last column
+    pub fn move_right(&mut self) {
+        self.cursor.0 = (self.cursor_column() + 1).min(self.width - 1);
+    }
+
+    /// Writes a character at the cursor, moving everything from there to the end of the line
+    /// right to make room for it rather than writing over it. Whatever gets pushed off the end of
+    /// the line is lost
+    pub fn insert_char(&mut self, character: char) -> Result<(), GraphicsError> {
+        if character.is_control() {
+            return Err(GraphicsError::NonPrintableChar(character))
         }
 
-        if (!character.is_ascii() || u32::from(character) > 126 || u32::from(character) < 32) {
-            return Err("The provided character must be a printable ASCII")
+        // When the cursor is waiting to wrap the character goes on the next line, where there's
+        // nothing to move out of the way
+        if self.cursor.0 < self.width {
+            self.insert_blanks(1);
         }
-        
-        unsafe {
-            let cursor = self.next();
-            self.text.as_bytes_mut()[cursor.1 * self.width + cursor.0] =
-                u8::try_from(character).or(Err("This error shouldn't be possible"))?
+
+        self.blink_on = true;
+        self.print(character)
+    }
+
+    /// Removes the character at the cursor, moving the rest of the line left to fill the gap
+    pub fn delete_char(&mut self) {
+        self.delete_chars(1);
+    }
+
+    /// Removes the character before the cursor and moves the cursor back onto where it was, which
+    /// is what the backspace key does in a shell. Does nothing at the start of a line
+    pub fn backspace(&mut self) {
+        if self.cursor.0 == 0 {
+            return
         }
 
-        Ok(())
+        // When it's waiting to wrap the cursor is past the last column, so this still lands on
+        // the last character written
+        self.cursor.0 -= 1;
+        self.delete_chars(1);
+    }
+
+    /// Blanks everything from the cursor to the end of its line
+    pub fn clear_to_end_of_line(&mut self) {
+        let line = self.cursor.1 * self.width;
+        self.erase(line + self.cursor_column()..line + self.width);
+    }
+
+    /// Moves everything from the cursor to the end of the line `count` cells right, leaving blanks
+    /// behind. The cursor doesn't move
+    fn insert_blanks(&mut self, count: usize) {
+        let line = self.cursor.1 * self.width;
+        let start = line + self.cursor_column();
+        let end = line + self.width;
+        let count = count.min(end - start);
+
+        self.text.copy_within(start..end - count, start + count);
+        self.styles.copy_within(start..end - count, start + count);
+        self.dirty[start + count..end].fill(true);
+        self.erase(start..start + count);
+    }
+
+    /// Removes `count` cells at the cursor, moving the rest of the line left and leaving blanks
+    /// at the end. The cursor doesn't move
+    fn delete_chars(&mut self, count: usize) {
+        let line = self.cursor.1 * self.width;
+        let start = line + self.cursor_column();
+        let end = line + self.width;
+        let count = count.min(end - start);
+
+        self.text.copy_within(start + count..end, start);
+        self.styles.copy_within(start + count..end, start);
+        self.dirty[start..end - count].fill(true);
+        self.erase(end - count..end);
+    }
+
+    /// Blanks out a range of cells. They get the current style, so erasing after changing the
+    /// background colour fills with that colour
+    fn erase(&mut self, cells: core::ops::Range<usize>) {
+        self.text[cells.clone()].fill(' ');
+        self.styles[cells.clone()].fill(self.style);
+        self.dirty[cells].fill(true);
     }
 
     /// Write a str into the text buffer
-    pub fn write_str(&mut self, string: &str) -> Result<(), &'static str> {
+    pub fn write_str(&mut self, string: &str) -> Result<(), GraphicsError> {
         for character in string.chars() {
             self.write_char(character)?
         }
@@ -222,73 +810,156 @@ impl TextBuffer {
         Ok(())
     }
 
-    pub fn write_pixels(&self, frame_buffer: &mut CPUFrameBuffer, font: &MonoFont, padding: (usize, usize)) -> Result<(), &'static str> {
+    /// Draws the text to `frame_buffer`. Only the cells that changed since the last call get drawn,
+    /// and if the text scrolled the pixels already in the framebuffer get moved up to match
+    /// instead of every glyph being drawn again (which is what made logging lots of lines slow)
+    ///
+    /// This assumes it's given the same framebuffer, font and padding every time. If that's not
+    /// the case call `invalidate` first
+    pub fn write_pixels(&mut self, frame_buffer: &mut CPUFrameBuffer, font: &impl Font, padding: (usize, usize)) -> Result<(), GraphicsError> {
+        let (font_width, font_height) = (font.width(), font.height());
+
         // These two bounds check mean that it is guaranteed to be safe to do all the memory
         // copying I want to do
-        if self.width * font.width + padding.0 >= frame_buffer.width {
-            return Err("The framebuffer is too small for a line of text")
+        if self.width * font_width + padding.0 >= frame_buffer.width {
+            return Err(GraphicsError::OutOfBounds)
+        }
+
+        if self.height * font_height + padding.1 >= frame_buffer.height { 
+            return Err(GraphicsError::OutOfBounds)
         }
 
-        if self.height * font.height + padding.1 >= frame_buffer.height { 
-            return Err("The framebuffer is too short for the text ")
+        if self.view_offset > 0 {
+            return self.write_scrollback_pixels(frame_buffer, font, padding)
         }
 
-        for (index, character) in self.text.chars()
-            .enumerate() {
-            let glyph = font.characters.get(
-                // 32 is the start of the ascii printable characters block and can therefore be
-                // used to calculate the offset into the glyph array.
-                u32::from(character) as usize - 32
-            )
-            // This error shouldn't be possible if this struct's API is used to interface with it
-            // This prevents panics if unsafe code is used to directly modify the struct for
-            // whatever reason
-            .ok_or("A character was not a printable ascii character")?;
+        // Once it's scrolled by the whole height nothing on the screen is worth keeping
+        if self.scrolled >= self.height {
+            self.redraw = true;
+        }
+
+        // Wherever the cursor was drawn gets drawn again without it. If the text scrolled, the
+        // pixels it was drawn on have moved up with everything else
+        if let Some((x, y)) = self.drawn_cursor.take() {
+            if y >= self.scrolled {
+                self.dirty[(y - self.scrolled) * self.width + x] = true;
+            }
+        }
+
+        if self.redraw {
+            self.dirty.fill(true);
+        } else if self.scrolled > 0 {
+            frame_buffer.scroll_up(
+                Rect::new(padding.0, padding.1, self.width * font_width, self.height * font_height),
+                self.scrolled * font_height
+            )?;
+        }
+
+        self.scrolled = 0;
+
+        for (index, character) in self.text.iter()
+            .enumerate()
+            .filter(|(index, _)| self.dirty[*index]) {
+            let (foreground, background) = self.styles[index].colors();
             
-            frame_buffer.write_rect_pixel_map(
-                glyph, font.width, font.height,
-                (index % self.width) * font.width + padding.0,
-                index / self.width * font.height + padding.1
+            frame_buffer.draw_char(
+                font, *character,
+                (index % self.width) * font_width + padding.0,
+                index / self.width * font_height + padding.1,
+                foreground, background
             )?
         }
 
+        self.dirty.fill(false);
+        self.redraw = false;
+
+        if self.cursor_showing() {
+            self.draw_cursor(frame_buffer, font, padding)?;
+        }
+
         Ok(())
     }
 
-    pub fn dbg_print_cursor(&mut self) -> Result<(), &'static str> {
-        let cursor = self.cursor;
-        self.cursor = (0, 0);
-        
-        let usize_digit_to_char = |digit: usize| {
-            match digit{
-                0 => Ok('0'),
-                1 => Ok('1'),
-                2 => Ok('2'),
-                3 => Ok('3'),
-                4 => Ok('4'),
-                5 => Ok('5'),
-                6 => Ok('6'),
-                7 => Ok('7'),
-                8 => Ok('8'),
-                9 => Ok('9'),
-                _ => Err("")
-            }
-        };
+    /// write_pixels for when the view is scrolled back. None of the dirty tracking lines up with
+    /// what's on the screen then, so if anything at all has changed the whole view gets drawn.
+    /// Looking back through the scrollback isn't something that happens while lots is being
+    /// written, so that's fine
+    fn write_scrollback_pixels(&mut self, frame_buffer: &mut CPUFrameBuffer, font: &impl Font, padding: (usize, usize)) -> Result<(), GraphicsError> {
+        if !self.redraw && self.scrolled == 0 && !self.dirty.contains(&true) {
+            return Ok(())
+        }
 
-        for offset in 0..1+cursor.0.ilog10() {
-            self.write_char(
-                usize_digit_to_char((cursor.0 / (10usize.saturating_pow(offset)) % 10))?
-            )?;
+        for y in 0..self.height {
+            let (text, styles) = self.visible_row(y);
+
+            for (x, (character, style)) in text.iter().zip(styles).enumerate() {
+                let (foreground, background) = style.colors();
+
+                frame_buffer.draw_char(
+                    font, *character,
+                    x * font.width() + padding.0,
+                    y * font.height() + padding.1,
+                    foreground, background
+                )?
+            }
         }
-        
-        self.write_char(',');
 
-        for offset in 0..1+cursor.1.ilog10() {
-            self.write_char(
-                usize_digit_to_char((cursor.1 / (10usize.saturating_pow(offset)) % 10))?
-            )?;
+        // Scrolling back to the live text sets redraw, so there's nothing to keep track of until
+        // then
+        self.dirty.fill(false);
+        self.scrolled = 0;
+        self.redraw = false;
+        self.drawn_cursor = None;
+
+        Ok(())
+    }
+
+    /// Draws the cursor over the cell it's in
+    fn draw_cursor(&mut self, frame_buffer: &mut CPUFrameBuffer, font: &impl Font, padding: (usize, usize)) -> Result<(), GraphicsError> {
+        let (x, y) = (self.cursor_column(), self.cursor.1);
+        let index = y * self.width + x;
+        let (font_width, font_height) = (font.width(), font.height());
+        let (left, top) = (x * font_width + padding.0, y * font_height + padding.1);
+        let (foreground, background) = self.styles[index].colors();
+
+        match self.cursor_shape {
+            CursorShape::Block => frame_buffer.draw_char(font, self.text[index], left, top, background, foreground)?,
+            // A sixteenth of the cell's height (but at least a pixel) thick, which is 2 pixels with
+            // spleen
+            CursorShape::Underline => {
+                let thickness = (font_height / 16).max(1);
+                frame_buffer.fill_rect(Rect::new(left, top + font_height - thickness, font_width, thickness), foreground);
+            },
+            CursorShape::Bar => {
+                let thickness = (font_width / 8).max(1);
+                frame_buffer.fill_rect(Rect::new(left, top, thickness, font_height), foreground);
+            }
         }
 
+        self.drawn_cursor = Some((x, y));
+
         Ok(())
     }
+
+    /// Writes the cursor position (before writing it) at the top left corner
+    pub fn dbg_print_cursor(&mut self) -> Result<(), GraphicsError> {
+        let cursor = self.cursor;
+        self.cursor = (0, 0);
+
+        // This used to turn each digit into a char by hand, and came out backwards
+        self.write_str(&alloc::format!("{},{}", cursor.0, cursor.1))
+    }
+}
+
+/// Lets anything that formats (`write!`, `format_args!` and so on) write to the buffer. Errors
+/// (like a character that isn't printable ASCII) just come out as fmt::Error since that's all
+/// fmt::Write allows
+impl core::fmt::Write for TextBuffer {
+    fn write_str(&mut self, string: &str) -> core::fmt::Result {
+        TextBuffer::write_str(self, string).or(Err(core::fmt::Error))
+    }
+
+    fn write_char(&mut self, character: char) -> core::fmt::Result {
+        TextBuffer::write_char(self, character).or(Err(core::fmt::Error))
+    }
 }
@@ -0,0 +1,210 @@
+/// A colour in the one colour space all the drawing code works in: 8 bits each of red, green and
+/// blue, stored as 0x00RRGGBB. It only gets turned into whatever the hardware wants (see
+/// `PixelFormat`) when a CPUFrameBuffer is flushed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[repr(transparent)]
+pub struct Color(u32);
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(0xff, 0xff, 0xff);
+    pub const RED: Color = Color::rgb(0xff, 0, 0);
+    pub const GREEN: Color = Color::rgb(0, 0xff, 0);
+    pub const BLUE: Color = Color::rgb(0, 0, 0xff);
+    pub const YELLOW: Color = Color::rgb(0xff, 0xff, 0);
+    pub const CYAN: Color = Color::rgb(0, 0xff, 0xff);
+    pub const GREY: Color = Color::rgb(0x80, 0x80, 0x80);
+
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color((red as u32) << 16 | (green as u32) << 8 | blue as u32)
+    }
+
+    /// From a 0xRRGGBB value, like the colours written in CSS. Anything above the bottom 24 bits
+    /// is ignored
+    pub const fn from_hex(hex: u32) -> Self {
+        Color(hex & 0x00ff_ffff)
+    }
+
+    pub const fn hex(self) -> u32 {
+        self.0
+    }
+
+    pub const fn red(self) -> u8 {
+        (self.0 >> 16) as u8
+    }
+
+    pub const fn green(self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    pub const fn blue(self) -> u8 {
+        self.0 as u8
+    }
+
+    /// `over` drawn on top of this, where an `alpha` of 0 leaves this as it is and 255 gives
+    /// `over`
+    pub const fn blend(self, over: Color, alpha: u8) -> Color {
+        // (a * x + (255 - a) * y) / 255, rounded, without a division
+        const fn mix(under: u8, over: u8, alpha: u8) -> u8 {
+            let mixed = over as u32 * alpha as u32 + under as u32 * (255 - alpha as u32) + 128;
+            ((mixed + (mixed >> 8)) >> 8) as u8
+        }
+
+        Color::rgb(
+            mix(self.red(), over.red(), alpha),
+            mix(self.green(), over.green(), alpha),
+            mix(self.blue(), over.blue(), alpha)
+        )
+    }
+}
+
+/// A colour with how opaque it is, for drawing things (like images with transparent parts) on top
+/// of what's already there. Only CPUFrameBuffer's blended draw functions use the alpha, the buffer
+/// itself is always opaque
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct AlphaColor {
+    pub color: Color,
+    /// 0 is fully transparent, 255 is fully opaque
+    pub alpha: u8
+}
+
+impl AlphaColor {
+    pub const TRANSPARENT: AlphaColor = AlphaColor::new(Color::BLACK, 0);
+
+    pub const fn new(color: Color, alpha: u8) -> Self {
+        AlphaColor { color, alpha }
+    }
+
+    pub const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        AlphaColor::new(Color::rgb(red, green, blue), alpha)
+    }
+}
+
+impl From<Color> for AlphaColor {
+    fn from(color: Color) -> Self {
+        AlphaColor::new(color, 0xff)
+    }
+}
+
+/// Where one colour channel sits in a pixel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Channel {
+    /// How far up from the least significant bit the channel starts
+    pub shift: u8,
+    /// How many bits it has
+    pub size: u8
+}
+
+impl Channel {
+    /// Works out the channel from a mask with all of its bits set, like UEFI gives. The mask has
+    /// to be one run of bits
+    pub const fn from_mask(mask: u32) -> Self {
+        Channel {
+            shift: if mask == 0 { 0 } else { mask.trailing_zeros() as u8 },
+            size: mask.count_ones() as u8
+        }
+    }
+
+    /// Scales an 8 bit value to the size of the channel and moves it into place
+    const fn encode(self, value: u8) -> u32 {
+        // Firmware can describe a channel with an empty mask, which just can't show that colour
+        if self.size == 0 {
+            return 0
+        }
+
+        let scaled = if self.size >= 8 {
+            (value as u32) << (self.size - 8)
+        } else {
+            (value >> (8 - self.size)) as u32
+        };
+
+        scaled << self.shift
+    }
+}
+
+/// How the hardware framebuffer lays out a pixel. Pixels are always little endian, so the first
+/// byte in memory is the least significant one
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelFormat {
+    /// 32 bits per pixel with red in the first byte, then green, then blue, then a reserved byte
+    /// (PixelRedGreenBlueReserved8BitPerColor in UEFI)
+    Rgb,
+    /// 32 bits per pixel with blue in the first byte, then green, then red, then a reserved byte
+    /// (PixelBlueGreenRedReserved8BitPerColor in UEFI). This is what most hardware uses
+    Bgr,
+    /// Anything else, which is how UEFI's PixelBitMask and every mode Limine reports are described
+    Bitmask {
+        /// One of 16, 24 or 32
+        bits_per_pixel: u8,
+        red: Channel,
+        green: Channel,
+        blue: Channel
+    }
+}
+
+impl PixelFormat {
+    pub fn bits_per_pixel(&self) -> u8 {
+        match self {
+            PixelFormat::Rgb | PixelFormat::Bgr => 32,
+            PixelFormat::Bitmask { bits_per_pixel, .. } => *bits_per_pixel
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        (self.bits_per_pixel() as usize).div_ceil(8)
+    }
+
+    /// Turns a colour into a pixel in this format. Only the bottom `bytes_per_pixel` bytes of the
+    /// result mean anything
+    pub fn encode(&self, color: Color) -> u32 {
+        match self {
+            PixelFormat::Rgb => color.red() as u32 | (color.green() as u32) << 8 | (color.blue() as u32) << 16,
+            PixelFormat::Bgr => color.hex(),
+            PixelFormat::Bitmask { red, green, blue, .. } => red.encode(color.red())
+                | green.encode(color.green())
+                | blue.encode(color.blue())
+        }
+    }
+
+    /// Whether this is the same layout as Color itself (32 bits, blue in the first byte), so colours
+    /// can be copied in without converting them
+    pub fn matches_color(&self) -> bool {
+        match self {
+            PixelFormat::Bgr => true,
+            PixelFormat::Rgb => false,
+            PixelFormat::Bitmask { bits_per_pixel, red, green, blue } => *bits_per_pixel == 32
+                && *red == Channel { shift: 16, size: 8 }
+                && *green == Channel { shift: 8, size: 8 }
+                && *blue == Channel { shift: 0, size: 8 }
+        }
+    }
+
+    /// Converts a row of colours to pixels in this format. `pixels` has to be exactly
+    /// `bytes_per_pixel` times longer than `colors`
+    pub fn encode_row(&self, colors: &[Color], pixels: &mut [u8]) {
+        let bytes_per_pixel = self.bytes_per_pixel();
+
+        for (color, pixel) in colors.iter().zip(pixels.chunks_exact_mut(bytes_per_pixel)) {
+            pixel.copy_from_slice(&self.encode(*color).to_le_bytes()[..bytes_per_pixel]);
+        }
+    }
+
+    /// Writes a colour as a pixel in this format to `pixel`
+    ///
+    /// # Safety
+    /// `pixel` has to be valid for writing `bytes_per_pixel` bytes. It doesn't need to be aligned
+    pub unsafe fn write(&self, pixel: *mut u8, color: Color) {
+        let encoded = self.encode(color);
+
+        // The framebuffer is memory mapped IO so everything is written volatile. 32 bit pixels
+        // are almost always aligned so they can go in one write
+        if self.bytes_per_pixel() == 4 && (pixel as usize).is_multiple_of(4) {
+            unsafe { (pixel as *mut u32).write_volatile(encoded) }
+            return
+        }
+
+        for (offset, byte) in encoded.to_le_bytes().iter().take(self.bytes_per_pixel()).enumerate() {
+            unsafe { pixel.add(offset).write_volatile(*byte) }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! A parser for PC Screen Font files (PSF1 and PSF2), the format the Linux console uses. They're
+//! binary and already laid out the same way a Glyph is, so loading one is mostly copying, which
+//! makes them a good fit for embedding in the kernel
+
+use alloc::vec::Vec;
+
+use crate::{FontError, Glyph, MonoFont};
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+/// The font has 512 glyphs rather than 256
+const PSF1_MODE_512: u8 = 0x01;
+/// There's a unicode table after the glyphs
+const PSF1_MODE_HAS_TABLE: u8 = 0x02;
+/// Same as HAS_TABLE, but the table might have sequences in it too
+const PSF1_MODE_HAS_SEQUENCES: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xffff;
+const PSF1_START_SEQUENCE: u16 = 0xfffe;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_START_SEQUENCE: u8 = 0xfe;
+
+/// Parses a PSF1 or PSF2 font, working out which from the magic number
+///
+/// With a unicode table each glyph goes under every character the table lists for it, and if it
+/// lists U+FFFD that becomes the replacement glyph. Sequences (several characters that combine into
+/// one glyph) are skipped since a cell only holds one char. Without a table, glyph n is just put
+/// at character n
+pub fn parse(bytes: &[u8]) -> Result<MonoFont, FontError> {
+    if bytes.starts_with(&PSF2_MAGIC) {
+        parse_psf2(bytes)
+    } else if bytes.starts_with(&PSF1_MAGIC) {
+        parse_psf1(bytes)
+    } else {
+        Err(FontError::WrongFormat)
+    }
+}
+
+fn parse_psf1(bytes: &[u8]) -> Result<MonoFont, FontError> {
+    let mode = *bytes.get(2).ok_or(FontError::Truncated)?;
+    let height = *bytes.get(3).ok_or(FontError::Truncated)? as usize;
+
+    let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+
+    // PSF1 glyphs are always 8 pixels wide, so one byte per row
+    let glyphs = split_glyphs(&bytes[4..], count, 8, height)?;
+    let table = &bytes[4 + count * height..];
+
+    let mut font = MonoFont::new(8, height);
+
+    if mode & (PSF1_MODE_HAS_TABLE | PSF1_MODE_HAS_SEQUENCES) == 0 {
+        return place_by_index(font, glyphs)
+    }
+
+    let mut entries = table.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+
+    for glyph in glyphs {
+        let mut in_sequence = false;
+
+        loop {
+            match entries.next().ok_or(FontError::Truncated)? {
+                PSF1_SEPARATOR => break,
+                PSF1_START_SEQUENCE => in_sequence = true,
+                _ if in_sequence => {},
+                // Anything that isn't a valid char (a lone surrogate) is skipped
+                entry => if let Some(character) = char::from_u32(entry as u32) {
+                    add(&mut font, character, &glyph)?
+                }
+            }
+        }
+    }
+
+    Ok(font)
+}
+
+fn parse_psf2(bytes: &[u8]) -> Result<MonoFont, FontError> {
+    let field = |index: usize| bytes.get(index * 4..index * 4 + 4)
+        .map(|field| u32::from_le_bytes([field[0], field[1], field[2], field[3]]) as usize)
+        .ok_or(FontError::Truncated);
+
+    let header_size = field(2)?;
+    let flags = field(3)? as u32;
+    let count = field(4)?;
+    let glyph_size = field(5)?;
+    let height = field(6)?;
+    let width = field(7)?;
+
+    if width.div_ceil(8).checked_mul(height) != Some(glyph_size) {
+        return Err(FontError::WrongGlyphSize)
+    }
+
+    let glyph_bytes = bytes.get(header_size..).ok_or(FontError::Truncated)?;
+    let glyphs = split_glyphs(glyph_bytes, count, width, height)?;
+    let mut table = &glyph_bytes[count * glyph_size..];
+
+    let mut font = MonoFont::new(width, height);
+
+    if flags & PSF2_HAS_UNICODE_TABLE == 0 {
+        return place_by_index(font, glyphs)
+    }
+
+    for glyph in glyphs {
+        let end = table.iter().position(|byte| *byte == PSF2_SEPARATOR)
+            .ok_or(FontError::Truncated)?;
+
+        // Everything before the first sequence is single characters in UTF-8
+        let singles = table[..end].split(|byte| *byte == PSF2_START_SEQUENCE).next().unwrap_or(&[]);
+        let singles = core::str::from_utf8(singles).or(Err(FontError::InvalidUnicodeTable))?;
+
+        for character in singles.chars() {
+            add(&mut font, character, &glyph)?;
+        }
+
+        table = &table[end + 1..];
+    }
+
+    Ok(font)
+}
+
+/// Cuts the glyph data up into `count` glyphs
+fn split_glyphs(bytes: &[u8], count: usize, width: usize, height: usize) -> Result<Vec<Glyph>, FontError> {
+    let glyph_size = width.div_ceil(8) * height;
+
+    if glyph_size == 0 {
+        return Err(FontError::WrongGlyphSize)
+    }
+
+    // The sizes come straight from the header, so they can't be trusted not to overflow
+    if count.checked_mul(glyph_size).is_none_or(|size| bytes.len() < size) {
+        return Err(FontError::Truncated)
+    }
+
+    bytes.chunks_exact(glyph_size)
+        .take(count)
+        .map(|bits| Glyph::new(width, height, bits.into()))
+        .collect()
+}
+
+/// For fonts without a unicode table
+fn place_by_index(mut font: MonoFont, glyphs: Vec<Glyph>) -> Result<MonoFont, FontError> {
+    for (index, glyph) in glyphs.into_iter().enumerate() {
+        if let Some(character) = char::from_u32(index as u32) {
+            font.insert(character, glyph)?;
+        }
+    }
+
+    Ok(font)
+}
+
+fn add(font: &mut MonoFont, character: char, glyph: &Glyph) -> Result<(), FontError> {
+    if character == char::REPLACEMENT_CHARACTER {
+        font.set_replacement(glyph.clone())?;
+    }
+
+    font.insert(character, glyph.clone())?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! A decoder for QOI ("Quite OK Image") files. It's lossless and compresses nearly as well as PNG
+//! but the whole format fits on a page, which makes it the best choice for anything big enough
+//! (like a full screen splash) that a BMP would be a waste of space on the EFI partition
+//!
+//! See https://qoiformat.org/qoi-specification.pdf
+
+use alloc::vec::Vec;
+
+use crate::image::check_size;
+use crate::{AlphaColor, Image, ImageError};
+
+pub(crate) const MAGIC: &[u8] = b"qoif";
+
+const HEADER_SIZE: usize = 14;
+/// Every file ends with seven 0x00 bytes and then a 0x01
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+/// The rest of the ops only use the top 2 bits for the tag
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const TAG_MASK: u8 = 0xc0;
+
+/// Decodes a QOI file
+pub fn parse(bytes: &[u8]) -> Result<Image, ImageError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(ImageError::WrongFormat)
+    }
+
+    let header = bytes.get(..HEADER_SIZE).ok_or(ImageError::Truncated)?;
+
+    // Unlike the rest of the formats here, QOI is big endian
+    let width = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let height = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+    let channels = header[12];
+    let colorspace = header[13];
+
+    // The channels and colourspace don't change how anything is decoded (RGB images just never
+    // change the alpha) but anything else there means it isn't really a QOI file
+    if !matches!(channels, 3 | 4) || colorspace > 1 {
+        return Err(ImageError::Corrupt)
+    }
+
+    let size = check_size(width, height)?;
+
+    let data = &bytes[HEADER_SIZE..];
+    let data = data.strip_suffix(&END_MARKER).ok_or(ImageError::Truncated)?;
+
+    let mut pixels = Vec::with_capacity(size);
+    // Every pixel that has been seen, hashed into 64 slots
+    let mut seen = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 0xff];
+    let mut position = 0;
+
+    let mut next = || {
+        let byte = data.get(position).copied().ok_or(ImageError::Truncated);
+        position += 1;
+        byte
+    };
+
+    while pixels.len() < size {
+        let op = next()?;
+
+        match op {
+            OP_RGB => {
+                pixel[0] = next()?;
+                pixel[1] = next()?;
+                pixel[2] = next()?;
+            },
+            OP_RGBA => {
+                pixel = [next()?, next()?, next()?, next()?];
+            },
+            _ => match op & TAG_MASK {
+                OP_INDEX => pixel = seen[op as usize],
+                OP_DIFF => {
+                    // Each channel's difference from the last pixel, -2 to 1, biased by 2
+                    pixel[0] = pixel[0].wrapping_add((op >> 4 & 0x03).wrapping_sub(2));
+                    pixel[1] = pixel[1].wrapping_add((op >> 2 & 0x03).wrapping_sub(2));
+                    pixel[2] = pixel[2].wrapping_add((op & 0x03).wrapping_sub(2));
+                },
+                OP_LUMA => {
+                    // Green's difference, and then red's and blue's relative to green's
+                    let green = (op & 0x3f).wrapping_sub(32);
+                    let second = next()?;
+
+                    pixel[0] = pixel[0].wrapping_add(green.wrapping_add(second >> 4).wrapping_sub(8));
+                    pixel[1] = pixel[1].wrapping_add(green);
+                    pixel[2] = pixel[2].wrapping_add(green.wrapping_add(second & 0x0f).wrapping_sub(8));
+                },
+                _ => {
+                    // The only tag left (0xc0) is a run, which repeats the last pixel 1 to 62 times,
+                    // biased by 1
+                    let run = (op & 0x3f) as usize + 1;
+
+                    if pixels.len() + run > size {
+                        return Err(ImageError::Corrupt)
+                    }
+
+                    let color = AlphaColor::rgba(pixel[0], pixel[1], pixel[2], pixel[3]);
+                    pixels.extend(core::iter::repeat_n(color, run));
+                    continue
+                }
+            }
+        }
+
+        seen[hash(pixel)] = pixel;
+        pixels.push(AlphaColor::rgba(pixel[0], pixel[1], pixel[2], pixel[3]));
+    }
+
+    Image::new(width, height, pixels.into_boxed_slice())
+}
+
+/// Which of the 64 slots a pixel goes in
+fn hash(pixel: [u8; 4]) -> usize {
+    let [red, green, blue, alpha] = pixel.map(|channel| channel as usize);
+    (red * 3 + green * 5 + blue * 7 + alpha * 11) % 64
+}
@@ -0,0 +1,69 @@
+/// A rectangle of pixels. (x, y) is the top left corner and nothing in here is ever negative
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    /// One past the last column
+    pub const fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    /// One past the last row
+    pub const fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Whether the two overlap or sit right next to each other, which is when merging them into
+    /// one doesn't cover anything that neither of them did (or not much, anyway)
+    pub fn touches(&self, other: &Rect) -> bool {
+        self.x <= other.right() && other.x <= self.right()
+            && self.y <= other.bottom() && other.y <= self.bottom()
+    }
+
+    /// The smallest rectangle covering both
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other
+        }
+
+        if other.is_empty() {
+            return *self
+        }
+
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+
+        Rect {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y
+        }
+    }
+
+    /// The part of this that is inside a `width` by `height` area at (0, 0)
+    pub fn clamp(&self, width: usize, height: usize) -> Rect {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+
+        Rect {
+            x,
+            y,
+            width: self.right().min(width) - x,
+            height: self.bottom().min(height) - y
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Dumping a CPUFrameBuffer as an image file, for bug reports and for tests to compare what got
+//! drawn against a known good image. It's the CPUFrameBuffer rather than the hardware framebuffer
+//! that gets saved since that's always in the same format, and reading back from VRAM is slow
+//!
+//! Both formats get written a row at a time to a function rather than all into one Vec, so a
+//! screenshot can be sent over serial without needing a few megabytes of heap for it
+
+use alloc::vec::Vec;
+
+use crate::{Color, CPUFrameBuffer};
+
+const BMP_FILE_HEADER_SIZE: usize = 14;
+const BMP_INFO_HEADER_SIZE: usize = 40;
+
+impl CPUFrameBuffer {
+    /// Writes the buffer as a binary PPM (P6), which is about as simple as an image file can be:
+    /// a short text header and then every pixel as red, green and blue bytes
+    ///
+    /// # Arguments
+    /// * `out` - Called with each part of the file in order
+    pub fn write_ppm(&self, mut out: impl FnMut(&[u8])) {
+        out(alloc::format!("P6\n{} {}\n255\n", self.width, self.height).as_bytes());
+
+        let mut row = Vec::with_capacity(self.width * 3);
+
+        for y in 0..self.height {
+            row.clear();
+
+            for color in self.row(y) {
+                row.extend_from_slice(&[color.red(), color.green(), color.blue()]);
+            }
+
+            out(&row);
+        }
+    }
+
+    /// Writes the buffer as a 24 bit BMP, for anything that can't open a PPM
+    ///
+    /// # Arguments
+    /// * `out` - Called with each part of the file in order
+    pub fn write_bmp(&self, mut out: impl FnMut(&[u8])) {
+        // Rows are padded out to a multiple of 4 bytes
+        let stride = (self.width * 3).next_multiple_of(4);
+        let data_offset = BMP_FILE_HEADER_SIZE + BMP_INFO_HEADER_SIZE;
+        let data_size = stride * self.height;
+
+        let mut header = Vec::with_capacity(data_offset);
+        header.extend_from_slice(b"BM");
+        header.extend_from_slice(&((data_offset + data_size) as u32).to_le_bytes());
+        // Two reserved u16s
+        header.extend_from_slice(&[0; 4]);
+        header.extend_from_slice(&(data_offset as u32).to_le_bytes());
+
+        header.extend_from_slice(&(BMP_INFO_HEADER_SIZE as u32).to_le_bytes());
+        header.extend_from_slice(&(self.width as i32).to_le_bytes());
+        // Negative so the rows can go top down, the same order as they're in here
+        header.extend_from_slice(&(-(self.height as i32)).to_le_bytes());
+        // Planes, then bits per pixel
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&24u16.to_le_bytes());
+        // BI_RGB (no compression), the size of the pixel data, then the resolution and palette
+        // sizes, which can all be 0
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&(data_size as u32).to_le_bytes());
+        header.extend_from_slice(&[0; 16]);
+
+        out(&header);
+
+        let mut row = Vec::with_capacity(stride);
+
+        for y in 0..self.height {
+            row.clear();
+
+            for color in self.row(y) {
+                row.extend_from_slice(&[color.blue(), color.green(), color.red()]);
+            }
+
+            row.resize(stride, 0);
+            out(&row);
+        }
+    }
+
+    /// The whole buffer as a PPM file, see `write_ppm`
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut file = Vec::new();
+        self.write_ppm(|bytes| file.extend_from_slice(bytes));
+        file
+    }
+
+    /// The whole buffer as a BMP file, see `write_bmp`
+    pub fn to_bmp(&self) -> Vec<u8> {
+        let mut file = Vec::new();
+        self.write_bmp(|bytes| file.extend_from_slice(bytes));
+        file
+    }
+
+    fn row(&self, y: usize) -> &[Color] {
+        &self.buffer[y * self.stride..][..self.width]
+    }
+}
@@ -0,0 +1,62 @@
+use alloc::boxed::Box;
+use alloc::vec;
+
+use crate::Style;
+
+/// The lines that have scrolled off the top of a TextBuffer, oldest first. It's a ring of
+/// `capacity` lines laid out the same way as the TextBuffer's own cells, so adding a line is just
+/// a copy and once it's full the oldest line gets written over
+pub(crate) struct Scrollback {
+    width: usize,
+    capacity: usize,
+    text: Box<[char]>,
+    styles: Box<[Style]>,
+    /// The line (not cell) the oldest line is in
+    start: usize,
+    len: usize
+}
+
+impl Scrollback {
+    pub fn new(width: usize, capacity: usize) -> Self {
+        Scrollback {
+            width,
+            capacity,
+            text: vec![' '; width * capacity].into_boxed_slice(),
+            styles: vec![Style::DEFAULT; width * capacity].into_boxed_slice(),
+            start: 0,
+            len: 0
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Adds a line after the newest one, dropping the oldest if it's full. Gives back whether one
+    /// was dropped
+    pub fn push(&mut self, text: &[char], styles: &[Style]) -> bool {
+        if self.capacity == 0 {
+            return false
+        }
+
+        let full = self.len == self.capacity;
+        let line = (self.start + self.len) % self.capacity * self.width;
+
+        self.text[line..line + self.width].copy_from_slice(text);
+        self.styles[line..line + self.width].copy_from_slice(styles);
+
+        if full {
+            self.start = (self.start + 1) % self.capacity;
+        } else {
+            self.len += 1;
+        }
+
+        full
+    }
+
+    /// The `index`th line, counting from the oldest
+    pub fn line(&self, index: usize) -> (&[char], &[Style]) {
+        let line = (self.start + index) % self.capacity * self.width;
+        (&self.text[line..line + self.width], &self.styles[line..line + self.width])
+    }
+}
@@ -0,0 +1,192 @@
+use crate::PixelFormat;
+
+/// A hardware framebuffer that a CPUFrameBuffer can be flushed to. This is what keeps the rest of
+/// the crate from caring whether it's running in the bootloader (with a UEFI GOP framebuffer) or
+/// the kernel (with the one Limine hands over)
+///
+/// # Safety
+/// `address` has to be valid for writing `pitch * height` bytes for as long as the implementor
+/// exists, and `width * bytes per pixel` can't be more than `pitch`
+pub unsafe trait FramebufferTarget {
+    /// The first byte of the top left pixel
+    fn address(&self) -> *mut u8;
+
+    /// Width and height are in pixels
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// The number of bytes from the start of one row to the start of the next
+    fn pitch(&self) -> usize;
+
+    fn format(&self) -> PixelFormat;
+
+    fn bits_per_pixel(&self) -> u8 {
+        self.format().bits_per_pixel()
+    }
+}
+
+#[cfg(feature = "uefi")]
+pub use gop::GopFramebuffer;
+
+#[cfg(feature = "uefi")]
+mod gop {
+    use core::marker::PhantomData;
+
+    use uefi::proto::console::gop::{GraphicsOutput, PixelFormat as GopPixelFormat};
+
+    use super::FramebufferTarget;
+    use crate::{Channel, GraphicsError, PixelFormat};
+
+    /// The framebuffer of the GOP's current mode
+    pub struct GopFramebuffer<'gop> {
+        address: *mut u8,
+        width: usize,
+        height: usize,
+        /// In pixels, which is how UEFI gives it
+        stride: usize,
+        format: PixelFormat,
+        _gop: PhantomData<&'gop mut GraphicsOutput>
+    }
+
+    impl<'gop> GopFramebuffer<'gop> {
+        /// Fails if the current mode has no framebuffer (PixelFormat::BltOnly)
+        pub fn new(gop: &'gop mut GraphicsOutput) -> Result<Self, GraphicsError> {
+            let info = gop.current_mode_info();
+
+            let format = match info.pixel_format() {
+                GopPixelFormat::Rgb => PixelFormat::Rgb,
+                GopPixelFormat::Bgr => PixelFormat::Bgr,
+                GopPixelFormat::Bitmask => {
+                    let bitmask = info.pixel_bitmask().ok_or(GraphicsError::NoFramebuffer)?;
+                    PixelFormat::Bitmask {
+                        bits_per_pixel: 32,
+                        red: Channel::from_mask(bitmask.red),
+                        green: Channel::from_mask(bitmask.green),
+                        blue: Channel::from_mask(bitmask.blue)
+                    }
+                },
+                GopPixelFormat::BltOnly => return Err(GraphicsError::NoFramebuffer)
+            };
+
+            let (width, height) = info.resolution();
+
+            Ok(GopFramebuffer {
+                address: gop.frame_buffer().as_mut_ptr(),
+                width,
+                height,
+                stride: info.stride(),
+                format,
+                _gop: PhantomData
+            })
+        }
+    }
+
+    // The GOP is borrowed for as long as this exists so the mode (and therefore the framebuffer)
+    // can't change underneath it
+    unsafe impl FramebufferTarget for GopFramebuffer<'_> {
+        fn address(&self) -> *mut u8 {
+            self.address
+        }
+
+        fn width(&self) -> usize {
+            self.width
+        }
+
+        fn height(&self) -> usize {
+            self.height
+        }
+
+        fn pitch(&self) -> usize {
+            self.stride * self.format.bytes_per_pixel()
+        }
+
+        fn format(&self) -> PixelFormat {
+            self.format
+        }
+    }
+}
+
+// Limine only has the one memory model (RGB with masks) and describes it the same way for 16, 24
+// and 32 bits per pixel
+#[cfg(feature = "limine")]
+unsafe impl FramebufferTarget for limine::framebuffer::Framebuffer<'_> {
+    fn address(&self) -> *mut u8 {
+        self.addr()
+    }
+
+    fn width(&self) -> usize {
+        limine::framebuffer::Framebuffer::width(self) as usize
+    }
+
+    fn height(&self) -> usize {
+        limine::framebuffer::Framebuffer::height(self) as usize
+    }
+
+    fn pitch(&self) -> usize {
+        limine::framebuffer::Framebuffer::pitch(self) as usize
+    }
+
+    fn format(&self) -> PixelFormat {
+        PixelFormat::Bitmask {
+            bits_per_pixel: self.bpp() as u8,
+            red: crate::Channel { shift: self.red_mask_shift(), size: self.red_mask_size() },
+            green: crate::Channel { shift: self.green_mask_shift(), size: self.green_mask_size() },
+            blue: crate::Channel { shift: self.blue_mask_shift(), size: self.blue_mask_size() }
+        }
+    }
+}
+
+/// A framebuffer described by nothing but where it is and how it's laid out, without the borrow
+/// of whatever it came from. This is what lets a `Console` be kept in a static (for the logger)
+/// and keep drawing after the thing that handed the framebuffer over is gone, like the GOP after
+/// exiting boot services
+#[derive(Clone, Copy, Debug)]
+pub struct RawFramebuffer {
+    address: *mut u8,
+    width: usize,
+    height: usize,
+    pitch: usize,
+    format: PixelFormat
+}
+
+impl RawFramebuffer {
+    /// # Safety
+    /// The target's memory has to stay valid for as long as this (or any copy of it) exists, and
+    /// nothing else can draw to it in the meantime. The GOP framebuffer stays where it is after
+    /// exiting boot services, and Limine's never moves
+    pub unsafe fn from_target(target: &impl FramebufferTarget) -> Self {
+        RawFramebuffer {
+            address: target.address(),
+            width: target.width(),
+            height: target.height(),
+            pitch: target.pitch(),
+            format: target.format()
+        }
+    }
+}
+
+// It's just memory, so which CPU writes to it doesn't matter. from_target's contract covers the
+// rest
+unsafe impl Send for RawFramebuffer {}
+
+unsafe impl FramebufferTarget for RawFramebuffer {
+    fn address(&self) -> *mut u8 {
+        self.address
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pitch(&self) -> usize {
+        self.pitch
+    }
+
+    fn format(&self) -> PixelFormat {
+        self.format
+    }
+}
@@ -0,0 +1,135 @@
+//! A decoder for Truevision TGA images. Uncompressed true colour (15, 16, 24 and 32 bit), grey
+//! and colour mapped images are supported, stored in any corner. TGA is about as simple as an
+//! image format gets, so it's an easy thing to have GIMP export a logo as
+//!
+//! TGA doesn't have a magic number, so this is also what `Image::decode` falls back on and it has
+//! to be careful with headers that are really something else
+
+use alloc::vec::Vec;
+
+use crate::image::{check_size, expand_bits, read_u16};
+use crate::{AlphaColor, Color, Image, ImageError};
+
+const HEADER_SIZE: usize = 18;
+
+const COLOR_MAPPED: u8 = 1;
+const TRUE_COLOR: u8 = 2;
+const GREY: u8 = 3;
+/// The run length encoded versions of the three above
+const RLE_COLOR_MAPPED: u8 = 9;
+const RLE_TRUE_COLOR: u8 = 10;
+const RLE_GREY: u8 = 11;
+
+/// Bits of the image descriptor byte
+const DESCRIPTOR_ALPHA_BITS: u8 = 0x0f;
+const DESCRIPTOR_RIGHT_TO_LEFT: u8 = 0x10;
+const DESCRIPTOR_TOP_TO_BOTTOM: u8 = 0x20;
+
+/// Decodes a TGA file
+pub fn parse(bytes: &[u8]) -> Result<Image, ImageError> {
+    let header = bytes.get(..HEADER_SIZE).ok_or(ImageError::Truncated)?;
+
+    let id_length = header[0] as usize;
+    let has_color_map = header[1];
+    let image_type = header[2];
+    let map_first = read_u16(header, 3)? as usize;
+    let map_length = read_u16(header, 5)? as usize;
+    let map_entry_bits = header[7];
+    let width = read_u16(header, 12)? as usize;
+    let height = read_u16(header, 14)? as usize;
+    let bits_per_pixel = header[16];
+    let descriptor = header[17];
+
+    // Without a magic number these checks are all there is to tell a TGA from something random
+    if has_color_map > 1 {
+        return Err(ImageError::WrongFormat)
+    }
+
+    match image_type {
+        COLOR_MAPPED | TRUE_COLOR | GREY => {},
+        RLE_COLOR_MAPPED | RLE_TRUE_COLOR | RLE_GREY => return Err(ImageError::Unsupported),
+        _ => return Err(ImageError::WrongFormat)
+    }
+
+    if (image_type == COLOR_MAPPED) != (has_color_map == 1) {
+        return Err(ImageError::Corrupt)
+    }
+
+    let size = check_size(width, height)?;
+
+    let mut offset = HEADER_SIZE + id_length;
+
+    // A grey or true colour image can still have a map in it, which just gets skipped
+    let map_entry_bytes = (map_entry_bits as usize).div_ceil(8);
+    let map = bytes.get(offset..offset + map_length * map_entry_bytes).ok_or(ImageError::Truncated)?;
+    offset += map.len();
+
+    let map = if image_type == COLOR_MAPPED {
+        map.chunks_exact(map_entry_bytes.max(1))
+            .map(|entry| decode_color(entry, map_entry_bits, 0))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+
+    let alpha_bits = descriptor & DESCRIPTOR_ALPHA_BITS;
+    let bytes_per_pixel = (bits_per_pixel as usize).div_ceil(8);
+
+    match (image_type, bits_per_pixel) {
+        (COLOR_MAPPED, 8 | 16) | (TRUE_COLOR, 15 | 16 | 24 | 32) | (GREY, 8 | 16) => {},
+        _ => return Err(ImageError::Unsupported)
+    }
+
+    let data = bytes.get(offset..offset + size * bytes_per_pixel).ok_or(ImageError::Truncated)?;
+
+    let mut pixels = alloc::vec![AlphaColor::TRANSPARENT; size];
+
+    for (index, pixel) in data.chunks_exact(bytes_per_pixel).enumerate() {
+        let color = match image_type {
+            COLOR_MAPPED => {
+                let index = if bytes_per_pixel == 1 { pixel[0] as usize } else { read_u16(pixel, 0)? as usize };
+
+                *index.checked_sub(map_first)
+                    .and_then(|index| map.get(index))
+                    .ok_or(ImageError::Corrupt)?
+            },
+            // A 16 bit grey pixel is the grey and then an alpha byte
+            GREY => AlphaColor::rgba(pixel[0], pixel[0], pixel[0], pixel.get(1).copied().unwrap_or(0xff)),
+            _ => decode_color(pixel, bits_per_pixel, alpha_bits)?
+        };
+
+        // The first pixel is the bottom left unless the descriptor says otherwise
+        let (x, y) = (index % width, index / width);
+        let x = if descriptor & DESCRIPTOR_RIGHT_TO_LEFT != 0 { width - 1 - x } else { x };
+        let y = if descriptor & DESCRIPTOR_TOP_TO_BOTTOM != 0 { y } else { height - 1 - y };
+
+        pixels[y * width + x] = color;
+    }
+
+    Image::new(width, height, pixels.into_boxed_slice())
+}
+
+/// Decodes a true colour pixel (or colour map entry), which is blue first. 15 and 16 bit ones are
+/// 5 bits per channel with the top bit as alpha, if it's used
+fn decode_color(pixel: &[u8], bits: u8, alpha_bits: u8) -> Result<AlphaColor, ImageError> {
+    match bits {
+        15 | 16 => {
+            let value = read_u16(pixel, 0)? as u32;
+            let alpha = if bits == 16 && alpha_bits == 1 && value & 0x8000 == 0 { 0 } else { 0xff };
+
+            Ok(AlphaColor::new(
+                Color::rgb(
+                    expand_bits(value >> 10 & 0x1f, 5),
+                    expand_bits(value >> 5 & 0x1f, 5),
+                    expand_bits(value & 0x1f, 5)
+                ),
+                alpha
+            ))
+        },
+        24 => Ok(AlphaColor::rgba(pixel[2], pixel[1], pixel[0], 0xff)),
+        // Some encoders write 32 bit images without saying there are alpha bits, and then the
+        // fourth byte isn't meant to be alpha at all
+        32 => Ok(AlphaColor::rgba(pixel[2], pixel[1], pixel[0], if alpha_bits == 0 { 0xff } else { pixel[3] })),
+        _ => Err(ImageError::Unsupported)
+    }
+}
@@ -0,0 +1,205 @@
+//! Parsing small hand written BDF fonts, checking where each glyph ends up in its cell and which
+//! line errors point at
+
+use graphics::bdf::{self, BdfFont};
+use graphics::{FontError, Glyph, ParseErrorKind};
+
+/// An 8x8 cell with the baseline 6 rows down, so there are 2 rows for descenders
+const HEADER: &str = "STARTFONT 2.1
+FONT -test-fixture
+SIZE 8 75 75
+FONTBOUNDINGBOX 8 8 0 -2
+STARTPROPERTIES 3
+FONT_ASCENT 6
+FONT_DESCENT 2
+DEFAULT_CHAR 63
+ENDPROPERTIES
+CHARS 2
+";
+
+/// A 4x5 'g' that hangs 2 rows below the baseline and starts a column in
+const G: &str = "STARTCHAR g
+ENCODING 103
+SWIDTH 500 0
+DWIDTH 8 0
+BBX 4 5 1 -2
+BITMAP
+F0
+90
+F0
+10
+F0
+ENDCHAR
+";
+
+/// A full height '?', which is the DEFAULT_CHAR
+const QUESTION: &str = "STARTCHAR question
+ENCODING 63
+DWIDTH 8 0
+BBX 8 8 0 -2
+BITMAP
+3C
+42
+02
+0C
+10
+00
+10
+00
+ENDCHAR
+";
+
+fn font(glyphs: &[&str]) -> String {
+    format!("{HEADER}{}ENDFONT\n", glyphs.concat())
+}
+
+fn parse(glyphs: &[&str]) -> Result<BdfFont, FontError> {
+    bdf::parse(&font(glyphs))
+}
+
+/// A glyph as rows of '#' and '.'
+fn rows(glyph: &Glyph) -> Vec<String> {
+    (0..glyph.height())
+        .map(|y| (0..glyph.width()).map(|x| if glyph.is_set(x, y) { '#' } else { '.' }).collect())
+        .collect()
+}
+
+/// The line of the file that starts with `prefix`, counting from 1
+fn line_of(source: &str, prefix: &str) -> usize {
+    source.lines().position(|line| line.starts_with(prefix)).unwrap() + 1
+}
+
+#[test]
+fn header_gives_the_cell_and_baseline() {
+    let bdf = parse(&[G, QUESTION]).unwrap();
+
+    assert_eq!((bdf.font.width, bdf.font.height), (8, 8));
+    assert_eq!((bdf.ascent, bdf.descent), (6, 2));
+    assert_eq!(bdf.default_char, Some('?'));
+    assert_eq!(bdf.font.characters().collect::<String>(), "?g");
+}
+
+#[test]
+fn glyph_with_a_negative_offset_hangs_below_the_baseline() {
+    let bdf = parse(&[G]).unwrap();
+
+    assert_eq!(rows(bdf.font.get('g').unwrap()), [
+        "........",
+        "........",
+        "........",
+        ".####...",
+        ".#..#...",
+        ".####...",
+        "....#...",
+        ".####..."
+    ]);
+}
+
+#[test]
+fn glyph_left_of_the_origin_is_cut_off_at_the_cell() {
+    let glyph = G.replace("BBX 4 5 1 -2", "BBX 4 5 -2 0");
+    let bdf = parse(&[&glyph]).unwrap();
+
+    // Two columns are left of the cell, and the bottom is on the baseline
+    assert_eq!(rows(bdf.font.get('g').unwrap())[1..6], [
+        "##......",
+        ".#......",
+        "##......",
+        ".#......",
+        "##......"
+    ]);
+}
+
+#[test]
+fn default_char_becomes_the_replacement() {
+    let bdf = parse(&[G, QUESTION]).unwrap();
+
+    assert_eq!(bdf.font.replacement(), bdf.font.get('?').unwrap());
+    assert_eq!(bdf.font.glyph('x'), bdf.font.get('?').unwrap());
+}
+
+#[test]
+fn glyph_without_an_encoding_is_skipped() {
+    let unencoded = QUESTION.replace("ENCODING 63\n", "");
+    let bdf = parse(&[G, &unencoded]).unwrap();
+
+    assert_eq!(bdf.font.characters().collect::<String>(), "g");
+
+    // And with nothing else, there's no font at all
+    assert!(matches!(parse(&[&unencoded]), Err(FontError::Empty)));
+}
+
+#[test]
+fn encoding_without_a_number_is_an_error() {
+    let glyph = G.replace("ENCODING 103", "ENCODING");
+    let source = font(&[&glyph]);
+
+    assert!(matches!(
+        bdf::parse(&source),
+        Err(FontError::Parse { line, kind: ParseErrorKind::ExpectedNumber }) if line == line_of(&source, "ENCODING")
+    ));
+}
+
+#[test]
+fn bitmap_row_that_isnt_hex_is_an_error() {
+    let glyph = G.replace("90\n", "9G\n");
+    let source = font(&[&glyph]);
+
+    assert!(matches!(
+        bdf::parse(&source),
+        Err(FontError::Parse { line, kind: ParseErrorKind::InvalidHex }) if line == line_of(&source, "9G")
+    ));
+}
+
+#[test]
+fn bitmap_row_shorter_than_the_bbx_is_an_error() {
+    let glyph = G.replace("BBX 4 5 1 -2", "BBX 12 5 1 -2");
+    let source = font(&[&glyph]);
+
+    assert!(matches!(
+        bdf::parse(&source),
+        Err(FontError::Parse { line, kind: ParseErrorKind::RowTooShort }) if line == line_of(&source, "BITMAP") + 1
+    ));
+}
+
+#[test]
+fn bitmap_with_more_rows_than_the_bbx_is_an_error() {
+    let glyph = G.replace("BBX 4 5 1 -2", "BBX 4 4 1 -2");
+    let source = font(&[&glyph]);
+
+    assert!(matches!(
+        bdf::parse(&source),
+        Err(FontError::Parse { line, kind: ParseErrorKind::MissingEndChar }) if line == line_of(&source, "BITMAP") + 5
+    ));
+}
+
+#[test]
+fn file_ending_inside_a_bitmap_is_an_error() {
+    let source = format!("{HEADER}STARTCHAR g\nENCODING 103\nBBX 4 5 1 -2\nBITMAP\nF0\n");
+
+    assert!(matches!(
+        bdf::parse(&source),
+        Err(FontError::Parse { kind: ParseErrorKind::UnexpectedEnd, .. })
+    ));
+}
+
+#[test]
+fn font_without_a_bounding_box_is_an_error() {
+    let source = font(&[G]).replace("FONTBOUNDINGBOX 8 8 0 -2\n", "");
+
+    assert!(matches!(
+        bdf::parse(&source),
+        Err(FontError::Parse { kind: ParseErrorKind::MissingBoundingBox, .. })
+    ));
+}
+
+#[test]
+fn glyph_of_another_width_is_an_error() {
+    let glyph = G.replace("DWIDTH 8 0", "DWIDTH 6 0");
+    let source = font(&[&glyph]);
+
+    assert!(matches!(
+        bdf::parse(&source),
+        Err(FontError::NotMonospaced { line }) if line == line_of(&source, "DWIDTH")
+    ));
+}
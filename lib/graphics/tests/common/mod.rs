@@ -0,0 +1,151 @@
+//! Renders a TextBuffer into an in-memory CPUFrameBuffer with spleen (the same font the kernel
+//! uses) so tests can check both what's in the buffer and what got drawn
+//!
+//! Pixels get checked two ways. Every render is compared against drawing the whole grid from
+//! scratch straight out of the font, which catches anything going wrong with the dirty tracking
+//! and scrolling. And a few screens are compared against snapshots in `snapshots/`, which catches
+//! the font or the glyph drawing itself changing. Run the tests with UPDATE_SNAPSHOTS=1 to write
+//! new snapshots, and look at them before committing them
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use graphics::{Color, CPUFrameBuffer, CursorShape, Font, MonoFont, TextBuffer};
+
+/// The gap between the edge of the framebuffer and the text
+pub const PADDING: (usize, usize) = (2, 3);
+
+pub fn spleen() -> &'static MonoFont {
+    static FONT: OnceLock<MonoFont> = OnceLock::new();
+
+    FONT.get_or_init(|| {
+        let source = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../../spleen/spleen-16x32.bdf"));
+        graphics::bdf::parse(source).expect("spleen should parse").font
+    })
+}
+
+pub struct Screen {
+    pub text: TextBuffer,
+    pub frame_buffer: CPUFrameBuffer
+}
+
+impl Screen {
+    /// A screen `columns` by `rows` characters, with the framebuffer as small as write_pixels
+    /// allows
+    pub fn new(columns: usize, rows: usize) -> Self {
+        let font = spleen();
+
+        Screen {
+            text: TextBuffer::new(rows, columns).unwrap(),
+            frame_buffer: CPUFrameBuffer::new(
+                columns * font.width() + PADDING.0 * 2 + 1,
+                rows * font.height() + PADDING.1 * 2 + 1
+            )
+        }
+    }
+
+    /// Writes `string` and draws whatever changed, the same way a Console does on every write
+    pub fn write(&mut self, string: &str) {
+        self.text.write_str(string).unwrap();
+        self.render();
+    }
+
+    pub fn render(&mut self) {
+        self.text.write_pixels(&mut self.frame_buffer, spleen(), PADDING).unwrap();
+    }
+
+    /// Each row of text, with the spaces at the end trimmed off
+    pub fn grid(&self) -> Vec<String> {
+        (0..self.text.height())
+            .map(|y| self.text.line(y).iter().collect::<String>().trim_end().to_owned())
+            .collect()
+    }
+
+    pub fn assert_grid(&self, expected: &[&str]) {
+        assert_eq!(self.grid(), expected);
+    }
+
+    /// The same as `grid`, but for what's being shown, which is different when the view is
+    /// scrolled back
+    pub fn view(&self) -> Vec<String> {
+        (0..self.text.height())
+            .map(|y| self.text.visible_line(y).iter().collect::<String>().trim_end().to_owned())
+            .collect()
+    }
+
+    pub fn assert_view(&self, expected: &[&str]) {
+        assert_eq!(self.view(), expected);
+    }
+
+    /// Checks the framebuffer holds exactly what drawing every cell that's being shown (and the
+    /// cursor, if it's showing) from scratch would give
+    pub fn assert_pixels_match_text(&self) {
+        let font = spleen();
+        let frame_buffer = &self.frame_buffer;
+        let mut expected = vec![Color::BLACK; frame_buffer.width * frame_buffer.height];
+
+        for row in 0..self.text.height() {
+            for (column, character) in self.text.visible_line(row).iter().enumerate() {
+                let (foreground, background) = self.text.visible_style_at(column, row).colors();
+                let bitmap = font.bitmap(*character);
+
+                let cursor = self.text.cursor_showing()
+                    && (self.text.cursor().0.min(self.text.width() - 1), self.text.cursor().1) == (column, row);
+
+                for y in 0..font.height() {
+                    for x in 0..font.width() {
+                        let glyph = bitmap[y * font.bytes_per_row() + x / 8] & (0x80 >> (x % 8)) != 0;
+
+                        let set = match self.text.cursor_shape() {
+                            _ if !cursor => glyph,
+                            CursorShape::Block => !glyph,
+                            CursorShape::Underline => glyph || y >= font.height() - 2,
+                            CursorShape::Bar => glyph || x < 2
+                        };
+
+                        let pixel_x = PADDING.0 + column * font.width() + x;
+                        let pixel_y = PADDING.1 + row * font.height() + y;
+
+                        expected[pixel_y * frame_buffer.width + pixel_x] = if set { foreground } else { background };
+                    }
+                }
+            }
+        }
+
+        for y in 0..frame_buffer.height {
+            for x in 0..frame_buffer.width {
+                assert_eq!(
+                    frame_buffer.pixel(x, y),
+                    Some(expected[y * frame_buffer.width + x]),
+                    "pixel ({x}, {y}), in the cell at ({}, {})",
+                    x.saturating_sub(PADDING.0) / font.width(),
+                    y.saturating_sub(PADDING.1) / font.height()
+                );
+            }
+        }
+    }
+
+    /// Compares the framebuffer against `snapshots/<name>.ppm`, or writes it there when
+    /// UPDATE_SNAPSHOTS is set. When they don't match, what was actually drawn gets written to
+    /// the temp directory to look at
+    pub fn assert_snapshot(&self, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{name}.ppm"));
+        let actual = self.frame_buffer.to_ppm();
+
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, &actual).unwrap();
+            return
+        }
+
+        let expected = std::fs::read(&path)
+            .unwrap_or_else(|_| panic!("no snapshot at {}, run with UPDATE_SNAPSHOTS=1 to make it", path.display()));
+
+        if actual != expected {
+            let actual_path = std::env::temp_dir().join(format!("{name}.actual.ppm"));
+            std::fs::write(&actual_path, &actual).unwrap();
+            panic!("{name} doesn't match its snapshot, what was drawn is in {}", actual_path.display());
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Draws shapes and blits partly off the edges of a small CPUFrameBuffer, checking what gets cut
+//! off and how blended pixels mix
+
+use graphics::{AlphaColor, Color, CPUFrameBuffer, GraphicsError, Rect};
+
+/// The buffer as rows of '#' for anything drawn and '.' for the black it started as
+fn picture(frame_buffer: &CPUFrameBuffer) -> Vec<String> {
+    (0..frame_buffer.height)
+        .map(|y| (0..frame_buffer.width)
+            .map(|x| if frame_buffer.pixel(x, y) == Some(Color::BLACK) { '.' } else { '#' })
+            .collect())
+        .collect()
+}
+
+#[test]
+fn rect_off_the_bottom_right_is_cut_off() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    frame_buffer.fill_rect(Rect::new(4, 2, 5, 5), Color::RED);
+
+    assert_eq!(picture(&frame_buffer), [
+        "......",
+        "......",
+        "....##",
+        "....##"
+    ]);
+    assert_eq!(frame_buffer.pixel(5, 3), Some(Color::RED));
+    assert_eq!(frame_buffer.pixel(6, 3), None);
+}
+
+#[test]
+fn outline_off_the_bottom_right_only_draws_the_sides_inside() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    frame_buffer.draw_rect(Rect::new(2, 1, 6, 6), 1, Color::WHITE);
+
+    assert_eq!(picture(&frame_buffer), [
+        "......",
+        "..####",
+        "..#...",
+        "..#..."
+    ]);
+}
+
+#[test]
+fn line_through_the_buffer_is_cut_off_at_both_ends() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    frame_buffer.draw_line((-3, -3), (8, 8), Color::GREEN);
+    frame_buffer.draw_line((-10, 3), (10, 3), Color::GREEN);
+
+    assert_eq!(picture(&frame_buffer), [
+        "#.....",
+        ".#....",
+        "..#...",
+        "######"
+    ]);
+}
+
+#[test]
+fn line_entirely_outside_draws_nothing() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    frame_buffer.draw_line((-5, -1), (20, -1), Color::GREEN);
+    frame_buffer.draw_line((6, 0), (6, 10), Color::GREEN);
+
+    assert!(picture(&frame_buffer).iter().all(|row| row == "......"));
+}
+
+#[test]
+fn circle_on_the_corner_only_draws_the_quarter_inside() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    frame_buffer.draw_circle((0, 0), 3, Color::CYAN);
+
+    assert_eq!(picture(&frame_buffer), [
+        "...#..",
+        "...#..",
+        "..#...",
+        "##...."
+    ]);
+}
+
+#[test]
+fn filled_circle_on_the_corner_covers_the_outline_and_inside() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    frame_buffer.fill_circle((0, 0), 3, Color::CYAN);
+
+    assert_eq!(picture(&frame_buffer), [
+        "####..",
+        "####..",
+        "###...",
+        "##...."
+    ]);
+}
+
+#[test]
+fn circle_off_the_bottom_right_is_cut_off() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    frame_buffer.fill_circle((6, 4), 2, Color::CYAN);
+
+    assert_eq!(picture(&frame_buffer), [
+        "......",
+        "......",
+        ".....#",
+        "....##"
+    ]);
+}
+
+#[test]
+fn blit_off_the_top_left_keeps_the_bottom_right_of_the_map() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    let pixel_map: Vec<Color> = (1..=9).map(|value| Color::rgb(value, 0, 0)).collect();
+
+    frame_buffer.blit(&pixel_map, 3, 3, -1, -1).unwrap();
+
+    assert_eq!(picture(&frame_buffer), [
+        "##....",
+        "##....",
+        "......",
+        "......"
+    ]);
+    assert_eq!(frame_buffer.pixel(0, 0), Some(Color::rgb(5, 0, 0)));
+    assert_eq!(frame_buffer.pixel(1, 1), Some(Color::rgb(9, 0, 0)));
+}
+
+#[test]
+fn blit_off_the_bottom_right_keeps_the_top_left_of_the_map() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+    let pixel_map: Vec<Color> = (1..=9).map(|value| Color::rgb(value, 0, 0)).collect();
+
+    frame_buffer.blit(&pixel_map, 3, 3, 5, 3).unwrap();
+    frame_buffer.blit(&pixel_map, 3, 3, 6, 0).unwrap();
+
+    assert_eq!(picture(&frame_buffer)[3], ".....#");
+    assert_eq!(frame_buffer.pixel(5, 3), Some(Color::rgb(1, 0, 0)));
+}
+
+#[test]
+fn blit_of_the_wrong_size_fails() {
+    let mut frame_buffer = CPUFrameBuffer::new(6, 4);
+
+    assert!(matches!(frame_buffer.blit(&[Color::RED; 5], 3, 2, 0, 0), Err(GraphicsError::BadDimensions)));
+    assert!(matches!(frame_buffer.blit_blended(&[AlphaColor::TRANSPARENT; 7], 3, 2, 0, 0), Err(GraphicsError::BadDimensions)));
+}
+
+#[test]
+fn blended_blit_mixes_by_alpha() {
+    let mut frame_buffer = CPUFrameBuffer::new(3, 1);
+    frame_buffer.fill_rect(Rect::new(0, 0, 3, 1), Color::BLUE);
+
+    let pixel_map = [
+        AlphaColor::new(Color::RED, 0),
+        AlphaColor::new(Color::RED, 128),
+        AlphaColor::new(Color::RED, 0xff)
+    ];
+    frame_buffer.blit_blended(&pixel_map, 3, 1, 0, 0).unwrap();
+
+    assert_eq!(frame_buffer.pixel(0, 0), Some(Color::BLUE));
+    assert_eq!(frame_buffer.pixel(1, 0), Some(Color::rgb(128, 0, 127)));
+    assert_eq!(frame_buffer.pixel(2, 0), Some(Color::RED));
+}
+
+#[test]
+fn blend_rounds_to_the_nearest_value() {
+    // A quarter of the way from black to white is 63.75
+    assert_eq!(Color::BLACK.blend(Color::WHITE, 64), Color::rgb(64, 64, 64));
+    assert_eq!(Color::WHITE.blend(Color::BLACK, 64), Color::rgb(191, 191, 191));
+    assert_eq!(Color::GREY.blend(Color::GREY, 200), Color::GREY);
+}
+
+#[test]
+fn blended_blit_off_the_edge_only_touches_whats_inside() {
+    let mut frame_buffer = CPUFrameBuffer::new(2, 2);
+    frame_buffer.fill_rect(Rect::new(0, 0, 2, 2), Color::WHITE);
+
+    frame_buffer.blit_blended(&[AlphaColor::new(Color::BLACK, 128); 4], 2, 2, 1, -1).unwrap();
+
+    assert_eq!(frame_buffer.pixel(0, 0), Some(Color::WHITE));
+    assert_eq!(frame_buffer.pixel(1, 0), Some(Color::rgb(127, 127, 127)));
+    assert_eq!(frame_buffer.pixel(0, 1), Some(Color::WHITE));
+    assert_eq!(frame_buffer.pixel(1, 1), Some(Color::WHITE));
+}
+
+#[test]
+fn blend_rect_off_the_edge_tints_only_whats_inside() {
+    let mut frame_buffer = CPUFrameBuffer::new(4, 2);
+    frame_buffer.fill_rect(Rect::new(0, 0, 4, 2), Color::WHITE);
+
+    frame_buffer.blend_rect(Rect::new(2, 1, 10, 10), AlphaColor::new(Color::BLACK, 128));
+
+    assert_eq!(frame_buffer.pixel(1, 1), Some(Color::WHITE));
+    assert_eq!(frame_buffer.pixel(2, 0), Some(Color::WHITE));
+    assert_eq!(frame_buffer.pixel(2, 1), Some(Color::rgb(127, 127, 127)));
+    assert_eq!(frame_buffer.pixel(3, 1), Some(Color::rgb(127, 127, 127)));
+}
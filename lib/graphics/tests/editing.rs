@@ -0,0 +1,207 @@
+//! The cursor being drawn, blinking, and the line editing functions a shell would use
+
+mod common;
+
+use common::Screen;
+
+use graphics::CursorShape;
+
+fn screen_with_cursor(columns: usize, rows: usize, shape: CursorShape) -> Screen {
+    let mut screen = Screen::new(columns, rows);
+    screen.text.set_cursor_shape(shape);
+    screen.text.set_cursor_visible(true);
+    screen
+}
+
+#[test]
+fn cursor_is_hidden_by_default() {
+    let screen = Screen::new(4, 2);
+
+    assert!(!screen.text.cursor_visible());
+    assert!(!screen.text.cursor_showing());
+}
+
+#[test]
+fn every_cursor_shape_is_drawn_at_the_cursor() {
+    for shape in [CursorShape::Block, CursorShape::Underline, CursorShape::Bar] {
+        let mut screen = screen_with_cursor(5, 2, shape);
+        screen.write("ab");
+
+        assert!(screen.text.cursor_showing());
+        screen.assert_pixels_match_text();
+    }
+}
+
+#[test]
+fn moving_the_cursor_draws_over_where_it_was() {
+    let mut screen = screen_with_cursor(5, 2, CursorShape::Block);
+    screen.write("abc");
+
+    screen.text.move_left();
+    screen.text.move_left();
+    screen.render();
+
+    assert_eq!(screen.text.cursor(), (1, 0));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn cursor_waiting_to_wrap_is_drawn_on_the_last_column() {
+    let mut screen = screen_with_cursor(3, 2, CursorShape::Underline);
+    screen.write("abc");
+
+    assert_eq!(screen.text.cursor(), (3, 0));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn cursor_is_drawn_over_after_scrolling() {
+    let mut screen = screen_with_cursor(4, 3, CursorShape::Block);
+    screen.write("a\nb\nc");
+    screen.write("d\ne");
+
+    screen.assert_grid(&["b", "cd", "e"]);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn escape_sequences_show_and_hide_the_cursor() {
+    let mut screen = Screen::new(4, 2);
+
+    screen.write("\x1b[?25h");
+    assert!(screen.text.cursor_visible());
+    screen.assert_pixels_match_text();
+
+    screen.write("\x1b[?25l");
+    assert!(!screen.text.cursor_visible());
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn ticking_blinks_the_cursor() {
+    let mut screen = screen_with_cursor(4, 2, CursorShape::Block);
+
+    // Nothing to do unless it's blinking
+    assert!(!screen.text.tick());
+
+    screen.text.set_cursor_blinking(true);
+    screen.write("a");
+
+    assert!(screen.text.tick());
+    assert!(!screen.text.cursor_showing());
+    screen.render();
+    screen.assert_pixels_match_text();
+
+    assert!(screen.text.tick());
+    assert!(screen.text.cursor_showing());
+    screen.render();
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn writing_turns_a_blinking_cursor_back_on() {
+    let mut screen = screen_with_cursor(4, 2, CursorShape::Bar);
+    screen.text.set_cursor_blinking(true);
+
+    screen.text.tick();
+    assert!(!screen.text.cursor_showing());
+
+    screen.write("a");
+    assert!(screen.text.cursor_showing());
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn inserting_moves_the_rest_of_the_line_right() {
+    let mut screen = Screen::new(6, 2);
+    screen.write("helo");
+
+    screen.text.move_left();
+    screen.text.insert_char('l').unwrap();
+    screen.render();
+
+    screen.assert_grid(&["hello", ""]);
+    assert_eq!(screen.text.cursor(), (4, 0));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn inserting_into_a_full_line_drops_the_last_character() {
+    let mut screen = Screen::new(4, 2);
+    screen.write("abcd\x1b[1;1H");
+
+    screen.text.insert_char('x').unwrap();
+    screen.render();
+
+    screen.assert_grid(&["xabc", ""]);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn deleting_moves_the_rest_of_the_line_left() {
+    let mut screen = Screen::new(6, 2);
+    screen.write("hexllo\x1b[1;3H");
+
+    screen.text.delete_char();
+    screen.render();
+
+    screen.assert_grid(&["hello", ""]);
+    assert_eq!(screen.text.cursor(), (2, 0));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn backspace_removes_the_character_before_the_cursor() {
+    let mut screen = Screen::new(4, 2);
+    screen.write("abcd");
+
+    // The cursor is waiting to wrap, so this takes off the d
+    screen.text.backspace();
+    screen.text.backspace();
+    screen.render();
+
+    screen.assert_grid(&["ab", ""]);
+    assert_eq!(screen.text.cursor(), (2, 0));
+    screen.assert_pixels_match_text();
+
+    screen.write("\r");
+    screen.text.backspace();
+    screen.assert_grid(&["ab", ""]);
+}
+
+#[test]
+fn moving_stops_at_the_ends_of_the_line() {
+    let mut screen = Screen::new(3, 2);
+
+    screen.text.move_left();
+    assert_eq!(screen.text.cursor(), (0, 0));
+
+    for _ in 0..5 {
+        screen.text.move_right();
+    }
+    assert_eq!(screen.text.cursor(), (2, 0));
+}
+
+#[test]
+fn clearing_to_the_end_of_the_line_keeps_the_cursor() {
+    let mut screen = Screen::new(6, 2);
+    screen.write("abcdef\nghi\x1b[1;3H");
+
+    screen.text.clear_to_end_of_line();
+    screen.render();
+
+    screen.assert_grid(&["ab", "ghi"]);
+    assert_eq!(screen.text.cursor(), (2, 0));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn insert_and_delete_escape_sequences_edit_the_line() {
+    let mut screen = Screen::new(8, 1);
+    screen.write("abcdef\x1b[1;2H\x1b[2@");
+    screen.assert_grid(&["a  bcdef"]);
+
+    screen.write("\x1b[3P");
+    screen.assert_grid(&["acdef"]);
+    screen.assert_pixels_match_text();
+}
@@ -0,0 +1,277 @@
+//! Decoding small BMP, TGA and QOI images built byte by byte, and making sure broken ones give an
+//! error rather than a panic
+
+use graphics::{bmp, qoi, tga, AlphaColor, Color, Image, ImageError};
+
+const RED: AlphaColor = AlphaColor::rgba(0xff, 0, 0, 0xff);
+const GREEN: AlphaColor = AlphaColor::rgba(0, 0xff, 0, 0xff);
+const BLUE: AlphaColor = AlphaColor::rgba(0, 0, 0xff, 0xff);
+const WHITE: AlphaColor = AlphaColor::rgba(0xff, 0xff, 0xff, 0xff);
+const BLACK: AlphaColor = AlphaColor::rgba(0, 0, 0, 0xff);
+
+/// A BMP with a BITMAPINFOHEADER. `extra` is whatever goes between it and the pixels (a palette or
+/// masks), and `colors_used` is the palette size the header gives
+fn bmp(width: i32, height: i32, bits_per_pixel: u16, compression: u32, colors_used: u32, extra: &[u8], pixels: &[u8]) -> Vec<u8> {
+    let offset = 14 + 40 + extra.len() as u32;
+
+    let mut bytes = b"BM".to_vec();
+    bytes.extend((offset + pixels.len() as u32).to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(offset.to_le_bytes());
+
+    bytes.extend(40u32.to_le_bytes());
+    bytes.extend(width.to_le_bytes());
+    bytes.extend(height.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(bits_per_pixel.to_le_bytes());
+    bytes.extend(compression.to_le_bytes());
+    bytes.extend([0; 12]);
+    bytes.extend(colors_used.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+
+    bytes.extend(extra);
+    bytes.extend(pixels);
+    bytes
+}
+
+/// 3x2, 24 bit and bottom up, so the first row in the file is the bottom one. Each row is 9
+/// bytes padded to 12
+fn bmp_24() -> Vec<u8> {
+    let pixels = [
+        0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0xff, 0, 0, 0,
+        0, 0, 0xff, 0, 0xff, 0, 0xff, 0, 0, 0, 0, 0
+    ];
+
+    bmp(3, 2, 24, 0, 0, &[], &pixels)
+}
+
+/// A TGA with no ID or colour map unless `map` has one
+fn tga(image_type: u8, width: u16, height: u16, bits_per_pixel: u8, descriptor: u8, map: Option<(u8, &[u8])>, pixels: &[u8]) -> Vec<u8> {
+    let (map_bits, map_bytes) = map.unwrap_or((0, &[]));
+    let map_length = if map_bits == 0 { 0 } else { map_bytes.len() as u16 / (map_bits as u16 / 8) };
+
+    let mut bytes = vec![0, map.is_some() as u8, image_type];
+    bytes.extend(0u16.to_le_bytes());
+    bytes.extend(map_length.to_le_bytes());
+    bytes.push(map_bits);
+    bytes.extend([0; 4]);
+    bytes.extend(width.to_le_bytes());
+    bytes.extend(height.to_le_bytes());
+    bytes.extend([bits_per_pixel, descriptor]);
+
+    bytes.extend(map_bytes);
+    bytes.extend(pixels);
+    bytes
+}
+
+/// 2x2 true colour, blue first, starting from the bottom left
+fn tga_24() -> Vec<u8> {
+    tga(2, 2, 2, 24, 0, None, &[0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0xff, 0, 0xff, 0])
+}
+
+fn qoi(width: u32, height: u32, channels: u8, data: &[u8]) -> Vec<u8> {
+    let mut bytes = b"qoif".to_vec();
+    bytes.extend(width.to_be_bytes());
+    bytes.extend(height.to_be_bytes());
+    bytes.extend([channels, 0]);
+    bytes.extend(data);
+    bytes.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+    bytes
+}
+
+/// 3x2 using every op: RGB, a run, RGBA, an index back to the first colour, a diff and a luma
+fn qoi_every_op() -> Vec<u8> {
+    qoi(3, 2, 4, &[
+        0xfe, 0xff, 0, 0,
+        0xc0,
+        0xff, 0, 0, 0xff, 0x80,
+        0x32,
+        0x5e,
+        0xa5, 0xa5
+    ])
+}
+
+#[test]
+fn bmp_24_bit_is_flipped_the_right_way_up() {
+    let image = bmp::parse(&bmp_24()).unwrap();
+
+    assert_eq!((image.width, image.height), (3, 2));
+    assert_eq!(image.pixels(), [RED, GREEN, BLUE, WHITE, BLACK, RED]);
+}
+
+#[test]
+fn bmp_paletted_top_down() {
+    // 8 bit indices into a 3 colour palette, with a negative height so the first row is the top
+    let palette = [0, 0, 0xff, 0, 0, 0xff, 0, 0, 0xff, 0, 0, 0];
+    let pixels = [2, 1, 0, 0, 0, 2, 0, 0];
+    let image = bmp::parse(&bmp(2, -2, 8, 0, 3, &palette, &pixels)).unwrap();
+
+    assert_eq!(image.pixels(), [BLUE, GREEN, RED, BLUE]);
+}
+
+#[test]
+fn bmp_alpha_bitfields_are_transparent() {
+    let masks = [0x00ff_0000u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000].map(u32::to_le_bytes).concat();
+    let pixels = [0x00, 0x00, 0xff, 0x80, 0xff, 0x00, 0x00, 0x00];
+    let image = bmp::parse(&bmp(2, 1, 32, 6, 0, &masks, &pixels)).unwrap();
+
+    assert_eq!(image.pixels(), [AlphaColor::rgba(0xff, 0, 0, 0x80), AlphaColor::rgba(0, 0, 0xff, 0)]);
+}
+
+#[test]
+fn bmp_with_a_bad_header_is_an_error() {
+    let mut unknown_header = bmp_24();
+    unknown_header[14] = 20;
+
+    let mut bad_depth = bmp_24();
+    bad_depth[28] = 7;
+
+    let mut compressed = bmp_24();
+    compressed[30] = 1;
+
+    assert_eq!(bmp::parse(b"BX"), Err(ImageError::WrongFormat));
+    assert_eq!(bmp::parse(&bmp_24()[..20]), Err(ImageError::Truncated));
+    assert_eq!(bmp::parse(&unknown_header), Err(ImageError::Unsupported));
+    assert_eq!(bmp::parse(&bad_depth), Err(ImageError::Corrupt));
+    assert_eq!(bmp::parse(&compressed), Err(ImageError::Unsupported));
+    assert_eq!(bmp::parse(&bmp(-3, 2, 24, 0, 0, &[], &[0; 24])), Err(ImageError::Corrupt));
+    assert_eq!(bmp::parse(&bmp(0, 2, 24, 0, 0, &[], &[])), Err(ImageError::BadDimensions));
+}
+
+#[test]
+fn bmp_with_a_short_pixel_array_is_truncated() {
+    let bytes = bmp_24();
+
+    // The padding on the last row can be left off, but not the pixels
+    assert!(bmp::parse(&bytes[..bytes.len() - 3]).is_ok());
+    assert_eq!(bmp::parse(&bytes[..bytes.len() - 4]), Err(ImageError::Truncated));
+}
+
+#[test]
+fn bmp_with_a_short_palette_is_truncated() {
+    assert_eq!(bmp::parse(&bmp(1, 1, 8, 0, 3, &[0; 8], &[])), Err(ImageError::Truncated));
+}
+
+#[test]
+fn bmp_index_past_the_palette_is_corrupt() {
+    let pixels = [3, 0, 0, 0];
+    assert_eq!(bmp::parse(&bmp(1, 1, 8, 0, 2, &[0; 8], &pixels)), Err(ImageError::Corrupt));
+}
+
+#[test]
+fn tga_24_bit_starts_at_the_bottom_left() {
+    let image = tga::parse(&tga_24()).unwrap();
+
+    assert_eq!((image.width, image.height), (2, 2));
+    assert_eq!(image.pixels(), [RED, GREEN, WHITE, BLACK]);
+}
+
+#[test]
+fn tga_32_bit_from_the_top_right_keeps_its_alpha() {
+    // 8 alpha bits, right to left and top to bottom
+    let pixels = [0, 0, 0xff, 0x80, 0xff, 0, 0, 0];
+    let image = tga::parse(&tga(2, 2, 1, 32, 0x38, None, &pixels)).unwrap();
+
+    assert_eq!(image.pixels(), [AlphaColor::rgba(0, 0, 0xff, 0), AlphaColor::rgba(0xff, 0, 0, 0x80)]);
+}
+
+#[test]
+fn tga_colour_mapped() {
+    let map = [0, 0, 0xff, 0xff, 0, 0];
+    let image = tga::parse(&tga(1, 2, 1, 8, 0x20, Some((24, &map)), &[1, 0])).unwrap();
+
+    assert_eq!(image.pixels(), [BLUE, RED]);
+}
+
+#[test]
+fn tga_with_a_bad_header_is_an_error() {
+    assert_eq!(tga::parse(&tga_24()[..17]), Err(ImageError::Truncated));
+    assert_eq!(tga::parse(&tga(5, 2, 2, 24, 0, None, &[0; 12])), Err(ImageError::WrongFormat));
+    assert_eq!(tga::parse(&tga(2, 2, 2, 12, 0, None, &[0; 12])), Err(ImageError::Unsupported));
+    assert_eq!(tga::parse(&tga(2, 0, 2, 24, 0, None, &[])), Err(ImageError::BadDimensions));
+
+    // A colour mapped type without a map
+    assert_eq!(tga::parse(&tga(1, 2, 2, 8, 0, None, &[0; 4])), Err(ImageError::Corrupt));
+}
+
+#[test]
+fn tga_run_length_encoding_is_rejected() {
+    // One run packet of 4 pixels, which would run past the end of a 2x1 image
+    let packets = [0x83, 0, 0, 0xff];
+
+    assert_eq!(tga::parse(&tga(10, 2, 1, 24, 0, None, &packets)), Err(ImageError::Unsupported));
+}
+
+#[test]
+fn tga_with_a_short_pixel_array_is_truncated() {
+    let bytes = tga_24();
+    assert_eq!(tga::parse(&bytes[..bytes.len() - 1]), Err(ImageError::Truncated));
+}
+
+#[test]
+fn tga_index_past_the_map_is_corrupt() {
+    assert_eq!(tga::parse(&tga(1, 1, 1, 8, 0, Some((24, &[0; 3])), &[1])), Err(ImageError::Corrupt));
+}
+
+#[test]
+fn qoi_every_op_decodes() {
+    let image = qoi::parse(&qoi_every_op()).unwrap();
+
+    assert_eq!((image.width, image.height), (3, 2));
+    assert_eq!(image.pixels(), [
+        RED,
+        RED,
+        AlphaColor::rgba(0, 0, 0xff, 0x80),
+        RED,
+        AlphaColor::rgba(0xfe, 1, 0, 0xff),
+        AlphaColor::rgba(5, 6, 2, 0xff)
+    ]);
+}
+
+#[test]
+fn qoi_without_the_end_marker_is_truncated() {
+    let bytes = qoi_every_op();
+    assert_eq!(qoi::parse(&bytes[..bytes.len() - 1]), Err(ImageError::Truncated));
+}
+
+#[test]
+fn qoi_running_out_of_data_is_truncated() {
+    // Four pixels, but only data for three
+    assert_eq!(qoi::parse(&qoi(2, 2, 3, &[0xfe, 0xff, 0, 0, 0xc1])), Err(ImageError::Truncated));
+    // An RGB op cut off partway
+    assert_eq!(qoi::parse(&qoi(1, 1, 3, &[0xfe, 0xff])), Err(ImageError::Truncated));
+    assert_eq!(qoi::parse(&qoi_every_op()[..10]), Err(ImageError::Truncated));
+}
+
+#[test]
+fn qoi_run_past_the_end_is_corrupt() {
+    // A run of 3 in a 2 pixel image
+    assert_eq!(qoi::parse(&qoi(2, 1, 3, &[0xc2])), Err(ImageError::Corrupt));
+}
+
+#[test]
+fn qoi_with_a_bad_header_is_an_error() {
+    assert_eq!(qoi::parse(b"qoix"), Err(ImageError::WrongFormat));
+    assert_eq!(qoi::parse(&qoi(1, 1, 5, &[0xc0])), Err(ImageError::Corrupt));
+    assert_eq!(qoi::parse(&qoi(0, 1, 3, &[])), Err(ImageError::BadDimensions));
+    assert_eq!(qoi::parse(&qoi(100_000, 100_000, 3, &[])), Err(ImageError::BadDimensions));
+}
+
+#[test]
+fn decode_works_out_the_format() {
+    assert_eq!(Image::decode(&bmp_24()), bmp::parse(&bmp_24()));
+    assert_eq!(Image::decode(&tga_24()), tga::parse(&tga_24()));
+    assert_eq!(Image::decode(&qoi_every_op()), qoi::parse(&qoi_every_op()));
+
+    // Which isn't a TGA either
+    assert_eq!(Image::decode(b"not an image at all"), Err(ImageError::WrongFormat));
+}
+
+#[test]
+fn transparent_pixels_are_blended_onto_the_background() {
+    let image = qoi::parse(&qoi_every_op()).unwrap();
+    let colors = image.to_colors(Color::WHITE);
+
+    assert_eq!(colors[0], Color::RED);
+    assert_eq!(colors[2], Color::WHITE.blend(Color::BLUE, 0x80));
+}
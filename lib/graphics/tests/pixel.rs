@@ -0,0 +1,87 @@
+//! Turning colours into the pixel formats framebuffers report
+
+use graphics::{Channel, Color, PixelFormat};
+
+/// 16 bit 5-6-5, which is about the oddest layout anything still uses
+const RGB565: PixelFormat = PixelFormat::Bitmask {
+    bits_per_pixel: 16,
+    red: Channel::from_mask(0xf800),
+    green: Channel::from_mask(0x07e0),
+    blue: Channel::from_mask(0x001f)
+};
+
+const COLOR: Color = Color::rgb(0x12, 0x34, 0x56);
+
+#[test]
+fn rgb_puts_red_in_the_first_byte() {
+    assert_eq!(PixelFormat::Rgb.encode(COLOR), 0x56_34_12);
+    assert_eq!(PixelFormat::Rgb.encode(COLOR).to_le_bytes(), [0x12, 0x34, 0x56, 0]);
+}
+
+#[test]
+fn bgr_is_the_same_as_color() {
+    assert_eq!(PixelFormat::Bgr.encode(COLOR), 0x12_34_56);
+    assert!(PixelFormat::Bgr.matches_color());
+    assert!(!PixelFormat::Rgb.matches_color());
+}
+
+#[test]
+fn masks_are_turned_into_channels() {
+    assert_eq!(Channel::from_mask(0xf800), Channel { shift: 11, size: 5 });
+    assert_eq!(Channel::from_mask(0x07e0), Channel { shift: 5, size: 6 });
+    assert_eq!(Channel::from_mask(0), Channel { shift: 0, size: 0 });
+}
+
+#[test]
+fn bitmask_channels_keep_their_top_bits() {
+    assert_eq!(RGB565.bytes_per_pixel(), 2);
+
+    assert_eq!(RGB565.encode(Color::WHITE), 0xffff);
+    assert_eq!(RGB565.encode(Color::RED), 0xf800);
+    assert_eq!(RGB565.encode(Color::GREEN), 0x07e0);
+    assert_eq!(RGB565.encode(Color::BLUE), 0x001f);
+
+    // 0x12 >> 3, 0x34 >> 2 and 0x56 >> 3
+    assert_eq!(RGB565.encode(COLOR), 0x02 << 11 | 0x0d << 5 | 0x0a);
+}
+
+#[test]
+fn bitmask_the_same_as_color_is_spotted() {
+    let format = PixelFormat::Bitmask {
+        bits_per_pixel: 32,
+        red: Channel::from_mask(0xff0000),
+        green: Channel::from_mask(0x00ff00),
+        blue: Channel::from_mask(0x0000ff)
+    };
+
+    assert!(format.matches_color());
+    assert_eq!(format.encode(COLOR), COLOR.hex());
+}
+
+#[test]
+fn channel_with_no_bits_is_left_empty() {
+    let format = PixelFormat::Bitmask {
+        bits_per_pixel: 32,
+        red: Channel::from_mask(0xff0000),
+        green: Channel::from_mask(0),
+        blue: Channel::from_mask(0x0000ff)
+    };
+
+    assert_eq!(format.encode(Color::WHITE), 0xff00ff);
+}
+
+#[test]
+fn rows_are_encoded_pixel_by_pixel() {
+    let colors = [Color::RED, COLOR, Color::BLUE];
+
+    let mut pixels = [0; 12];
+    PixelFormat::Rgb.encode_row(&colors, &mut pixels);
+    assert_eq!(pixels, [0xff, 0, 0, 0, 0x12, 0x34, 0x56, 0, 0, 0, 0xff, 0]);
+
+    PixelFormat::Bgr.encode_row(&colors, &mut pixels);
+    assert_eq!(pixels, [0, 0, 0xff, 0, 0x56, 0x34, 0x12, 0, 0xff, 0, 0, 0]);
+
+    let mut pixels = [0; 6];
+    RGB565.encode_row(&colors, &mut pixels);
+    assert_eq!(pixels, [0x00, 0xf8, 0xaa, 0x11, 0x1f, 0x00]);
+}
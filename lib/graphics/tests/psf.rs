@@ -0,0 +1,183 @@
+//! Parsing PSF1 and PSF2 fonts built byte by byte, with and without unicode tables
+
+use graphics::{psf, FontError, MonoFont};
+
+/// A PSF1 font with 256 glyphs 8x4, where every row of glyph n is n
+fn psf1(mode: u8, table: &[u16]) -> Vec<u8> {
+    let mut bytes = vec![0x36, 0x04, mode, 4];
+
+    for glyph in 0..=255u8 {
+        bytes.extend([glyph; 4]);
+    }
+
+    for entry in table {
+        bytes.extend(entry.to_le_bytes());
+    }
+
+    bytes
+}
+
+/// The table for `psf1`: glyph 'A' is also capital alpha, glyph 1 is U+FFFD, glyph 2 is 'é' both
+/// on its own and as a sequence, and nothing else has any characters
+fn psf1_table() -> Vec<u16> {
+    let mut table = Vec::new();
+
+    for glyph in 0..256 {
+        match glyph {
+            0x41 => table.extend([0x41, 0x391]),
+            0x01 => table.push(0xfffd),
+            0x02 => table.extend([0xe9, 0xfffe, 0x65, 0x301]),
+            _ => {}
+        }
+        table.push(0xffff);
+    }
+
+    table
+}
+
+/// A PSF2 font with 3 glyphs 12x3, so each row is 2 bytes with 4 bits of padding
+fn psf2(flags: u32, glyph_size: u32, table: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x72, 0xb5, 0x4a, 0x86];
+
+    for field in [0, 32, flags, 3, glyph_size, 3, 12] {
+        bytes.extend(u32::to_le_bytes(field));
+    }
+
+    // Glyph n has its nth row set, all 12 pixels of it
+    for glyph in 0..3 {
+        for row in 0..3 {
+            bytes.extend(if row == glyph { [0xff, 0xf0] } else { [0, 0] });
+        }
+    }
+
+    bytes.extend(table);
+    bytes
+}
+
+/// The table for `psf2`, with the same characters as `psf1_table` but for glyphs 0, 1 and 2
+fn psf2_table() -> Vec<u8> {
+    let mut table = Vec::new();
+
+    table.extend("A\u{391}".bytes());
+    table.push(0xff);
+    table.extend("\u{fffd}".bytes());
+    table.push(0xff);
+    table.extend("é".bytes());
+    table.push(0xfe);
+    table.extend("e\u{301}".bytes());
+    table.push(0xff);
+
+    table
+}
+
+fn rows(font: &MonoFont, character: char) -> Vec<&[u8]> {
+    let glyph = font.get(character).unwrap();
+    (0..glyph.height()).map(|y| glyph.row(y)).collect()
+}
+
+#[test]
+fn psf1_glyphs_go_under_every_character_in_the_table() {
+    let font = psf::parse(&psf1(0x02, &psf1_table())).unwrap();
+
+    assert_eq!((font.width, font.height), (8, 4));
+    assert_eq!(font.characters().collect::<String>(), "Aé\u{391}\u{fffd}");
+
+    assert_eq!(rows(&font, 'A'), [[0x41]; 4]);
+    assert_eq!(font.get('\u{391}'), font.get('A'));
+    assert_eq!(rows(&font, 'é'), [[0x02]; 4]);
+}
+
+#[test]
+fn psf1_replacement_character_is_the_replacement_glyph() {
+    let font = psf::parse(&psf1(0x02, &psf1_table())).unwrap();
+
+    assert_eq!(font.replacement().bits(), [0x01; 4]);
+    assert_eq!(font.glyph('z').bits(), [0x01; 4]);
+}
+
+#[test]
+fn psf1_without_a_table_is_placed_by_index() {
+    let font = psf::parse(&psf1(0, &[])).unwrap();
+
+    assert_eq!(font.len(), 256);
+    assert_eq!(rows(&font, 'A'), [[0x41]; 4]);
+    assert_eq!(rows(&font, 'ÿ'), [[0xff]; 4]);
+}
+
+#[test]
+fn psf2_glyphs_go_under_every_character_in_the_table() {
+    let font = psf::parse(&psf2(1, 6, &psf2_table())).unwrap();
+
+    assert_eq!((font.width, font.height), (12, 3));
+    assert_eq!(font.characters().collect::<String>(), "Aé\u{391}\u{fffd}");
+
+    assert_eq!(rows(&font, 'A'), [[0xff, 0xf0], [0, 0], [0, 0]]);
+    assert_eq!(font.get('\u{391}'), font.get('A'));
+    assert_eq!(rows(&font, 'é'), [[0, 0], [0, 0], [0xff, 0xf0]]);
+    assert_eq!(font.replacement(), font.get('\u{fffd}').unwrap());
+
+    let glyph = font.get('A').unwrap();
+    assert!(glyph.is_set(11, 0));
+    assert!(!glyph.is_set(0, 1));
+}
+
+#[test]
+fn psf2_without_a_table_is_placed_by_index() {
+    let font = psf::parse(&psf2(0, 6, &[])).unwrap();
+
+    assert_eq!(font.characters().collect::<String>(), "\0\u{1}\u{2}");
+    assert_eq!(rows(&font, '\u{1}'), [[0, 0], [0xff, 0xf0], [0, 0]]);
+}
+
+#[test]
+fn bad_magic_is_the_wrong_format() {
+    let mut bytes = psf2(1, 6, &psf2_table());
+    bytes[0] = 0x73;
+
+    assert!(matches!(psf::parse(&bytes), Err(FontError::WrongFormat)));
+    assert!(matches!(psf::parse(b"STARTFONT 2.1\n"), Err(FontError::WrongFormat)));
+}
+
+#[test]
+fn psf1_missing_glyphs_is_truncated() {
+    // 512 glyphs, but only 256 of them are there
+    assert!(matches!(psf::parse(&psf1(0x01, &[])), Err(FontError::Truncated)));
+    assert!(matches!(psf::parse(&[0x36, 0x04, 0]), Err(FontError::Truncated)));
+}
+
+#[test]
+fn psf1_table_without_enough_entries_is_truncated() {
+    let mut table = psf1_table();
+    table.pop();
+
+    assert!(matches!(psf::parse(&psf1(0x02, &table)), Err(FontError::Truncated)));
+}
+
+#[test]
+fn psf2_missing_glyphs_is_truncated() {
+    let bytes = psf2(0, 6, &[]);
+
+    assert!(matches!(psf::parse(&bytes[..bytes.len() - 1]), Err(FontError::Truncated)));
+    assert!(matches!(psf::parse(&bytes[..20]), Err(FontError::Truncated)));
+}
+
+#[test]
+fn psf2_table_without_a_separator_is_truncated() {
+    let mut table = psf2_table();
+    table.pop();
+
+    assert!(matches!(psf::parse(&psf2(1, 6, &table)), Err(FontError::Truncated)));
+}
+
+#[test]
+fn psf2_table_that_isnt_utf8_is_invalid() {
+    let mut table = psf2_table();
+    table[0] = 0xc3;
+
+    assert!(matches!(psf::parse(&psf2(1, 6, &table)), Err(FontError::InvalidUnicodeTable)));
+}
+
+#[test]
+fn psf2_glyph_size_has_to_match_the_width_and_height() {
+    assert!(matches!(psf::parse(&psf2(1, 3, &psf2_table())), Err(FontError::WrongGlyphSize)));
+}
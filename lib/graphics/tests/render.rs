@@ -0,0 +1,190 @@
+//! Writes text to a TextBuffer and renders it with spleen (see `common`), checking the characters,
+//! the cursor and the pixels
+
+mod common;
+
+use common::{spleen, Screen};
+
+use graphics::{Color, CPUFrameBuffer, MonoFont};
+
+#[test]
+fn text_is_written_from_the_top_left() {
+    let mut screen = Screen::new(6, 3);
+    screen.write("abc");
+
+    screen.assert_grid(&["abc", "", ""]);
+    assert_eq!(screen.text.cursor(), (3, 0));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn filling_a_line_waits_to_wrap() {
+    let mut screen = Screen::new(4, 3);
+    screen.write("abcd");
+
+    screen.assert_grid(&["abcd", "", ""]);
+    assert_eq!(screen.text.cursor(), (4, 0));
+}
+
+#[test]
+fn wrapping_goes_to_the_first_column() {
+    let mut screen = Screen::new(4, 3);
+    screen.write("abcdef");
+
+    screen.assert_grid(&["abcd", "ef", ""]);
+    assert_eq!(screen.text.cursor(), (2, 1));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn newline_after_a_full_line_doesnt_leave_a_blank_line() {
+    let mut screen = Screen::new(4, 3);
+    screen.write("abcd\nef");
+
+    screen.assert_grid(&["abcd", "ef", ""]);
+}
+
+#[test]
+fn filling_the_last_cell_doesnt_scroll_until_the_next_character() {
+    let mut screen = Screen::new(3, 2);
+    screen.write("abcdef");
+
+    screen.assert_grid(&["abc", "def"]);
+    assert_eq!(screen.text.cursor(), (3, 1));
+    screen.assert_pixels_match_text();
+
+    screen.write("g");
+
+    screen.assert_grid(&["def", "g"]);
+    assert_eq!(screen.text.cursor(), (1, 1));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn newline_on_the_last_row_scrolls() {
+    let mut screen = Screen::new(5, 3);
+    screen.write("one\ntwo\nthree\n");
+
+    screen.assert_grid(&["two", "three", ""]);
+    assert_eq!(screen.text.cursor(), (0, 2));
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn scrolling_a_line_at_a_time_matches_drawing_from_scratch() {
+    let mut screen = Screen::new(6, 4);
+
+    // Rendering after every line makes write_pixels move the pixels up each time rather than
+    // drawing everything again
+    for line in 0..10 {
+        screen.write(&format!("\nline{line}"));
+        screen.assert_pixels_match_text();
+    }
+
+    screen.assert_grid(&["line6", "line7", "line8", "line9"]);
+}
+
+#[test]
+fn scrolling_several_lines_at_once_matches_drawing_from_scratch() {
+    let mut screen = Screen::new(6, 4);
+    screen.write("a\nb\nc\nd");
+
+    screen.write("\ne\nf");
+    screen.assert_grid(&["c", "d", "e", "f"]);
+    screen.assert_pixels_match_text();
+
+    // More lines than the screen has, which redraws everything
+    screen.write("\n1\n2\n3\n4\n5");
+    screen.assert_grid(&["2", "3", "4", "5"]);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn control_characters_move_the_cursor() {
+    let mut screen = Screen::new(20, 2);
+
+    screen.write("abc\r");
+    assert_eq!(screen.text.cursor(), (0, 0));
+
+    screen.write("x\ty");
+    screen.assert_grid(&["xbc     y", ""]);
+    assert_eq!(screen.text.cursor(), (9, 0));
+
+    screen.write("\x08\x08z");
+    screen.assert_grid(&["xbc    zy", ""]);
+    assert_eq!(screen.text.cursor(), (8, 0));
+}
+
+#[test]
+fn cursor_escape_sequences_are_clamped_to_the_screen() {
+    let mut screen = Screen::new(5, 3);
+
+    screen.write("\x1b[2;4H");
+    assert_eq!(screen.text.cursor(), (3, 1));
+
+    screen.write("\x1b[10B\x1b[10C");
+    assert_eq!(screen.text.cursor(), (4, 2));
+
+    screen.write("\x1b[99A\x1b[2D");
+    assert_eq!(screen.text.cursor(), (2, 0));
+
+    screen.write("x");
+    screen.assert_grid(&["  x", "", ""]);
+}
+
+#[test]
+fn erasing_uses_the_current_background() {
+    let mut screen = Screen::new(4, 2);
+    screen.write("abcd\x1b[44m\x1b[1;3H\x1b[K");
+
+    screen.assert_grid(&["ab", ""]);
+    assert_eq!(screen.text.style_at(1, 0).background, Color::BLACK);
+    assert_eq!(screen.text.style_at(2, 0).background, screen.text.style().background);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn characters_spleen_doesnt_have_are_drawn_with_the_replacement_glyph() {
+    let mut screen = Screen::new(4, 1);
+    screen.write("a\u{e000}b");
+
+    screen.assert_grid(&["a\u{e000}b"]);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn drawing_again_after_invalidating_gives_the_same_pixels() {
+    let mut screen = Screen::new(5, 3);
+    screen.write("\x1b[31mred\x1b[0m\nplain text that wraps\nand scrolls");
+
+    let mut fresh = CPUFrameBuffer::new(screen.frame_buffer.width, screen.frame_buffer.height);
+    screen.text.invalidate();
+    screen.text.write_pixels(&mut fresh, spleen(), common::PADDING).unwrap();
+
+    assert!(fresh.buffer == screen.frame_buffer.buffer);
+}
+
+#[test]
+fn text_that_doesnt_fit_the_framebuffer_is_an_error() {
+    let mut screen = Screen::new(4, 2);
+    let mut small = CPUFrameBuffer::new(4 * 16, 2 * 32);
+
+    assert!(screen.text.write_pixels(&mut small, &MonoFont::new(16, 32), (0, 0)).is_err());
+}
+
+#[test]
+fn wrapped_and_scrolled_text_matches_the_snapshot() {
+    let mut screen = Screen::new(4, 2);
+    screen.write("hello, world");
+
+    screen.assert_grid(&["o, w", "orld"]);
+    screen.assert_snapshot("wrap_and_scroll");
+}
+
+#[test]
+fn coloured_text_matches_the_snapshot() {
+    let mut screen = Screen::new(4, 2);
+    screen.write("\x1b[31mR\x1b[32mG\x1b[34mB\x1b[0m!\n\x1b[7minv\x1b[0m");
+
+    screen.assert_snapshot("colours");
+}
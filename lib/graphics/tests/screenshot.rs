@@ -0,0 +1,108 @@
+//! Saves a small CPUFrameBuffer as PPM and BMP, checking the headers and every pixel byte
+
+use graphics::{bmp, AlphaColor, Color, CPUFrameBuffer, Rect};
+
+/// 3x2: red, green, blue along the top and white, black, grey along the bottom. A width of 3 is
+/// 9 bytes a row, so BMP rows need 3 bytes of padding
+fn frame_buffer() -> CPUFrameBuffer {
+    let mut frame_buffer = CPUFrameBuffer::new(3, 2);
+    let pixels = [Color::RED, Color::GREEN, Color::BLUE, Color::WHITE, Color::BLACK, Color::GREY];
+
+    frame_buffer.blit(&pixels, 3, 2, 0, 0).unwrap();
+    frame_buffer
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+#[test]
+fn ppm_is_a_text_header_then_rgb_rows() {
+    let ppm = frame_buffer().to_ppm();
+    let header = b"P6\n3 2\n255\n";
+
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(&ppm[header.len()..], [
+        0xff, 0, 0, 0, 0xff, 0, 0, 0, 0xff,
+        0xff, 0xff, 0xff, 0, 0, 0, 0x80, 0x80, 0x80
+    ]);
+}
+
+#[test]
+fn ppm_is_written_a_row_at_a_time() {
+    let mut parts = Vec::new();
+    frame_buffer().write_ppm(|bytes| parts.push(bytes.len()));
+
+    assert_eq!(parts, [11, 9, 9]);
+}
+
+#[test]
+fn bmp_header_describes_a_top_down_24_bit_image() {
+    let bmp = frame_buffer().to_bmp();
+
+    // BITMAPFILEHEADER
+    assert_eq!(&bmp[..2], b"BM");
+    assert_eq!(u32_at(&bmp, 2) as usize, bmp.len());
+    assert_eq!(u32_at(&bmp, 6), 0);
+    assert_eq!(u32_at(&bmp, 10), 54);
+
+    // BITMAPINFOHEADER, with a negative height since the rows go from the top down rather than
+    // the usual bottom up
+    assert_eq!(u32_at(&bmp, 14), 40);
+    assert_eq!(u32_at(&bmp, 18) as i32, 3);
+    assert_eq!(u32_at(&bmp, 22) as i32, -2);
+    assert_eq!(u16_at(&bmp, 26), 1);
+    assert_eq!(u16_at(&bmp, 28), 24);
+    assert_eq!(u32_at(&bmp, 30), 0);
+    assert_eq!(u32_at(&bmp, 34), 24);
+    assert!(bmp[38..54].iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn bmp_rows_are_bgr_from_the_top_padded_to_4_bytes() {
+    let bmp = frame_buffer().to_bmp();
+
+    assert_eq!(bmp.len(), 54 + 2 * 12);
+    assert_eq!(&bmp[54..66], [0, 0, 0xff, 0, 0xff, 0, 0xff, 0, 0, 0, 0, 0]);
+    assert_eq!(&bmp[66..78], [0xff, 0xff, 0xff, 0, 0, 0, 0x80, 0x80, 0x80, 0, 0, 0]);
+}
+
+#[test]
+fn bmp_row_that_is_already_a_multiple_of_4_has_no_padding() {
+    let mut frame_buffer = CPUFrameBuffer::new(4, 1);
+    frame_buffer.fill_rect(Rect::new(3, 0, 1, 1), Color::RED);
+
+    let bmp = frame_buffer.to_bmp();
+
+    assert_eq!(bmp.len(), 54 + 12);
+    assert_eq!(&bmp[63..], [0, 0, 0xff]);
+}
+
+#[test]
+fn stride_past_the_width_isnt_saved() {
+    let mut frame_buffer = CPUFrameBuffer::new(2, 2);
+    frame_buffer.stride = 3;
+    frame_buffer.buffer = vec![Color::WHITE, Color::BLACK, Color::RED, Color::BLACK, Color::WHITE, Color::RED].into_boxed_slice();
+
+    let ppm = frame_buffer.to_ppm();
+
+    assert_eq!(&ppm[11..], [0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff]);
+}
+
+#[test]
+fn bmp_decodes_back_to_the_same_pixels() {
+    let frame_buffer = frame_buffer();
+    let image = bmp::parse(&frame_buffer.to_bmp()).unwrap();
+
+    assert_eq!((image.width, image.height), (3, 2));
+
+    for y in 0..2 {
+        for x in 0..3 {
+            assert_eq!(image.pixel(x, y), frame_buffer.pixel(x, y).map(AlphaColor::from));
+        }
+    }
+}
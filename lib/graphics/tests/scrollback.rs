@@ -0,0 +1,175 @@
+//! Lines that scroll off the top being kept, and looking back through them
+
+mod common;
+
+use common::Screen;
+
+use graphics::{Color, CursorShape};
+
+/// A 6 by 3 screen that has had lines 0 to 7 written to it, so 0 to 4 are in the scrollback
+fn screen_with_history(scrollback: usize) -> Screen {
+    let mut screen = Screen::new(6, 3);
+    screen.text.set_scrollback(scrollback);
+
+    screen.write("line0");
+    for line in 1..8 {
+        screen.write(&format!("\nline{line}"));
+    }
+
+    screen
+}
+
+#[test]
+fn nothing_is_kept_by_default() {
+    let mut screen = Screen::new(6, 2);
+    screen.write("a\nb\nc\nd");
+
+    assert_eq!(screen.text.scrollback_len(), 0);
+
+    screen.text.page_up();
+    assert_eq!(screen.text.view_offset(), 0);
+    screen.assert_view(&["c", "d"]);
+}
+
+#[test]
+fn lines_that_scroll_off_are_kept() {
+    let screen = screen_with_history(10);
+
+    assert_eq!(screen.text.scrollback_len(), 5);
+    screen.assert_grid(&["line5", "line6", "line7"]);
+    screen.assert_view(&["line5", "line6", "line7"]);
+}
+
+#[test]
+fn scrolling_the_view_shows_older_lines() {
+    let mut screen = screen_with_history(10);
+
+    screen.text.scroll_view_up(1);
+    screen.render();
+    screen.assert_view(&["line4", "line5", "line6"]);
+    screen.assert_pixels_match_text();
+
+    screen.text.page_up();
+    screen.render();
+    screen.assert_view(&["line1", "line2", "line3"]);
+    screen.assert_pixels_match_text();
+
+    // It stops at the oldest line
+    screen.text.page_up();
+    screen.render();
+    assert_eq!(screen.text.view_offset(), 5);
+    screen.assert_view(&["line0", "line1", "line2"]);
+    screen.assert_pixels_match_text();
+
+    // The live text hasn't changed
+    screen.assert_grid(&["line5", "line6", "line7"]);
+}
+
+#[test]
+fn scrolling_down_goes_back_to_the_live_text() {
+    let mut screen = screen_with_history(10);
+
+    screen.text.page_up();
+    screen.render();
+    screen.text.scroll_view_down(1);
+    screen.render();
+    screen.assert_view(&["line3", "line4", "line5"]);
+    screen.assert_pixels_match_text();
+
+    screen.text.page_down();
+    screen.render();
+    assert_eq!(screen.text.view_offset(), 0);
+    screen.assert_view(&["line5", "line6", "line7"]);
+    screen.assert_pixels_match_text();
+
+    screen.text.page_up();
+    screen.text.scroll_to_live();
+    assert_eq!(screen.text.view_offset(), 0);
+}
+
+#[test]
+fn the_oldest_lines_are_dropped_when_it_fills_up() {
+    let mut screen = screen_with_history(2);
+
+    assert_eq!(screen.text.scrollback_len(), 2);
+
+    screen.text.page_up();
+    screen.render();
+    screen.assert_view(&["line3", "line4", "line5"]);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn the_view_stays_put_while_more_is_written() {
+    let mut screen = screen_with_history(10);
+
+    screen.text.scroll_view_up(2);
+    screen.render();
+    screen.write("\nline8\nline9");
+
+    screen.assert_view(&["line3", "line4", "line5"]);
+    screen.assert_pixels_match_text();
+
+    // The live text carried on underneath
+    screen.text.scroll_to_live();
+    screen.render();
+    screen.assert_view(&["line7", "line8", "line9"]);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn writing_on_a_row_thats_in_view_shows_up() {
+    let mut screen = screen_with_history(10);
+
+    // The bottom row of the view is the top row of the live text
+    screen.text.scroll_view_up(2);
+    screen.render();
+    screen.write("\x1b[1;1HLINE");
+
+    screen.assert_view(&["line3", "line4", "LINE5"]);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn lines_keep_their_colours_in_the_scrollback() {
+    let mut screen = Screen::new(4, 2);
+    screen.text.set_scrollback(4);
+    screen.write("\x1b[31mred\x1b[0m\nb\nc");
+
+    screen.text.page_up();
+    screen.render();
+
+    screen.assert_view(&["red", "b"]);
+    assert_ne!(screen.text.visible_style_at(0, 0).foreground, Color::WHITE);
+    assert_eq!(screen.text.visible_style_at(0, 1).foreground, Color::WHITE);
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn cursor_is_hidden_while_scrolled_back() {
+    let mut screen = screen_with_history(10);
+    screen.text.set_cursor_shape(CursorShape::Block);
+    screen.text.set_cursor_visible(true);
+    screen.render();
+
+    screen.text.scroll_view_up(1);
+    assert!(!screen.text.cursor_showing());
+    screen.render();
+    screen.assert_pixels_match_text();
+
+    screen.text.scroll_to_live();
+    assert!(screen.text.cursor_showing());
+    screen.render();
+    screen.assert_pixels_match_text();
+}
+
+#[test]
+fn changing_the_size_throws_the_scrollback_away() {
+    let mut screen = screen_with_history(10);
+    screen.text.page_up();
+
+    screen.text.set_scrollback(20);
+
+    assert_eq!(screen.text.scrollback_len(), 0);
+    assert_eq!(screen.text.view_offset(), 0);
+}
@@ -211,43 +211,101 @@ unsafe impl x86_64::structures::paging::FrameAllocator<Size4KiB> for BootstrapFr
     }
 }
 
+/// What a mapped page is allowed to be used for. Pages are always readable
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PagePermissions {
+    pub writable: bool,
+    pub executable: bool
+}
+
 /// A trait to describe the interface by which the kernel can map pages into virtual memory. This
 /// means that the implementor must be able to get frames from somewhere (probably deffered to a
 /// FrameAllocator) and then map those frames into virtual memory by modifying a page table. It
 /// should work even if said page table is not active.
 pub trait MemoryMapper {
+    /// Whatever describes the address space being mapped into. For the bootloader and the kernel
+    /// this is a RecursivePageTable, but keeping it abstract means anything built on a mapper
+    /// (like the elf loader) can be run on the host against a fake address space
+    type PageTable;
+
     type MapErrorType;
     type UnmapErrorType;
 
     type MapAllocErrorType;
 
+    type ProtectErrorType;
+
     /// The Ok arm of the return type should probably have a different associated type but I don't
     /// know that should be so it is what it is
-    fn map(&mut self, page_table: &mut RecursivePageTable, page: VirtAddr, frame: PhysAddr) -> Result<(), Self::MapErrorType>;
+    fn map(&mut self, page_table: &mut Self::PageTable, page: VirtAddr, frame: PhysAddr) -> Result<(), Self::MapErrorType>;
 
     /// When it succeeds it should return the phyiscal address of associated frame so that it can
     /// be deallocated if needed.
-    fn unmap(&mut self, page_table: &mut RecursivePageTable, page: VirtAddr) -> Result<PhysAddr, Self::UnmapErrorType>;
+    fn unmap(&mut self, page_table: &mut Self::PageTable, page: VirtAddr) -> Result<PhysAddr, Self::UnmapErrorType>;
 
     /// Get one or more frames (presumably from a FrameAllocator) and map them to a specific
     /// location in virtual memory. I don't remember why this returns Ok(u64) but in the bootloader
     /// implementation I had that return back the start of the page
-    fn map_alloc(&mut self, page_table: &mut RecursivePageTable, page: VirtAddr, page_count: u32) -> Result<u64, Self::MapAllocErrorType>;
+    fn map_alloc(&mut self, page_table: &mut Self::PageTable, page: VirtAddr, page_count: u32) -> Result<u64, Self::MapAllocErrorType>;
+
+    /// Changes what `page_count` pages starting at `page` can be used for. The pages must already
+    /// be mapped
+    fn protect(&mut self, page_table: &mut Self::PageTable, page: VirtAddr, page_count: u32, permissions: PagePermissions) -> Result<(), Self::ProtectErrorType>;
+
+    /// Copies `data` into the address space starting at `address`. The default goes straight
+    /// through the virtual address, so it only works when `page_table` is the active one
+    ///
+    /// # Safety
+    /// Everything written to has to be mapped (writable) in `page_table`, and not be in use by
+    /// anything that would mind it changing
+    unsafe fn write(&mut self, _page_table: &mut Self::PageTable, address: VirtAddr, data: &[u8]) {
+        unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), address.as_mut_ptr::<u8>(), data.len()) }
+    }
+
+    /// Sets `size` bytes starting at `address` to `value`. The same as `write` otherwise
+    ///
+    /// # Safety
+    /// The same as `write`
+    unsafe fn fill(&mut self, _page_table: &mut Self::PageTable, address: VirtAddr, value: u8, size: usize) {
+        unsafe { core::ptr::write_bytes(address.as_mut_ptr::<u8>(), value, size) }
+    }
+
+    /// Copies from the address space starting at `address` into `buffer`. The default only works
+    /// when `page_table` is the active one, like `write`
+    ///
+    /// # Safety
+    /// Everything read from has to be mapped in `page_table`
+    unsafe fn read(&self, _page_table: &Self::PageTable, address: VirtAddr, buffer: &mut [u8]) {
+        unsafe { core::ptr::copy_nonoverlapping(address.as_ptr::<u8>(), buffer.as_mut_ptr(), buffer.len()) }
+    }
 }
 
-pub struct BootloaderMemoryMapper {
-    frame_allocator: BootstrapFrameManager
+/// Maps pages into a RecursivePageTable using frames from `Allocator`. The allocator has to be
+/// able to hand out single frames for new page tables as well as blocks for `map_alloc`
+pub struct BootloaderMemoryMapper<Allocator = BootstrapFrameManager> {
+    frame_allocator: Allocator
+}
+
+impl<Allocator> BootloaderMemoryMapper<Allocator> {
+    pub fn new(frame_allocator: Allocator) -> Self {
+        BootloaderMemoryMapper { frame_allocator }
+    }
 }
 
-impl MemoryMapper for BootloaderMemoryMapper {
+impl<Allocator> MemoryMapper for BootloaderMemoryMapper<Allocator>
+where Allocator: FrameAllocator + x86_64::structures::paging::FrameAllocator<Size4KiB> {
+    type PageTable = RecursivePageTable<'static>;
+
     type MapErrorType = &'static str;
     type UnmapErrorType = &'static str;
 
     // This will probably be an enum in the kernel implementation but since I'm taking the sum of
     // two identical types it's good enough to be &'static str
     type MapAllocErrorType = &'static str;
+
+    type ProtectErrorType = &'static str;
     
-    fn map(&mut self, page_table: &mut RecursivePageTable, page: VirtAddr, frame: PhysAddr) -> Result<(), &'static str> {
+    fn map(&mut self, page_table: &mut RecursivePageTable<'static>, page: VirtAddr, frame: PhysAddr) -> Result<(), &'static str> {
         unsafe { match page_table.map_to(
             Page::<Size4KiB>::from_start_address(page).or(Err("Page start not aligned correctly"))?,
             PhysFrame::from_start_address(frame).or(Err("Frame start not aligned correctly"))?,
@@ -261,30 +319,56 @@ impl MemoryMapper for BootloaderMemoryMapper {
         } }
     }
 
-    fn unmap(&mut self, page_table: &mut RecursivePageTable, page: VirtAddr) -> Result<PhysAddr, &'static str> {
+    fn unmap(&mut self, page_table: &mut RecursivePageTable<'static>, page: VirtAddr) -> Result<PhysAddr, &'static str> {
         match page_table.unmap(
             Page::<Size4KiB>::from_start_address(page).or(Err("Page start not aligned correctly"))?,
         ) {
             Ok((frame, flusher)) => {
                 flusher.flush();
-                Ok(frame.start_address().as_u64())
+                Ok(frame.start_address())
             },
             Err(_) => Err("Failed to unmap the page :/")
         }
     }
 
-    fn map_alloc(&mut self, page_table: &mut RecursivePageTable, page: VirtAddr, page_count: u32) -> Result<u64, &'static str> {
-        let frame_block = self.frame_allocator.allocate(page_count * PAGE_SIZE)?;
+    fn map_alloc(&mut self, page_table: &mut RecursivePageTable<'static>, page: VirtAddr, page_count: u32) -> Result<u64, &'static str> {
+        let frame_block = self.frame_allocator.allocate(page_count as usize * PAGE_SIZE)
+            .or(Err("Couldn't allocate frames for the pages"))?;
 
         for offset in 0..page_count as u64{
             self.map(
                 page_table,
                 page + offset * PAGE_SIZE as u64,
-                frame_block + offset * FRAME_SIZE
+                PhysAddr::new(frame_block as u64 + offset * FRAME_SIZE as u64)
             )?
         }
 
-        return Ok(page)
+        return Ok(page.as_u64())
+    }
+
+    fn protect(&mut self, page_table: &mut RecursivePageTable<'static>, page: VirtAddr, page_count: u32, permissions: PagePermissions) -> Result<(), &'static str> {
+        let mut flags = PageTableFlags::PRESENT;
+
+        if permissions.writable {
+            flags |= PageTableFlags::WRITABLE;
+        }
+
+        if !permissions.executable {
+            flags |= PageTableFlags::NO_EXECUTE;
+        }
+
+        for offset in 0..page_count as u64 {
+            unsafe { match page_table.update_flags(
+                Page::<Size4KiB>::from_start_address(page + offset * PAGE_SIZE as u64)
+                    .or(Err("Page start not aligned correctly"))?,
+                flags
+            ) {
+                Ok(flusher) => flusher.flush(),
+                Err(_) => return Err("Couldn't change the flags, probably because it isn't mapped")
+            } }
+        }
+
+        Ok(())
     }
 }
 
@@ -1,3 +1,161 @@
-fn main() {
-    println!("Hello, world!");
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use elf::inspect::{abi, Inspector};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: regulome inspect <elf file> [--symbols]");
+    ExitCode::FAILURE
+}
+
+fn object_type_name(object_type: u16) -> &'static str {
+    match object_type {
+        abi::ET_REL => "REL (relocatable)",
+        abi::ET_EXEC => "EXEC (executable)",
+        abi::ET_DYN => "DYN (shared object or pie)",
+        abi::ET_CORE => "CORE (core dump)",
+        _ => "unknown"
+    }
+}
+
+fn segment_type_name(segment_type: u32) -> String {
+    match segment_type {
+        abi::PT_NULL => "NULL".into(),
+        abi::PT_LOAD => "LOAD".into(),
+        abi::PT_DYNAMIC => "DYNAMIC".into(),
+        abi::PT_INTERP => "INTERP".into(),
+        abi::PT_NOTE => "NOTE".into(),
+        abi::PT_PHDR => "PHDR".into(),
+        abi::PT_TLS => "TLS".into(),
+        abi::PT_GNU_EH_FRAME => "GNU_EH_FRAME".into(),
+        abi::PT_GNU_STACK => "GNU_STACK".into(),
+        abi::PT_GNU_RELRO => "GNU_RELRO".into(),
+        abi::PT_GNU_PROPERTY => "GNU_PROPERTY".into(),
+        other => format!("{other:#x}")
+    }
+}
+
+fn segment_flags(flags: u32) -> String {
+    [(abi::PF_R, 'R'), (abi::PF_W, 'W'), (abi::PF_X, 'X')]
+        .iter()
+        .map(|&(flag, letter)| if flags & flag != 0 { letter } else { ' ' })
+        .collect()
+}
+
+fn section_type_name(section_type: u32) -> String {
+    match section_type {
+        abi::SHT_NULL => "NULL".into(),
+        abi::SHT_PROGBITS => "PROGBITS".into(),
+        abi::SHT_SYMTAB => "SYMTAB".into(),
+        abi::SHT_STRTAB => "STRTAB".into(),
+        abi::SHT_RELA => "RELA".into(),
+        abi::SHT_HASH => "HASH".into(),
+        abi::SHT_DYNAMIC => "DYNAMIC".into(),
+        abi::SHT_NOTE => "NOTE".into(),
+        abi::SHT_NOBITS => "NOBITS".into(),
+        abi::SHT_DYNSYM => "DYNSYM".into(),
+        abi::SHT_INIT_ARRAY => "INIT_ARRAY".into(),
+        abi::SHT_FINI_ARRAY => "FINI_ARRAY".into(),
+        abi::SHT_GNU_HASH => "GNU_HASH".into(),
+        other => format!("{other:#x}")
+    }
+}
+
+fn symbol_type_name(symbol_type: u8) -> &'static str {
+    match symbol_type {
+        abi::STT_NOTYPE => "NOTYPE",
+        abi::STT_OBJECT => "OBJECT",
+        abi::STT_FUNC => "FUNC",
+        abi::STT_SECTION => "SECTION",
+        abi::STT_FILE => "FILE",
+        abi::STT_TLS => "TLS",
+        abi::STT_GNU_IFUNC => "IFUNC",
+        _ => "?"
+    }
+}
+
+/// Prints a summary of an elf file, so images can be checked over before they get written to the
+/// disk
+fn inspect(path: &str, show_symbols: bool) -> ExitCode {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            return ExitCode::FAILURE
+        }
+    };
+
+    let inspector = match Inspector::new(&data) {
+        Ok(inspector) => inspector,
+        Err(_) => {
+            eprintln!("{path}: not an x86_64 ELF64 file");
+            return ExitCode::FAILURE
+        }
+    };
+
+    println!("Type:     {}", object_type_name(inspector.object_type()));
+    println!("Entry:    {:#x}", inspector.entry().as_u64());
+
+    match inspector.build_id() {
+        Some(build_id) => println!("Build-id: {build_id}"),
+        None => println!("Build-id: none")
+    }
+
+    println!();
+    println!("Segments:");
+    println!("  {:<14} {:>10} {:>18} {:>10} {:>10} {:<5} {:>8}", "Type", "Offset", "Address", "FileSize", "MemSize", "Flags", "Align");
+    for segment in inspector.segments() {
+        println!(
+            "  {:<14} {:>#10x} {:>#18x} {:>#10x} {:>#10x} {:<5} {:>#8x}",
+            segment_type_name(segment.segment_type),
+            segment.offset,
+            segment.address,
+            segment.file_size,
+            segment.memory_size,
+            segment_flags(segment.flags),
+            segment.alignment
+        );
+    }
+
+    println!();
+    println!("Sections:");
+    println!("  {:<24} {:<12} {:>18} {:>10} {:>10}", "Name", "Type", "Address", "Offset", "Size");
+    for section in inspector.sections() {
+        println!(
+            "  {:<24} {:<12} {:>#18x} {:>#10x} {:>#10x}",
+            section.name,
+            section_type_name(section.section_type),
+            section.address,
+            section.offset,
+            section.size
+        );
+    }
+
+    if show_symbols {
+        println!();
+        println!("Symbols:");
+        println!("  {:>18} {:>8} {:<8} Name", "Address", "Size", "Type");
+        for symbol in inspector.symbols().filter(|symbol| !symbol.name.is_empty()) {
+            println!(
+                "  {:>#18x} {:>8} {:<8} {}",
+                symbol.address,
+                symbol.size,
+                symbol_type_name(symbol.symbol_type),
+                symbol.name
+            );
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    match arguments.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["inspect", path] => inspect(path, false),
+        ["inspect", path, "--symbols"] | ["inspect", "--symbols", path] => inspect(path, true),
+        _ => usage()
+    }
 }